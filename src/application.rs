@@ -1,22 +1,52 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fmt::Debug;
-use std::io;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use crossterm::execute;
 use tui::backend::Backend;
 use tui::Terminal;
 
-use crate::file_item_list::file_item::FileItem;
+use crate::archive::{create_archive, extraction_dir, Archive, ArchiveKind};
+use crate::bookmark::{Bookmarks, BOOKMARKS_FILE_NAME};
+use crate::columns::{toggle_column, Column, ColumnKind};
+use crate::command::{command_description, COMMANDS};
+use crate::dir_size::DirSizes;
+use crate::file_item_list::file_item::{FileItem, SizeUnits};
 use crate::file_item_list::Kinds;
-use crate::input_ui::{init_input_area_terminal, start_user_input};
+use crate::frecency::{now_epoch_secs, FrecencyDb, FRECENCY_FILE_NAME};
+use crate::git_status::{run_git, GitStatus, GitWatcher};
+use crate::history::{DirHistory, HistoryEntry, RecentDirs, RECENT_DIRS_FILE_NAME};
+use crate::ignore_rules::IgnoreRules;
+use crate::input_ui::run_input_prompt;
+use crate::jobs::Jobs;
+use crate::key_notation::key_sequence_to_notation;
+use crate::key_trie::{KeyInput, KeyTrie, UserKeybinds};
+use crate::listing::Listings;
 use crate::load_config::{
-    load_user_config_file, multi_string_map_to_user_keyboad, SettingTheme, UserConfig, UserKeyCode,
-    UserKeybinds,
+    config_file_layers, load_user_config_file, user_config_file_path, ConfigWatcher, UserConfig,
+};
+use crate::mouse::{MouseLayout, MouseTarget, DOUBLE_CLICK_INTERVAL};
+use crate::path_process::{
+    create_symlink, get_data_directory_path, link_destinations, pathbuf_to_string_name,
 };
-use crate::path_process::pathbuf_to_string_name;
-use crate::state::StatefulDirectory;
+use crate::picker::{run_picker, run_picker_with_actions, Picker, PickerEvent};
+use crate::preview::Previewer;
+use crate::sftp::{host_root, SftpFs, SftpUrl};
+use crate::state::{SortKey, StatefulDirectory};
+use crate::theme::SettingTheme;
 use crate::ui::ui;
+use crate::vfs::{copy_items, LocalFs, Vfs};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
@@ -28,28 +58,297 @@ pub enum Mode {
 // TODO: Restrictions without reason, so think cost
 const DRAIN_SIZE: usize = 50;
 const MAX_HIST_SIZE: usize = 500;
-const MAX_FILE_NAME_SIZE: usize = 40;
+
+// The connection once it is made, and the URL to go to then
+type Connecting = (Receiver<Result<SftpFs, String>>, Option<SftpUrl>);
 
 // TODO: Do I have to load  use config in this struct?
 #[derive(Debug)]
 pub struct App {
-    directory_tabs: Vec<String>,
+    // the directory each tab shows
+    directory_tabs: Vec<PathBuf>,
+    // back/forward history of each tab, same order as directory_tabs
+    tab_histories: Vec<DirHistory>,
     tab_index: usize,
-    dir_map: HashMap<String, StatefulDirectory>,
+    // the listings by their full path, shared by the tabs showing them
+    dir_map: HashMap<PathBuf, StatefulDirectory>,
     command_history: Vec<String>,
+    recent_dirs: RecentDirs,
+    bookmarks: Bookmarks,
+    frecency: FrecencyDb,
     mode: Mode,
     config: UserConfig,
+    // `--config`, layered over the other config files again on reload
+    config_override: Option<PathBuf>,
+    theme_override: Option<SettingTheme>,
+    config_watcher: ConfigWatcher,
+    normal_keybinds: UserKeybinds,
+    input_keybinds: UserKeybinds,
+    stacker_keybinds: UserKeybinds,
+    // when the current unfinished key sequence was started, for the which-key popup
+    pending_since: Option<Instant>,
+    mouse_layout: MouseLayout,
+    // the last left click, to tell a double click
+    last_click: Option<(Instant, usize)>,
+    // the row a drag started on and the marks from before it
+    drag: Option<(usize, HashSet<PathBuf>)>,
+    // the default of new tabs, and the flag of each tab
+    show_hidden: bool,
+    tab_show_hidden: Vec<bool>,
+    ignore_rules: IgnoreRules,
+    git: GitWatcher,
+    // archives opened as directories, by the path of the archive
+    archives: HashMap<PathBuf, Arc<Archive>>,
+    // what is read from somewhere other than the local disk, by the
+    // directory it is shown at. Archives are in here too.
+    mounts: HashMap<PathBuf, Arc<dyn Vfs>>,
+    // connections to remote hosts, by `sftp://user@host`
+    hosts: HashMap<PathBuf, Arc<SftpFs>>,
+    // connections being made on a thread, by root
+    connecting: HashMap<PathBuf, Connecting>,
+    // remote directories being read on a thread
+    listings: Listings,
+    // None while the preview is hidden
+    preview: Option<Previewer>,
+    jobs: Jobs,
+    sort_key: SortKey,
+    // totals of directories, asked for by `calc_dir_size`, the `dir_sizes`
+    // option and the disk usage view
+    dir_sizes: DirSizes,
+    // the current listing is sorted by size with bars, like ncdu
+    disk_usage: bool,
 }
 
 impl App {
-    pub fn new() -> Self {
-        App {
+    pub fn new(config: UserConfig, config_override: Option<PathBuf>) -> Self {
+        let bookmarks_path = bookmarks_file_path(config_override.as_deref());
+        let (bookmarks, bookmarks_error) = Bookmarks::load(&bookmarks_path, config.bookmarks());
+        let (recent_dirs, recent_dirs_error) = load_recent_dirs();
+        let (frecency, frecency_error) = load_frecency_db();
+        let config_watcher = ConfigWatcher::new(config_file_layers(config_override.as_deref()));
+        let mut app = App {
             directory_tabs: Vec::new(),
+            tab_histories: Vec::new(),
             tab_index: 0,
             dir_map: HashMap::new(),
             command_history: Vec::new(),
+            recent_dirs,
+            bookmarks,
+            frecency,
             mode: Mode::Normal,
-            config: load_user_config_file(),
+            config,
+            config_override,
+            theme_override: None,
+            config_watcher,
+            normal_keybinds: UserKeybinds::new(),
+            input_keybinds: UserKeybinds::new(),
+            stacker_keybinds: UserKeybinds::new(),
+            pending_since: None,
+            mouse_layout: MouseLayout::default(),
+            last_click: None,
+            drag: None,
+            show_hidden: true,
+            tab_show_hidden: Vec::new(),
+            ignore_rules: IgnoreRules::default(),
+            git: GitWatcher::default(),
+            archives: HashMap::new(),
+            mounts: HashMap::new(),
+            hosts: HashMap::new(),
+            connecting: HashMap::new(),
+            listings: Listings::default(),
+            preview: None,
+            jobs: Jobs::default(),
+            sort_key: SortKey::default(),
+            dir_sizes: DirSizes::default(),
+            disk_usage: false,
+        };
+        app.show_hidden = app.config.show_hidden();
+        app.rebuild_keybinds();
+        app.rebuild_ignore_rules();
+        for e in [bookmarks_error, recent_dirs_error, frecency_error]
+            .into_iter()
+            .flatten()
+        {
+            app.push_command_log(e);
+        }
+        app
+    }
+
+    // `--theme` wins over the config, also after a reload
+    pub fn set_theme(&mut self, theme: SettingTheme) {
+        self.config.set_theme(theme.clone());
+        self.theme_override = Some(theme);
+    }
+
+    fn rebuild_keybinds(&mut self) {
+        self.normal_keybinds = self.normal_user_keybinds();
+        self.input_keybinds = self.input_user_keybinds();
+        self.stacker_keybinds = self.stacker_user_keybinds();
+    }
+
+    fn rebuild_ignore_rules(&mut self) {
+        let (rules, errors) = IgnoreRules::new(self.config.ignore_globs(), self.config.gitignore());
+        self.ignore_rules = rules;
+        for e in errors {
+            self.push_command_log(e);
+        }
+    }
+
+    // An invalid config is reported and the current one is kept.
+    pub fn reload_config(&mut self) {
+        match load_user_config_file(self.config_override.as_deref()) {
+            Ok(config) => {
+                self.config = config;
+                if let Some(theme) = self.theme_override.clone() {
+                    self.config.set_theme(theme);
+                }
+                self.rebuild_keybinds();
+                self.rebuild_ignore_rules();
+                self.refresh_hideable();
+                self.push_command_log("Config reloaded".to_string());
+            }
+            Err(e) => self.push_command_log(format!("Config not reloaded: {}", e)),
+        }
+    }
+
+    // Reload when `watch_config` is on and one of the config files was written
+    pub fn reload_config_if_changed(&mut self) {
+        if self.config.watch_config() && self.config_watcher.changed() {
+            self.reload_config();
+        }
+    }
+
+    // The default for tabs opened from now on
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+    }
+
+    fn crr_show_hidden(&self) -> bool {
+        self.tab_show_hidden
+            .get(self.tab_index)
+            .copied()
+            .unwrap_or(self.show_hidden)
+    }
+
+    // The directory of a tab is shared with the other tabs showing it, so it
+    // is filtered again for the tab whenever it is shown
+    fn sync_hidden(&mut self) {
+        let show = self.crr_show_hidden();
+        if let Some(dir) = self
+            .directory_tabs
+            .get(self.tab_index)
+            .and_then(|path| self.dir_map.get_mut(path))
+        {
+            dir.set_show_hidden(show);
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        let show = !self.crr_show_hidden();
+        if let Some(flag) = self.tab_show_hidden.get_mut(self.tab_index) {
+            *flag = show;
+        }
+        self.sync_hidden();
+        let msg = match show {
+            true => "Showing hidden items".to_string(),
+            false => format!("{} items hidden", self.hidden_count()),
+        };
+        self.push_command_log(msg);
+    }
+
+    // Items of the current directory that are left out
+    pub fn hidden_count(&self) -> usize {
+        self.peeking_selected_statefuldir().hidden_count()
+    }
+
+    // After the ignore rules changed
+    fn refresh_hideable(&mut self) {
+        for dir in self.dir_map.values_mut() {
+            let hideable = self.ignore_rules.hideable(dir.dir_path(), dir.all_items());
+            dir.set_hideable(hideable);
+        }
+    }
+
+    pub fn update_git_status(&mut self) {
+        let dir = self.crr_dir_path().to_path_buf();
+        // git only runs on this machine, and only when something shows it
+        if host_root(&dir).is_none() && self.shows_git() {
+            self.git.update(&dir);
+        }
+    }
+
+    // The GitStatus column is shown or the title has the branch
+    fn shows_git(&self) -> bool {
+        self.config.git_branch()
+            || self
+                .columns()
+                .iter()
+                .any(|c| c.kind() == ColumnKind::GitStatus && !c.is_hidden())
+    }
+
+    pub fn git_status_of(&self, path: &Path) -> Option<GitStatus> {
+        self.git.status_for(self.crr_dir_path())?.status_of(path)
+    }
+
+    // The branch of the current directory's repository with ahead/behind counts
+    pub fn git_branch_summary(&self) -> Option<String> {
+        if !self.config.git_branch() {
+            return None;
+        }
+        self.git
+            .status_for(self.crr_dir_path())
+            .map(|status| status.branch_summary())
+    }
+
+    // `git add` or `git restore --staged` the chosen items
+    pub fn git_stage(&mut self, stage: bool) {
+        let paths = self.chosen_paths();
+        if paths.is_empty() || self.refuse_remote() || self.refuse_read_only() {
+            return;
+        }
+        let args: &[&str] = match stage {
+            true => &["add"],
+            false => &["restore", "--staged"],
+        };
+        let verb = if stage { "Staged" } else { "Unstaged" };
+        match run_git(self.crr_dir_path(), args, &paths) {
+            Ok(()) => {
+                let noun = if paths.len() == 1 { "item" } else { "items" };
+                self.push_command_log(format!("{} {} {}", verb, paths.len(), noun))
+            }
+            Err(e) => self.push_command_log(e),
+        }
+        self.git.refresh();
+    }
+
+    // Selects the next item below the selection that git sees as changed,
+    // wrapping around
+    pub fn move_to_next_changed_file(&mut self) {
+        let dir = self.peeking_selected_statefuldir();
+        let items = dir.file_items_vec();
+        let start = dir.state_table().selected().map_or(0, |i| i + 1);
+        let next = (0..items.len())
+            .map(|i| (start + i) % items.len())
+            .find(|&i| {
+                self.git_status_of(items[i].path())
+                    .is_some_and(|status| status.is_changed())
+            });
+        match next {
+            Some(i) => self.peek_selected_statefuldir().select_index(Some(i)),
+            None => self.push_command_log("No changed files".to_string()),
+        }
+    }
+
+    pub fn set_sort_key(&mut self, key: SortKey) {
+        self.sort_key = key;
+    }
+
+    // Select the item at `path` in the current tab if it is listed there
+    pub fn select_path(&mut self, path: &std::path::Path) {
+        let dir = self.peek_selected_statefuldir();
+        let pos = dir.file_items_vec().iter().position(|x| x.path() == path);
+        if pos.is_some() {
+            dir.select_index(pos);
         }
     }
 
@@ -82,33 +381,319 @@ impl App {
         self.tab_index
     }
 
-    pub fn dirtab(&self) -> &Vec<String> {
+    pub fn dirtab(&self) -> &[PathBuf] {
         &self.directory_tabs
     }
 
+    // The mount `path` is in, or the local disk
+    fn vfs_for(&self, path: &Path) -> Arc<dyn Vfs> {
+        path.ancestors()
+            .find_map(|dir| self.mounts.get(dir))
+            .cloned()
+            .unwrap_or_else(|| Arc::new(LocalFs))
+    }
+
+    // Shows what `vfs` holds at `path` and below, instead of the local disk
+    pub fn mount(&mut self, path: PathBuf, vfs: Arc<dyn Vfs>) {
+        self.mounts.insert(path, vfs);
+    }
+
+    // Starts connecting to the host or opens the archive `path` is in, unless
+    // that was done already. A listing on a host that isn't connected yet is
+    // read again once it is.
+    fn open_mount_of(&mut self, path: &Path) -> Result<(), String> {
+        match host_root(path) {
+            Some(root) if self.connected_host(&root).is_none() => {
+                let url = root.to_string_lossy();
+                let url =
+                    SftpUrl::parse(&url).ok_or_else(|| format!("Not an sftp URL: {}", url))?;
+                self.start_connecting(url, false);
+                Ok(())
+            }
+            Some(_) => Ok(()),
+            None => self.open_archive_of(path),
+        }
+    }
+
+    // The connection to the host at `root`, shared by every tab. One that was
+    // lost is dropped, so that the next use connects again.
+    fn connected_host(&mut self, root: &Path) -> Option<Arc<SftpFs>> {
+        let fs = self.hosts.get(root)?.clone();
+        if fs.is_broken() {
+            self.hosts.remove(root);
+            self.mounts.remove(root);
+            self.push_command_log(format!("Lost the connection to {}", root.display()));
+            return None;
+        }
+        Some(fs)
+    }
+
+    // Connects on a thread, so a slow host doesn't hold up the listing. With
+    // `go`, `url` is opened once connected.
+    fn start_connecting(&mut self, url: SftpUrl, go: bool) {
+        let root = url.root();
+        if let Some((_, then)) = self.connecting.get_mut(&root) {
+            if go {
+                *then = Some(url);
+            }
+            return;
+        }
+        let key = self.config.ssh_key().map(Path::to_path_buf);
+        let (tx, rx) = channel();
+        let connect_url = url.clone();
+        thread::spawn(move || {
+            let _ = tx.send(SftpFs::connect(&connect_url, key.as_deref()));
+        });
+        self.connecting.insert(root, (rx, go.then_some(url)));
+    }
+
+    // Where `path` is shown when it is an sftp:// URL, the home directory when
+    // the URL has no path. None while the host is being connected to, it is
+    // opened once connected. Other paths are returned as they are.
+    pub fn resolve_remote(&mut self, path: PathBuf) -> Result<Option<PathBuf>, String> {
+        let raw = path.to_string_lossy().into_owned();
+        let url = match SftpUrl::parse(&raw) {
+            Some(url) => url,
+            None if host_root(&path).is_some() => {
+                return Err(format!("Not an sftp URL: {}", raw));
+            }
+            None => return Ok(Some(path)),
+        };
+        match self.connected_host(&url.root()) {
+            Some(fs) => Ok(Some(fs.shown_path_of(&url))),
+            None => {
+                self.start_connecting(url, true);
+                Ok(None)
+            }
+        }
+    }
+
+    // Finished connections are mounted, and what was waiting for them opened
+    // or read again
+    pub fn update_connections(&mut self) {
+        let mut finished = Vec::new();
+        self.connecting
+            .retain(|root, (rx, then)| match rx.try_recv() {
+                Ok(res) => {
+                    finished.push((root.clone(), res, then.take()));
+                    false
+                }
+                Err(TryRecvError::Empty) => true,
+                Err(TryRecvError::Disconnected) => false,
+            });
+        for (root, res, then) in finished {
+            let fs = match res {
+                Ok(fs) => Arc::new(fs),
+                Err(e) => {
+                    self.push_command_log(e);
+                    continue;
+                }
+            };
+            self.mount(root.clone(), fs.clone());
+            self.hosts.insert(root.clone(), fs.clone());
+            let waiting: Vec<PathBuf> = self
+                .dir_map
+                .values()
+                .map(|dir| dir.dir_path().to_path_buf())
+                .filter(|path| path.starts_with(&root))
+                .collect();
+            for path in waiting {
+                self.reload_dir(&path);
+            }
+            if let Some(url) = then {
+                self.jump_to_dir(fs.shown_path_of(&url));
+            }
+        }
+    }
+
+    // Opens the archive `path` is in, if it is in one that isn't open yet
+    fn open_archive_of(&mut self, path: &Path) -> Result<(), String> {
+        let archive_path = path.ancestors().find(|dir| {
+            self.archives.contains_key(*dir)
+                || (ArchiveKind::from_path(dir).is_some() && dir.is_file())
+        });
+        match archive_path {
+            Some(archive_path) if !self.archives.contains_key(archive_path) => {
+                let archive = Archive::open(archive_path)
+                    .map_err(|e| format!("{}: {}", archive_path.display(), e))?;
+                let archive = Arc::new(archive);
+                self.mount(archive_path.to_path_buf(), archive.clone());
+                self.archives.insert(archive_path.to_path_buf(), archive);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The directory holding the archive the current directory is in, or the
+    // current directory itself
+    pub fn crr_local_dir(&self) -> PathBuf {
+        let path = self.crr_dir_path();
+        path.ancestors()
+            .find(|dir| self.archives.contains_key(*dir))
+            .and_then(Path::parent)
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+
+    // Reports it when the current directory is on a remote host, for what only
+    // works on this machine
+    fn refuse_remote(&mut self) -> bool {
+        let remote = host_root(self.crr_dir_path()).is_some();
+        if remote {
+            let msg = format!("{} is on a remote host", self.crr_dir_path().display());
+            self.push_command_log(msg);
+        }
+        remote
+    }
+
+    // Reports it when the current directory can't be changed
+    fn refuse_read_only(&mut self) -> bool {
+        let read_only = self.peeking_selected_statefuldir().is_read_only();
+        if read_only {
+            let msg = format!("{} is read-only", self.crr_dir_path().display());
+            self.push_command_log(msg);
+        }
+        read_only
+    }
+
+    // A remote directory is listed empty and read on a thread, its items are
+    // put in by `update_listings`
+    fn new_statefuldir(&mut self, dir_path: PathBuf) -> StatefulDirectory {
+        let vfs = self.vfs_for(&dir_path);
+        if host_root(&dir_path).is_some() {
+            self.listings.request(dir_path.clone(), vfs.clone());
+            let empty = StatefulDirectory::from_items(dir_path, vfs, Vec::new(), Vec::new());
+            return self.arrange_statefuldir(empty);
+        }
+        let new_stateful_dir = StatefulDirectory::new(dir_path, vfs);
+        self.arrange_statefuldir(new_stateful_dir)
+    }
+
+    fn arrange_statefuldir(&self, mut new_stateful_dir: StatefulDirectory) -> StatefulDirectory {
+        let hideable = self
+            .ignore_rules
+            .hideable(new_stateful_dir.dir_path(), new_stateful_dir.all_items());
+        new_stateful_dir.set_hideable(hideable);
+        new_stateful_dir.set_show_hidden(self.crr_show_hidden());
+
+        // Sorted by name in each of the files and directories
+        new_stateful_dir.sort_by_key(self.sort_key);
+
+        // filtering selected the first item as it was read, sorting kept it
+        new_stateful_dir.select_top();
+        new_stateful_dir
+    }
+
     pub fn insert_new_statefuldir(&mut self, dir_path: PathBuf) {
-        let dir_name = pathbuf_to_string_name(&dir_path);
-        if let Entry::Vacant(item) = self.dir_map.entry(dir_name) {
-            let mut new_stateful_dir = StatefulDirectory::new(dir_path);
+        if let Err(e) = self.open_mount_of(&dir_path) {
+            self.push_command_log(e);
+        }
+        self.recent_dirs.push(&dir_path);
+        if !self.dir_map.contains_key(&dir_path) {
+            let new_stateful_dir = self.new_statefuldir(dir_path.clone());
+            self.report_read_errors(&new_stateful_dir);
+            self.dir_map.insert(dir_path, new_stateful_dir);
+        } else if host_root(&dir_path).is_some() {
+            // nothing tells when a remote directory changes, so it is read
+            // again whenever it is gone into
+            self.reload_dir(&dir_path);
+        }
+    }
+
+    // One line in the command bar however many entries failed
+    fn report_read_errors(&mut self, dir: &StatefulDirectory) {
+        let msg = match dir.read_errors() {
+            [] => return,
+            [e] => format!("Could not read {}", e),
+            [e, rest @ ..] => format!(
+                "Could not read {} entries in {}: {} and {} more",
+                rest.len() + 1,
+                dir.dir_path().display(),
+                e,
+                rest.len()
+            ),
+        };
+        self.push_command_log(msg);
+    }
 
-            // Sorted by name in each of the files and directories
-            new_stateful_dir.sort_by_kinds();
+    // Read the current directory again after something in it changed, keeping
+    // the selection and the marks of the items that are still there
+    pub fn reload_crr_dir(&mut self) {
+        let path = self.directory_tabs[self.tab_index].clone();
+        self.reload_dir(&path);
+    }
+
+    // Read the shown listings again when something was added to or removed
+    // from them elsewhere
+    pub fn reload_if_changed(&mut self) {
+        let keys: Vec<PathBuf> = self
+            .dir_map
+            .iter()
+            .filter(|(key, dir)| self.directory_tabs.contains(key) && dir.is_stale())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.reload_dir(&key);
+        }
+    }
+
+    // The open listing of `path`, if there is one
+    fn reload_dir(&mut self, path: &Path) {
+        if !self.dir_map.contains_key(path) {
+            return;
+        }
+        let new = self.new_statefuldir(path.to_path_buf());
+        // a remote listing is replaced when it has been read
+        if !self.listings.is_pending(path) {
+            self.replace_dir(path, new);
+        }
+    }
 
-            if !new_stateful_dir.is_selected() {
-                new_stateful_dir.select_top();
+    // Puts in remote listings that were read since the last frame
+    pub fn update_listings(&mut self) {
+        for (path, items, errors) in self.listings.poll() {
+            if !self.dir_map.contains_key(&path) {
+                continue;
             }
-            item.insert(new_stateful_dir);
+            let vfs = self.vfs_for(&path);
+            let dir = StatefulDirectory::from_items(path.clone(), vfs, items, errors);
+            let new = self.arrange_statefuldir(dir);
+            self.replace_dir(&path, new);
+        }
+    }
+
+    // Keeps the selection and the marks of the items that are still there
+    fn replace_dir(&mut self, path: &Path, mut new: StatefulDirectory) {
+        let old = match self.dir_map.remove(path) {
+            Some(old) => old,
+            None => return,
+        };
+        new.keep_marks(old.marked());
+        let selected = old.selecting_file_item().map(|item| item.file_name());
+        let pos = new
+            .file_items_vec()
+            .iter()
+            .position(|item| Some(item.file_name()) == selected);
+        match pos {
+            Some(_) => new.select_index(pos),
+            None => new.select_nearest(old.state_table().selected()),
         }
+        self.report_read_errors(&new);
+        self.dir_map.insert(path.to_path_buf(), new);
     }
 
-    pub fn push_new_dirname_to_dirtab(&mut self, dir_name: String) {
-        if !self.directory_tabs.contains(&dir_name) {
-            self.directory_tabs.push(dir_name)
+    pub fn push_new_dirtab(&mut self, dir_path: PathBuf) {
+        if !self.directory_tabs.contains(&dir_path) {
+            self.directory_tabs.push(dir_path);
+            self.tab_histories.push(DirHistory::new());
+            self.tab_show_hidden.push(self.show_hidden);
         }
     }
 
     pub fn next_dirtab(&mut self) {
         self.tab_index = (self.tab_index + 1) % self.directory_tabs.len();
+        self.sync_hidden();
     }
 
     pub fn move_to_next_file_item(&mut self) {
@@ -137,6 +722,7 @@ impl App {
         } else {
             self.tab_index = self.directory_tabs.len() - 1;
         }
+        self.sync_hidden();
     }
 
     pub fn limit_command_log(&mut self) {
@@ -159,6 +745,35 @@ impl App {
         self.config.theme()
     }
 
+    pub fn columns(&self) -> &[Column] {
+        self.config.columns()
+    }
+
+    pub fn date_format(&self) -> &str {
+        self.config.date_format()
+    }
+
+    pub fn toggle_column(&mut self, kind: ColumnKind) {
+        let shown = toggle_column(self.config.columns_mut(), kind);
+        let state = if shown { "shown" } else { "hidden" };
+        self.push_command_log(format!("Column {} {}", kind.header(), state));
+    }
+
+    // Every column, with a check mark on the shown ones
+    fn column_picker_entries(&self) -> Vec<(String, ColumnKind)> {
+        ColumnKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let shown = self
+                    .columns()
+                    .iter()
+                    .any(|c| c.kind() == kind && !c.is_hidden());
+                let check = if shown { "x" } else { " " };
+                (format!("[{}] {}", check, kind.header()), kind)
+            })
+            .collect()
+    }
+
     pub fn symbols(&self, item: &crate::load_config::FileItems) -> String {
         let config = &self.config;
         config.symbols().get(item).unwrap().to_owned()
@@ -167,40 +782,671 @@ impl App {
     pub fn move_to_child_dir(&mut self) {
         let select_dir = self.peek_selected_statefuldir();
         if let Some(file_item) = select_dir.selecting_file_item() {
-            match Kinds::classifiy_kinds(file_item.path(), file_item.meta()) {
-                Kinds::Directory(_) => {
-                    let dir_name = pathbuf_to_string_name(file_item.path());
-                    let new_dir_path = file_item.path().to_path_buf();
-                    self.insert_new_statefuldir(new_dir_path);
-                    let i = self.tab_index;
-                    let name = self.directory_tabs.get_mut(i);
-                    *name.unwrap() = dir_name;
+            // a link to a directory is opened as a directory at the link's path
+            if file_item.kinds().is_dir() {
+                let new_dir_path = file_item.path().to_path_buf();
+                self.move_to_dir(new_dir_path, None);
+            } else if ArchiveKind::from_path(file_item.path()).is_some()
+                && host_root(file_item.path()).is_none()
+            {
+                let archive_path = file_item.path().to_path_buf();
+                match self.open_archive_of(&archive_path) {
+                    Ok(()) => self.move_to_dir(archive_path, None),
+                    Err(e) => self.push_command_log(e),
                 }
-                Kinds::File(_) => {}
             }
         }
     }
 
     pub fn move_to_parent_dir(&mut self) {
         let selected_dir = self.peek_selected_statefuldir();
-        let dir_name = selected_dir.crr_dir_name();
+        let dir_name = selected_dir.dir_path().file_name().map(OsStr::to_os_string);
         let parent_path = selected_dir.crr_dir_parent_path().clone();
-        let parent_dir_name = pathbuf_to_string_name(&parent_path);
-        self.insert_new_statefuldir(parent_path);
-        let i = self.tab_index;
-        let name = self.directory_tabs.get_mut(i).unwrap();
-        *name = parent_dir_name;
+        if parent_path == selected_dir.dir_path() {
+            return;
+        }
 
         // select the position of crr dir name or select top
-        let dir_pos = self
-            .peek_selected_statefuldir()
-            .file_items_vec()
+        self.move_to_dir(parent_path, dir_name.as_deref());
+    }
+
+    fn crr_history_entry(&self) -> HistoryEntry {
+        let dir = self.peeking_selected_statefuldir();
+        let selected = dir
+            .selecting_file_item()
+            .map(|item| item.file_name().to_os_string());
+        HistoryEntry::new(dir.dir_path().to_path_buf(), selected)
+    }
+
+    // Show dir_path in the current tab and remember where we came from.
+    pub fn move_to_dir(&mut self, dir_path: PathBuf, selected: Option<&OsStr>) {
+        let leaving = self.crr_history_entry();
+        self.tab_histories[self.tab_index].visit(leaving);
+        self.change_crr_dir(dir_path, selected);
+    }
+
+    fn change_crr_dir(&mut self, dir_path: PathBuf, selected: Option<&OsStr>) {
+        self.frecency.visit(&dir_path, now_epoch_secs());
+        self.insert_new_statefuldir(dir_path.clone());
+        self.directory_tabs[self.tab_index] = dir_path;
+        self.sync_hidden();
+
+        if let Some(selected) = selected {
+            let pos = self
+                .peek_selected_statefuldir()
+                .file_items_vec()
+                .iter()
+                .position(|x| x.file_name() == selected);
+            if pos.is_some() {
+                self.peek_selected_statefuldir().select_index(pos);
+            }
+        }
+    }
+
+    pub fn move_back_in_history(&mut self) {
+        let current = self.crr_history_entry();
+        match self.tab_histories[self.tab_index].go_back(current) {
+            Some(entry) => {
+                let (path, selected) = entry.into_parts();
+                self.change_crr_dir(path, selected.as_deref());
+            }
+            None => self.push_command_log("No older directory in history".to_string()),
+        }
+    }
+
+    pub fn move_forward_in_history(&mut self) {
+        let current = self.crr_history_entry();
+        match self.tab_histories[self.tab_index].go_forward(current) {
+            Some(entry) => {
+                let (path, selected) = entry.into_parts();
+                self.change_crr_dir(path, selected.as_deref());
+            }
+            None => self.push_command_log("No newer directory in history".to_string()),
+        }
+    }
+
+    // The tab's own history first, then the directories of every session
+    fn history_picker_entries(&self) -> Vec<(String, HistoryEntry)> {
+        let history = &self.tab_histories[self.tab_index];
+        let mut entries: Vec<(String, HistoryEntry)> = Vec::new();
+        for entry in history.forward_entries().rev() {
+            let label = format!("forward {}", entry.path().display());
+            entries.push((label, entry.clone()));
+        }
+        for entry in history.back_entries().rev() {
+            let label = format!("back    {}", entry.path().display());
+            entries.push((label, entry.clone()));
+        }
+        for path in self.recent_dirs.dirs() {
+            let label = format!("recent  {}", path.display());
+            entries.push((label, HistoryEntry::new(path.clone(), None)));
+        }
+        entries
+    }
+
+    // Show dir_path in a tab, reusing the tab that already shows it.
+    pub fn jump_to_dir(&mut self, dir_path: PathBuf) {
+        self.frecency.visit(&dir_path, now_epoch_secs());
+        if let Some(i) = self.directory_tabs.iter().position(|p| p == &dir_path) {
+            self.tab_index = i;
+            self.sync_hidden();
+            return;
+        }
+
+        self.insert_new_statefuldir(dir_path.clone());
+        self.push_new_dirtab(dir_path);
+        self.tab_index = self.directory_tabs.len() - 1;
+        self.sync_hidden();
+    }
+
+    pub fn set_bookmark(&mut self, c: char) {
+        let path = self.crr_dir_path().to_path_buf();
+        self.push_command_log(format!("Bookmark '{}' -> {}", c, path.display()));
+        self.bookmarks.set(c, path);
+        self.save_bookmarks();
+    }
+
+    pub fn jump_to_bookmark(&mut self, c: char) {
+        let path = match self.bookmarks.get(c) {
+            Some(bookmark) => bookmark.path().to_path_buf(),
+            None => {
+                self.push_command_log(format!("No bookmark '{}'", c));
+                return;
+            }
+        };
+        // looked up where it is, on a host it is gone to once connected
+        let path = match self.resolve_remote(path) {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(e) => {
+                self.push_command_log(e);
+                return;
+            }
+        };
+        if let Err(e) = self.open_mount_of(&path) {
+            self.push_command_log(e);
+            return;
+        }
+        // a remote one is read on a thread, which says if it is gone
+        let vfs = self.vfs_for(&path);
+        if host_root(&path).is_some() || vfs.stat(&path).is_ok_and(|item| item.kinds().is_dir()) {
+            self.jump_to_dir(path);
+        } else {
+            self.push_command_log(format!("{} no longer exists", path.display()));
+        }
+    }
+
+    pub fn rename_bookmark(&mut self, c: char, name: String) {
+        if self.bookmarks.rename(c, name) {
+            self.save_bookmarks();
+        }
+    }
+
+    pub fn delete_bookmark(&mut self, c: char) {
+        if self.bookmarks.remove(c).is_some() {
+            self.save_bookmarks();
+        }
+    }
+
+    fn save_bookmarks(&mut self) {
+        let path = bookmarks_file_path(self.config_override.as_deref());
+        if let Err(e) = self.bookmarks.save(&path) {
+            self.push_command_log(format!("Failed to save bookmarks: {}", e));
+        }
+    }
+
+    // `z foo bar` in the command line
+    pub fn jump_by_frecency(&mut self, fragments: &[&str]) {
+        let now = now_epoch_secs();
+        match self.frecency.query(fragments, self.crr_dir_path(), now) {
+            Some(path) => self.move_to_dir(path, None),
+            None => self.push_command_log(format!("No match for {}", fragments.join(" "))),
+        }
+    }
+
+    pub fn import_frecency_db(&mut self, path: &str) {
+        match self.frecency.import(std::path::Path::new(path)) {
+            Ok(n) => self.push_command_log(format!("Imported {} directories", n)),
+            Err(e) => self.push_command_log(format!("Failed to import {}: {}", path, e)),
+        }
+    }
+
+    fn frecency_picker_entries(&self) -> Vec<(String, PathBuf)> {
+        self.frecency
+            .ranked(now_epoch_secs())
+            .into_iter()
+            .map(|(score, e)| {
+                let label = format!("{:>8.1}  {}", score, e.path().display());
+                (label, e.path().to_path_buf())
+            })
+            .collect()
+    }
+
+    pub fn save_session_data(&self) -> io::Result<()> {
+        match get_data_directory_path() {
+            Some(dir) => {
+                self.recent_dirs.save(&dir.join(RECENT_DIRS_FILE_NAME))?;
+                self.frecency.save(&dir.join(FRECENCY_FILE_NAME))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn toggle_mark(&mut self) {
+        let dir = self.peek_selected_statefuldir();
+        dir.toggle_mark_selected();
+        dir.select_next();
+    }
+
+    // Go where the selected link points: into a directory, or to the directory
+    // that holds a file with the file selected
+    pub fn follow_symlink(&mut self) {
+        if self.refuse_remote() {
+            return;
+        }
+        let item = match self.peeking_selected_statefuldir().selecting_file_item() {
+            Some(item) => item,
+            None => return,
+        };
+        let target = match item.kinds().link_target() {
+            Some(target) => target,
+            None => {
+                let msg = format!("{} is not a symlink", item.name());
+                self.push_command_log(msg);
+                return;
+            }
+        };
+        let link_dir = item.path().parent().unwrap_or(Path::new("/"));
+        let resolved = match link_dir.join(target.path()).canonicalize() {
+            Ok(path) => path,
+            Err(e) => {
+                let msg = format!("{}: {}", target.path().display(), e);
+                self.push_command_log(msg);
+                return;
+            }
+        };
+        if resolved.is_dir() {
+            self.move_to_dir(resolved, None);
+        } else if let Some(parent) = resolved.parent() {
+            let parent = parent.to_path_buf();
+            self.move_to_dir(parent, resolved.file_name());
+        }
+    }
+
+    // Links to the chosen items at `dest`, relative to the current directory
+    pub fn link_chosen_items(&mut self, dest: &str, hard: bool) {
+        if self.refuse_remote() || self.refuse_read_only() {
+            return;
+        }
+        let sources = self.chosen_paths();
+        let dest = self.crr_dir_path().join(dest);
+        let links = match link_destinations(&sources, &dest) {
+            Ok(links) => links,
+            Err(e) => {
+                self.push_command_log(e);
+                return;
+            }
+        };
+        let mut errors = Vec::new();
+        for (source, link) in sources.iter().zip(links.iter()) {
+            let res = match hard {
+                true => std::fs::hard_link(source, link),
+                false => create_symlink(source, link),
+            };
+            if let Err(e) = res {
+                errors.push(format!("{}: {}", link.display(), e));
+            }
+        }
+        self.reload_crr_dir();
+        let made = sources.len() - errors.len();
+        let msg = match errors.first() {
+            None => format!("Linked {} item(s)", made),
+            Some(e) => format!("Linked {} item(s), {} failed: {}", made, errors.len(), e),
+        };
+        self.push_command_log(msg);
+    }
+
+    // What the selected link points to, as stored in the link
+    pub fn selected_link_target(&self) -> Option<PathBuf> {
+        let item = self.peeking_selected_statefuldir().selecting_file_item()?;
+        item.kinds()
+            .link_target()
+            .map(|target| target.path().to_path_buf())
+    }
+
+    // Replaces the selected link with one to `target`, or puts the old one back
+    pub fn retarget_selected_link(&mut self, target: &str) {
+        if self.refuse_remote() || self.refuse_read_only() {
+            return;
+        }
+        let old = match self.selected_link_target() {
+            Some(old) => old,
+            None => return,
+        };
+        let link = match self.peeking_selected_statefuldir().selecting_file_item() {
+            Some(item) => item.path().to_path_buf(),
+            None => return,
+        };
+        let res = std::fs::remove_file(&link).and_then(|_| {
+            create_symlink(Path::new(target), &link).inspect_err(|_| {
+                let _ = create_symlink(&old, &link);
+            })
+        });
+        match res {
+            Ok(()) => self.push_command_log(format!("{} -> {}", link.display(), target)),
+            Err(e) => self.push_command_log(format!("{}: {}", link.display(), e)),
+        }
+        self.reload_crr_dir();
+    }
+
+    // The directories of the other tabs that can be written to
+    pub fn other_tab_dirs(&self) -> Vec<PathBuf> {
+        self.directory_tabs
             .iter()
-            // .inspect(|x| println!("{:?}", x.name() == dir_name))
-            .position(|x| x.name() == dir_name);
+            .enumerate()
+            .filter(|(i, _)| *i != self.tab_index)
+            .filter_map(|(_, path)| self.dir_map.get(path))
+            .filter(|dir| !dir.is_read_only())
+            .map(|dir| dir.dir_path().to_path_buf())
+            .collect()
+    }
+
+    // Where the chosen entries of an archive can be extracted to: the
+    // directories of the other tabs, then the one holding the archive
+    pub fn extract_destinations(&self) -> Vec<PathBuf> {
+        let mut dests = self.other_tab_dirs();
+        dests.push(self.crr_local_dir());
+        dests.dedup();
+        dests
+    }
 
-        let state_dir = self.peek_selected_statefuldir();
-        state_dir.select_index(dir_pos);
+    pub fn extract_chosen_entries(&mut self, dest: &Path) {
+        let dir = self.crr_dir_path();
+        let archive = match dir.ancestors().find_map(|a| self.archives.get(a)) {
+            Some(archive) => archive.clone(),
+            None => {
+                let msg = format!("{} is not in an archive", dir.display());
+                self.push_command_log(msg);
+                return;
+            }
+        };
+        let paths = self.chosen_paths();
+        let dest = dest.to_path_buf();
+        let title = format!("Extracting to {}", dest.display());
+        self.jobs.spawn(title, dest.clone(), move |progress| {
+            archive
+                .extract(&paths, &dest, &mut |done, total| {
+                    progress.report(done, total)
+                })
+                .map(|n| format!("Extracted {} file(s) to {}", n, dest.display()))
+                .map_err(|e| e.to_string())
+        });
+    }
+
+    // Unpacks the selected archive into a new directory next to it
+    pub fn extract_selected_archive(&mut self) {
+        let path = match self.peeking_selected_statefuldir().selecting_file_item() {
+            Some(item) => item.path().to_path_buf(),
+            None => return,
+        };
+        if ArchiveKind::from_path(&path).is_none() {
+            let msg = format!("{} is not an archive", path.display());
+            self.push_command_log(msg);
+            return;
+        }
+        if self.refuse_remote() || self.refuse_read_only() {
+            return;
+        }
+        let dest = extraction_dir(&path);
+        let refresh = self.crr_dir_path().to_path_buf();
+        let title = format!("Unpacking {}", pathbuf_to_string_name(&path));
+        self.jobs.spawn(title, refresh, move |progress| {
+            let archive = Archive::open(&path).map_err(|e| e.to_string())?;
+            let res = fs::create_dir(&dest).and_then(|_| {
+                archive.extract_all(&dest, &mut |done, total| progress.report(done, total))
+            });
+            match res {
+                Ok(n) => Ok(format!("Unpacked {} file(s) into {}", n, dest.display())),
+                Err(e) => Err(e.to_string()),
+            }
+        });
+    }
+
+    // Packs the chosen items into `name` in the current directory
+    pub fn compress_chosen_items(&mut self, name: &str) {
+        if self.refuse_remote() || self.refuse_read_only() {
+            return;
+        }
+        let sources = self.chosen_paths();
+        let dest = self.crr_dir_path().join(name);
+        let refresh = self.crr_dir_path().to_path_buf();
+        let title = format!("Packing {}", name);
+        self.jobs.spawn(title, refresh, move |progress| {
+            create_archive(&sources, &dest, &mut |done, total| {
+                progress.report(done, total)
+            })
+            .map(|(n, skipped)| {
+                let msg = format!("Packed {} file(s) into {}", n, dest.display());
+                with_left_out(msg, &skipped)
+            })
+            .map_err(|e| e.to_string())
+        });
+    }
+
+    // A name for the archive of the chosen items: the item's own name, or the
+    // current directory's for several
+    fn default_archive_name(&self) -> String {
+        let paths = self.chosen_paths();
+        let base = match paths.as_slice() {
+            [one] => one.as_path(),
+            _ => self.crr_dir_path(),
+        };
+        format!("{}.zip", pathbuf_to_string_name(base))
+    }
+
+    // Copies the chosen items into `dest` in the background, also between the
+    // local disk and a remote host
+    pub fn copy_chosen_items(&mut self, dest: &Path) {
+        let sources = self.chosen_paths();
+        if sources.is_empty() {
+            return;
+        }
+        let from = self.vfs_for(self.crr_dir_path());
+        let to = self.vfs_for(dest);
+        let dest = dest.to_path_buf();
+        let title = format!("Copying to {}", dest.display());
+        self.jobs.spawn(title, dest.clone(), move |progress| {
+            copy_items(&*from, &sources, &*to, &dest, &mut |done, total| {
+                progress.report(done, total)
+            })
+            .map(|(n, skipped)| {
+                let msg = format!("Copied {} file(s) to {}", n, dest.display());
+                with_left_out(msg, &skipped)
+            })
+            .map_err(|e| e.to_string())
+        });
+    }
+
+    pub fn selected_file_name(&self) -> Option<String> {
+        let item = self.peeking_selected_statefuldir().selecting_file_item()?;
+        Some(item.file_name().to_string_lossy().into_owned())
+    }
+
+    // Gives the selected item the name `name` in the same directory
+    pub fn rename_selected(&mut self, name: &str) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let from = match self.peeking_selected_statefuldir().selecting_file_item() {
+            Some(item) => item.path().to_path_buf(),
+            None => return,
+        };
+        if name.is_empty() || name == "." || name == ".." || name.contains(MAIN_SEPARATOR) {
+            self.push_command_log(format!("Not a file name: {}", name));
+            return;
+        }
+        let to = from.with_file_name(name);
+        if to == from {
+            return;
+        }
+        let vfs = self.vfs_for(&from);
+        if host_root(&from).is_some() {
+            let dir = self.crr_dir_path().to_path_buf();
+            let title = format!("Renaming {}", from.display());
+            let name = name.to_string();
+            self.jobs.spawn(title, dir, move |_| {
+                rename_item(&*vfs, &from, &to)
+                    .map(|()| format!("Renamed {} to {}", from.display(), name))
+            });
+            return;
+        }
+        match rename_item(&*vfs, &from, &to) {
+            Ok(()) => {
+                self.push_command_log(format!("Renamed {} to {}", from.display(), name));
+                self.reload_crr_dir();
+                self.select_path(&to);
+            }
+            Err(e) => self.push_command_log(e),
+        }
+    }
+
+    // Directories are deleted with everything in them
+    pub fn delete_chosen_items(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
+        let paths = self.chosen_paths();
+        let dir = self.crr_dir_path().to_path_buf();
+        let vfs = self.vfs_for(&dir);
+        if host_root(&dir).is_some() {
+            let title = format!("Deleting in {}", dir.display());
+            self.jobs
+                .spawn(title, dir, move |_| Ok(remove_items(&*vfs, &paths)));
+            return;
+        }
+        let msg = remove_items(&*vfs, &paths);
+        self.reload_crr_dir();
+        self.push_command_log(msg);
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.preview = match self.preview {
+            Some(_) => None,
+            None => Some(Previewer::default()),
+        };
+    }
+
+    // Starts reading the selected item when the preview is shown
+    pub fn update_preview(&mut self) {
+        if self.preview.is_none() {
+            return;
+        }
+        let selected = self
+            .peeking_selected_statefuldir()
+            .selecting_file_item()
+            .map(|item| (item.path().to_path_buf(), item.kinds().is_dir()));
+        let vfs = self.vfs_for(self.crr_dir_path());
+        if let Some(preview) = self.preview.as_mut() {
+            match &selected {
+                Some((path, is_dir)) => preview.update(Some(path), *is_dir, vfs),
+                None => preview.update(None, false, vfs),
+            }
+        }
+    }
+
+    pub fn preview(&self) -> Option<&Previewer> {
+        self.preview.as_ref()
+    }
+
+    // Ended jobs are reported and what they changed is read again
+    pub fn update_jobs(&mut self) {
+        for (msg, dir) in self.jobs.poll() {
+            self.push_command_log(msg);
+            self.reload_dir(&dir);
+        }
+    }
+
+    pub fn job_summary(&self) -> Option<String> {
+        self.jobs
+            .summary()
+            .or_else(|| {
+                let root = self.connecting.keys().next()?;
+                Some(format!("Connecting to {}", root.display()))
+            })
+            .or_else(|| {
+                let dir = self.listings.reading()?;
+                Some(format!("Reading {}", dir.display()))
+            })
+            .or_else(|| {
+                self.dir_sizes
+                    .is_busy()
+                    .then(|| "Adding up directory sizes".to_string())
+            })
+    }
+
+    // Picks up finished totals, asks for the directories of the current
+    // listing when they are wanted, and keeps the disk usage view in order
+    pub fn update_dir_sizes(&mut self) {
+        self.dir_sizes.poll();
+        if !self.config.dir_sizes() && !self.disk_usage {
+            return;
+        }
+        let vfs = self.vfs_for(self.crr_dir_path());
+        let selected_tab = &self.directory_tabs[self.tab_index];
+        if let Some(dir) = self.dir_map.get_mut(selected_tab) {
+            for item in dir.all_items() {
+                self.dir_sizes.request(item, vfs.clone());
+            }
+            if self.disk_usage {
+                let sizes = &self.dir_sizes;
+                dir.sort_by_usage(|item| usage_of(sizes, item));
+            }
+        }
+    }
+
+    // The total of a directory when it is known
+    pub fn dir_size_of(&self, item: &FileItem) -> Option<u64> {
+        self.dir_sizes.get(item)
+    }
+
+    // What an item takes up, the length of a file or the total of a directory
+    pub fn usage_of(&self, item: &FileItem) -> Option<u64> {
+        usage_of(&self.dir_sizes, item)
+    }
+
+    pub fn is_adding_up(&self, path: &Path) -> bool {
+        self.dir_sizes.is_pending(path)
+    }
+
+    // Adds up the marked directories (or the selected one) again, also when
+    // their total is known
+    pub fn calc_chosen_dir_sizes(&mut self) {
+        let chosen: HashSet<PathBuf> = self.chosen_paths().into_iter().collect();
+        let vfs = self.vfs_for(self.crr_dir_path());
+        let dirs: Vec<FileItem> = self
+            .peeking_selected_statefuldir()
+            .all_items()
+            .iter()
+            .filter(|item| chosen.contains(item.path()))
+            .filter(|item| matches!(item.kinds(), Kinds::Directory(_)))
+            .cloned()
+            .collect();
+        if dirs.is_empty() {
+            self.push_command_log("No directory to add up".to_string());
+            return;
+        }
+        for item in &dirs {
+            self.dir_sizes.refresh(item, vfs.clone());
+        }
+    }
+
+    pub fn toggle_disk_usage(&mut self) {
+        self.disk_usage = !self.disk_usage;
+        if !self.disk_usage {
+            let key = self.sort_key;
+            self.dir_map
+                .values_mut()
+                .for_each(|dir| dir.sort_by_key(key));
+        }
+    }
+
+    pub fn disk_usage(&self) -> bool {
+        self.disk_usage
+    }
+
+    // From the `format` of the size column
+    pub fn size_units(&self) -> SizeUnits {
+        self.columns()
+            .iter()
+            .find(|column| column.kind() == ColumnKind::Size)
+            .map(Column::size_units)
+            .unwrap_or_default()
+    }
+
+    // Marked items of the current tab, or the selected item if nothing is marked
+    pub fn chosen_paths(&self) -> Vec<PathBuf> {
+        let dir = self.peeking_selected_statefuldir();
+        let marked = dir.marked_paths();
+        if !marked.is_empty() {
+            return marked;
+        }
+        dir.selecting_file_item()
+            .map(|item| vec![item.path().to_path_buf()])
+            .unwrap_or_default()
+    }
+
+    // The chosen paths that are on the disk, for what runs outside: an entry
+    // inside an archive or on a remote host isn't there
+    pub fn chosen_disk_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.chosen_paths();
+        paths.retain(|path| {
+            let in_archive = path
+                .ancestors()
+                .skip(1)
+                .any(|dir| self.archives.contains_key(dir));
+            !in_archive && host_root(path).is_none()
+        });
+        paths
     }
 
     fn move_to_top_of_file_item(&mut self) {
@@ -211,105 +1457,640 @@ impl App {
         self.peek_selected_statefuldir().select_bottom();
     }
 
+    fn move_to_nth_file_item(&mut self, n: usize) {
+        let dir = self.peek_selected_statefuldir();
+        let len = dir.file_items_vec().len();
+        if len > 0 {
+            dir.select_index(Some(n.saturating_sub(1).min(len - 1)));
+        }
+    }
+
+    fn crr_keybinds_mut(&mut self) -> &mut UserKeybinds {
+        match self.mode {
+            Mode::Normal => &mut self.normal_keybinds,
+            Mode::Input => &mut self.input_keybinds,
+            Mode::Stacker => &mut self.stacker_keybinds,
+        }
+    }
+
+    pub fn crr_keybinds(&self) -> &UserKeybinds {
+        match self.mode {
+            Mode::Normal => &self.normal_keybinds,
+            Mode::Input => &self.input_keybinds,
+            Mode::Stacker => &self.stacker_keybinds,
+        }
+    }
+
+    pub fn mouse_layout_mut(&mut self) -> &mut MouseLayout {
+        &mut self.mouse_layout
+    }
+
+    // Moves without wrapping around, unlike the keys
+    fn scroll_selection(&mut self, down: bool) {
+        let dir = self.peek_selected_statefuldir();
+        let len = dir.file_items_vec().len();
+        if len == 0 {
+            return;
+        }
+        let i = dir.state_table().selected().unwrap_or(0);
+        let i = match down {
+            true => (i + MOUSE_SCROLL_LINES).min(len - 1),
+            false => i.saturating_sub(MOUSE_SCROLL_LINES),
+        };
+        dir.select_index(Some(i));
+    }
+
+    fn click_row(&mut self, i: usize) {
+        let now = Instant::now();
+        let double = matches!(self.last_click,
+            Some((at, row)) if row == i && now.duration_since(at) <= DOUBLE_CLICK_INTERVAL);
+        let dir = self.peek_selected_statefuldir();
+        dir.select_index(Some(i));
+        let marked = dir.marked().clone();
+        if double {
+            self.last_click = None;
+            self.move_to_child_dir();
+        } else {
+            self.last_click = Some((now, i));
+            self.drag = Some((i, marked));
+        }
+    }
+
+    fn drag_to_row(&mut self, i: usize) {
+        if let Some((start, base)) = self.drag.take() {
+            let dir = self.peek_selected_statefuldir();
+            dir.mark_range(&base, start, i);
+            dir.select_index(Some(i));
+            self.drag = Some((start, base));
+        }
+    }
+
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if !self.config.mouse() {
+            return;
+        }
+        let target = self.mouse_layout.target(event.column, event.row);
+        match (event.kind, target) {
+            (MouseEventKind::ScrollDown, _) => self.scroll_selection(true),
+            (MouseEventKind::ScrollUp, _) => self.scroll_selection(false),
+            (MouseEventKind::Down(MouseButton::Left), Some(MouseTarget::Row(i))) => {
+                self.click_row(i)
+            }
+            (MouseEventKind::Down(MouseButton::Left), Some(MouseTarget::Tab(i)))
+                if i < self.directory_tabs.len() =>
+            {
+                self.tab_index = i;
+                self.sync_hidden();
+            }
+            (MouseEventKind::Down(MouseButton::Left), Some(MouseTarget::Path(path))) => {
+                // select the directory we came from
+                let crr = self.crr_dir_path().to_path_buf();
+                if path != crr {
+                    let child = crr
+                        .strip_prefix(&path)
+                        .ok()
+                        .and_then(|rest| rest.components().next())
+                        .map(|c| c.as_os_str().to_os_string());
+                    self.move_to_dir(path, child.as_deref());
+                }
+            }
+            (MouseEventKind::Drag(MouseButton::Left), Some(MouseTarget::Row(i))) => {
+                self.drag_to_row(i)
+            }
+            (MouseEventKind::Up(MouseButton::Left), _) => self.drag = None,
+            _ => {}
+        }
+    }
+
+    // One line per command with all of its keys, in the order of COMMANDS.
+    // Commands that don't exist come last, so a typo is easy to spot.
+    fn help_entries(&self) -> Vec<String> {
+        let mut by_command: Vec<(String, Vec<String>)> = Vec::new();
+        for (keys, cmd) in self.crr_keybinds().trie().bindings() {
+            let keys = key_sequence_to_notation(&keys);
+            match by_command.iter_mut().find(|(c, _)| *c == cmd) {
+                Some((_, all)) => all.push(keys),
+                None => by_command.push((cmd, vec![keys])),
+            }
+        }
+        let order = |cmd: &str| COMMANDS.iter().position(|(c, _)| *c == cmd);
+        by_command.sort_by_key(|(cmd, _)| order(cmd).unwrap_or(usize::MAX));
+        by_command
+            .into_iter()
+            .map(|(cmd, keys)| {
+                let description = command_description(&cmd).unwrap_or("unknown command");
+                format!("{:<28} {:<20} {}", cmd, keys.join(", "), description)
+            })
+            .collect()
+    }
+
+    // The popup shows up once a sequence has been pending for the configured delay
+    pub fn which_key_visible(&self) -> bool {
+        match (self.pending_since, self.config.which_key_delay()) {
+            (Some(since), Some(delay)) => since.elapsed() >= delay,
+            _ => false,
+        }
+    }
+
+    // (keys, command, description) for every way to finish the pending sequence
+    pub fn which_key_entries(&self) -> Vec<(String, String, &'static str)> {
+        let keybinds = self.crr_keybinds();
+        keybinds
+            .trie()
+            .get(keybinds.pending_keys())
+            .map(|node| node.bindings())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(keys, cmd)| {
+                let description = command_description(&cmd).unwrap_or("");
+                (key_sequence_to_notation(&keys), cmd, description)
+            })
+            .collect()
+    }
+
+    fn update_pending_since(&mut self) {
+        if self.crr_keybinds().pending_keys().is_empty() {
+            self.pending_since = None;
+        } else if self.pending_since.is_none() {
+            self.pending_since = Some(Instant::now());
+        }
+    }
+
+    // Wake up in time to draw the which-key popup
+    fn poll_timeout(&self) -> Duration {
+        let previewing = self.preview.as_ref().is_some_and(|p| p.is_pending());
+        let busy = !self.jobs.is_empty()
+            || !self.connecting.is_empty()
+            || self.listings.reading().is_some();
+        if busy || previewing || self.dir_sizes.is_busy() {
+            return JOB_POLL_INTERVAL;
+        }
+        match (self.pending_since, self.config.which_key_delay()) {
+            (Some(since), Some(delay)) if since.elapsed() < delay => {
+                (delay - since.elapsed()).min(CONFIG_WATCH_INTERVAL)
+            }
+            _ => CONFIG_WATCH_INTERVAL,
+        }
+    }
+
     fn normal_user_keybinds(&self) -> UserKeybinds {
         let keybind = self.config.normal_keybindings_map();
-        let keymap = multi_string_map_to_user_keyboad(&keybind);
-        UserKeybinds::new()
-            .make_single_keybinds(keymap.clone())
-            .make_multiple_keybinds(keymap)
+        // the config was checked when it was loaded
+        UserKeybinds::from_trie(KeyTrie::from_keybinds(&keybind).unwrap_or_default())
     }
 
     fn input_user_keybinds(&self) -> UserKeybinds {
         let keybind = self.config.input_keybindings_map();
-        let keymap = multi_string_map_to_user_keyboad(&keybind);
-        UserKeybinds::new()
-            .make_single_keybinds(keymap.clone())
-            .make_multiple_keybinds(keymap)
+        // the config was checked when it was loaded
+        UserKeybinds::from_trie(KeyTrie::from_keybinds(&keybind).unwrap_or_default())
     }
 
     fn stacker_user_keybinds(&self) -> UserKeybinds {
         let keybind = self.config.stacker_keybindings_map();
-        let keymap = multi_string_map_to_user_keyboad(&keybind);
-        UserKeybinds::new()
-            .make_single_keybinds(keymap.clone())
-            .make_multiple_keybinds(keymap)
+        // the config was checked when it was loaded
+        UserKeybinds::from_trie(KeyTrie::from_keybinds(&keybind).unwrap_or_default())
+    }
+}
+
+fn usage_of(sizes: &DirSizes, item: &FileItem) -> Option<u64> {
+    match item.kinds() {
+        Kinds::Directory(_) => sizes.get(item),
+        _ => item.meta().map(|meta| meta.len),
+    }
+}
+
+// A job's message, followed by what it left out
+fn with_left_out(mut msg: String, skipped: &[PathBuf]) -> String {
+    if !skipped.is_empty() {
+        let names: Vec<String> = skipped
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        msg.push_str(&format!(", left out {}", names.join(", ")));
+    }
+    msg
+}
+
+// Refuses to replace what is already at `to`
+fn rename_item(vfs: &dyn Vfs, from: &Path, to: &Path) -> Result<(), String> {
+    if vfs.stat(to).is_ok() {
+        return Err(format!("{} already exists", to.display()));
+    }
+    vfs.rename(from, to)
+        .map_err(|e| format!("{}: {}", from.display(), e))
+}
+
+// What was deleted and the first of what wasn't
+fn remove_items(vfs: &dyn Vfs, paths: &[PathBuf]) -> String {
+    let errors: Vec<String> = paths
+        .iter()
+        .filter_map(|path| {
+            vfs.remove(path)
+                .err()
+                .map(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect();
+    let deleted = paths.len() - errors.len();
+    match errors.first() {
+        None => format!("Deleted {} item(s)", deleted),
+        Some(e) => format!(
+            "Deleted {} item(s), {} failed: {}",
+            deleted,
+            errors.len(),
+            e
+        ),
+    }
+}
+
+fn load_recent_dirs() -> (RecentDirs, Option<String>) {
+    match get_data_directory_path() {
+        Some(dir) => RecentDirs::load(&dir.join(RECENT_DIRS_FILE_NAME)),
+        None => (RecentDirs::new(), None),
+    }
+}
+
+// Next to the config given with --config, otherwise next to the user's config.ron
+fn bookmarks_file_path(config_override: Option<&std::path::Path>) -> PathBuf {
+    match config_override {
+        Some(path) => path.with_file_name(BOOKMARKS_FILE_NAME),
+        None => user_config_file_path().with_file_name(BOOKMARKS_FILE_NAME),
+    }
+}
+
+fn load_frecency_db() -> (FrecencyDb, Option<String>) {
+    match get_data_directory_path() {
+        Some(dir) => FrecencyDb::load(&dir.join(FRECENCY_FILE_NAME)),
+        None => (FrecencyDb::new(), None),
+    }
+}
+
+fn open_frecency_picker<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let (labels, paths): (Vec<String>, Vec<PathBuf>) =
+        app.frecency_picker_entries().into_iter().unzip();
+    let mut picker = Picker::new("Frequent directories", labels);
+    if let Some(i) = run_picker(terminal, app, &mut picker)? {
+        app.move_to_dir(paths[i].clone(), None);
+    }
+    Ok(())
+}
+
+// `:` prompt for commands that take arguments
+fn open_command_line<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut line = String::new();
+    if !run_input_prompt(terminal, app, ":", &mut line)? {
+        return Ok(());
+    }
+    let mut words = line.split_whitespace();
+    let cmd = match words.next() {
+        Some(cmd) => cmd,
+        None => return Ok(()),
+    };
+    let args: Vec<&str> = words.collect();
+    // a path is the rest of the line, spaces and all, quotes around it dropped
+    let rest = line.trim_start()[cmd.len()..].trim();
+    let path = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(rest);
+    match (cmd, args.as_slice()) {
+        ("z", []) | ("zi", _) => open_frecency_picker(terminal, app)?,
+        ("z", fragments) => app.jump_by_frecency(fragments),
+        ("zimport", [_, ..]) => app.import_frecency_db(path),
+        ("reload_config", []) => app.reload_config(),
+        ("open", [_, ..]) => match app.resolve_remote(PathBuf::from(path)) {
+            Ok(Some(path)) if host_root(&path).is_some() => app.jump_to_dir(path),
+            // relative to the current directory
+            Ok(Some(path)) => {
+                let path = app.crr_local_dir().join(path);
+                app.jump_to_dir(path);
+            }
+            // opened once connected
+            Ok(None) => {}
+            Err(e) => app.push_command_log(e),
+        },
+        ("column", [name]) => match ColumnKind::from_header(name) {
+            Some(kind) => app.toggle_column(kind),
+            None => app.push_command_log(format!("Unknown column: {}", name)),
+        },
+        _ => app.push_command_log(format!("Unknown command: {}", line)),
+    }
+    Ok(())
+}
+
+// The letter after `m` or `'`. Anything else cancels.
+fn read_mark_key<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    pending: &str,
+) -> io::Result<Option<char>> {
+    app.push_command_log(pending.to_string());
+    terminal.draw(|f| ui(f, app))?;
+    if let Event::Key(key) = event::read()? {
+        if let KeyCode::Char(c) = key.code {
+            if Bookmarks::is_mark_key(c) {
+                return Ok(Some(c));
+            }
+        }
+    }
+    app.push_command_log("Cancelled".to_string());
+    Ok(None)
+}
+
+fn open_bookmark_manager<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    loop {
+        let (keys, labels): (Vec<char>, Vec<String>) = app
+            .bookmarks
+            .iter()
+            .map(|(c, b)| (*c, format!("{}  {}  {}", c, b.name(), b.path().display())))
+            .unzip();
+        let mut picker = Picker::new("Bookmarks (C-r: rename, C-d: delete)", labels);
+        match run_picker_with_actions(terminal, app, &mut picker, &['r', 'd'])? {
+            Some(PickerEvent::Select(i)) => {
+                app.jump_to_bookmark(keys[i]);
+                return Ok(());
+            }
+            Some(PickerEvent::Action('r', i)) => {
+                let c = keys[i];
+                let mut name = app.bookmarks.get(c).unwrap().name().to_string();
+                if run_input_prompt(terminal, app, "Rename bookmark", &mut name)? {
+                    app.rename_bookmark(c, name);
+                }
+            }
+            Some(PickerEvent::Action('d', i)) => app.delete_bookmark(keys[i]),
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn open_history_picker<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let (labels, entries): (Vec<String>, Vec<HistoryEntry>) =
+        app.history_picker_entries().into_iter().unzip();
+    let mut picker = Picker::new("History", labels);
+    if let Some(i) = run_picker(terminal, app, &mut picker)? {
+        let (path, selected) = entries[i].clone().into_parts();
+        if path.is_dir() {
+            app.move_to_dir(path, selected.as_deref());
+        } else {
+            app.push_command_log(format!("{} no longer exists", path.display()));
+        }
+    }
+    Ok(())
+}
+
+fn open_link_prompt<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    hard: bool,
+) -> io::Result<()> {
+    let count = app.chosen_paths().len();
+    if count == 0 {
+        return Ok(());
     }
+    let kind = if hard { "Hard link" } else { "Symlink" };
+    let title = format!("{} {} item(s) at", kind, count);
+    let mut dest = format!("{}{}", app.crr_dir_path().display(), MAIN_SEPARATOR);
+    if run_input_prompt(terminal, app, &title, &mut dest)? {
+        app.link_chosen_items(&dest, hard);
+    }
+    Ok(())
+}
 
-    fn run_user_input(&mut self) -> Option<String> {
-        let mut terminal = init_input_area_terminal().unwrap();
-        let mut name = String::with_capacity(MAX_FILE_NAME_SIZE);
-        if let Ok(()) = start_user_input(&mut terminal, &mut name) {
-            return Some(name);
+fn open_link_target_prompt<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> io::Result<()> {
+    let mut target = match app.selected_link_target() {
+        Some(target) => target.display().to_string(),
+        None => {
+            app.push_command_log("Not a symlink".to_string());
+            return Ok(());
         }
-        None
+    };
+    if run_input_prompt(terminal, app, "Link target", &mut target)? {
+        app.retarget_selected_link(&target);
+    }
+    Ok(())
+}
+
+fn open_extract_picker<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    if app.chosen_paths().is_empty() {
+        return Ok(());
+    }
+    let dests = app.extract_destinations();
+    let labels = dests.iter().map(|d| d.display().to_string()).collect();
+    let mut picker = Picker::new("Extract to", labels);
+    if let Some(i) = run_picker(terminal, app, &mut picker)? {
+        app.extract_chosen_entries(&dests[i]);
+    }
+    Ok(())
+}
+
+fn open_compress_prompt<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let count = app.chosen_paths().len();
+    if count == 0 {
+        return Ok(());
+    }
+    let title = format!(
+        "Pack {} item(s) into (.zip, .tar.gz, .tar.xz, .tar.zst)",
+        count
+    );
+    let mut name = app.default_archive_name();
+    if run_input_prompt(terminal, app, &title, &mut name)? {
+        app.compress_chosen_items(&name);
+    }
+    Ok(())
+}
+
+fn open_copy_picker<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let count = app.chosen_paths().len();
+    if count == 0 {
+        return Ok(());
+    }
+    let dests = app.other_tab_dirs();
+    if dests.is_empty() {
+        app.push_command_log("Open where to copy to in another tab".to_string());
+        return Ok(());
+    }
+    let labels = dests.iter().map(|d| d.display().to_string()).collect();
+    let mut picker = Picker::new(format!("Copy {} item(s) to", count), labels);
+    if let Some(i) = run_picker(terminal, app, &mut picker)? {
+        app.copy_chosen_items(&dests[i]);
+    }
+    Ok(())
+}
+
+fn open_rename_prompt<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut name = match app.selected_file_name() {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    if run_input_prompt(terminal, app, "Rename to", &mut name)? {
+        app.rename_selected(&name);
     }
+    Ok(())
 }
 
-pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
-    let mut multi_normal = app.normal_user_keybinds();
-    let mut multi_input = app.input_user_keybinds();
-    let mut multi_stacker = app.stacker_user_keybinds();
+fn open_delete_confirm<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let count = app.chosen_paths().len();
+    if count == 0 {
+        return Ok(());
+    }
+    let labels = vec!["No".to_string(), "Yes".to_string()];
+    let mut picker = Picker::new(format!("Delete {} item(s)?", count), labels);
+    if run_picker(terminal, app, &mut picker)? == Some(1) {
+        app.delete_chosen_items();
+    }
+    Ok(())
+}
+
+fn open_column_picker<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let (labels, kinds): (Vec<String>, Vec<ColumnKind>) =
+        app.column_picker_entries().into_iter().unzip();
+    let mut picker = Picker::new("Show or hide a column", labels);
+    if let Some(i) = run_picker(terminal, app, &mut picker)? {
+        app.toggle_column(kinds[i]);
+    }
+    Ok(())
+}
+
+fn open_help<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let title = format!("Keybindings in {:?} mode", app.mode());
+    let mut picker = Picker::new(title, app.help_entries());
+    run_picker(terminal, app, &mut picker)?;
+    Ok(())
+}
+
+const MOUSE_SCROLL_LINES: usize = 3;
+
+// How often the config files are checked for changes while no key is pressed
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+// How often the progress of running jobs is redrawn
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn run_app<B: Backend + Write>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut mouse_captured = false;
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        // `mouse` can change with a reload
+        if app.config.mouse() != mouse_captured {
+            mouse_captured = app.config.mouse();
+            match mouse_captured {
+                true => execute!(terminal.backend_mut(), EnableMouseCapture)?,
+                false => execute!(terminal.backend_mut(), DisableMouseCapture)?,
+            }
+        }
+        app.update_git_status();
+        app.update_jobs();
+        app.update_connections();
+        app.update_listings();
+        app.update_preview();
+        app.update_dir_sizes();
+        terminal.draw(|f| ui(f, app))?;
+        if !event::poll(app.poll_timeout())? {
+            app.reload_config_if_changed();
+            app.reload_if_changed();
+            continue;
+        }
         // TODO: Consider a more efficient way to declare the name of each command.
-        if let Event::Key(key) = event::read()? {
+        let event = event::read()?;
+        if let Event::Mouse(mouse) = event {
+            app.handle_mouse(mouse);
+        }
+        if let Event::Key(key) = event {
+            let input = app.crr_keybinds_mut().feed(key);
+            app.update_pending_since();
+            let (cmd, count) = match input {
+                KeyInput::Command(cmd, count) => (cmd, count),
+                KeyInput::Pending | KeyInput::Cancelled => continue,
+                KeyInput::Unbound(keys) => {
+                    app.push_command_log(format!(
+                        "{} is not bound",
+                        key_sequence_to_notation(&keys)
+                    ));
+                    continue;
+                }
+            };
+            // commands that move repeat with a count, others ignore it
+            let n = count.unwrap_or(1);
             if app.mode() == &Mode::Normal {
-                if let Ok(cmd) = key_matchings(key, &mut multi_normal) {
-                    match cmd.as_str() {
-                        "move_to_parent_dir" => app.move_to_parent_dir(),
-                        "move_to_next_file_item" => app.move_to_next_file_item(),
-                        "move_to_prev_file_item" => app.move_to_prev_file_item(),
-                        "move_to_child_dir" => app.move_to_child_dir(),
-                        "move_to_top_of_file_item" => app.move_to_top_of_file_item(),
-                        "move_to_bottom_of_file_item" => app.move_to_bottom_of_file_item(),
-                        "next_dirtab" => app.next_dirtab(),
-                        "prev_dirtab" => app.prev_dirtab(),
-                        "quit" => return Ok(()),
-                        "input" => app.shift_to_input_mode(),
-                        _ => app.push_command_log(format!("{:?}", key.code)),
+                match cmd.as_str() {
+                    "move_to_parent_dir" => (0..n).for_each(|_| app.move_to_parent_dir()),
+                    "move_to_next_file_item" => (0..n).for_each(|_| app.move_to_next_file_item()),
+                    "move_to_prev_file_item" => (0..n).for_each(|_| app.move_to_prev_file_item()),
+                    "move_to_child_dir" => app.move_to_child_dir(),
+                    // `5gg` and `5G` go to the 5th item like in vim
+                    "move_to_top_of_file_item" | "move_to_bottom_of_file_item"
+                        if count.is_some() =>
+                    {
+                        app.move_to_nth_file_item(n)
+                    }
+                    "move_to_top_of_file_item" => app.move_to_top_of_file_item(),
+                    "move_to_bottom_of_file_item" => app.move_to_bottom_of_file_item(),
+                    "toggle_mark" => (0..n).for_each(|_| app.toggle_mark()),
+                    "reload_config" => app.reload_config(),
+                    "next_dirtab" => (0..n).for_each(|_| app.next_dirtab()),
+                    "prev_dirtab" => (0..n).for_each(|_| app.prev_dirtab()),
+                    "move_back_in_history" => (0..n).for_each(|_| app.move_back_in_history()),
+                    "move_forward_in_history" => (0..n).for_each(|_| app.move_forward_in_history()),
+                    "open_history_picker" => open_history_picker(terminal, app)?,
+                    "set_bookmark" => {
+                        if let Some(c) = read_mark_key(terminal, app, "m-")? {
+                            app.set_bookmark(c);
+                        }
                     }
+                    "jump_to_bookmark" => {
+                        if let Some(c) = read_mark_key(terminal, app, "'-")? {
+                            app.jump_to_bookmark(c);
+                        }
+                    }
+                    "open_bookmark_manager" => open_bookmark_manager(terminal, app)?,
+                    "open_frecency_picker" => open_frecency_picker(terminal, app)?,
+                    "command_line" => open_command_line(terminal, app)?,
+                    "toggle_column" => open_column_picker(terminal, app)?,
+                    "toggle_hidden" => app.toggle_hidden(),
+                    "follow_symlink" => app.follow_symlink(),
+                    "make_symlink" => open_link_prompt(terminal, app, false)?,
+                    "make_hardlink" => open_link_prompt(terminal, app, true)?,
+                    "edit_symlink" => open_link_target_prompt(terminal, app)?,
+                    "extract_entries" => open_extract_picker(terminal, app)?,
+                    "extract_archive" => app.extract_selected_archive(),
+                    "compress" => open_compress_prompt(terminal, app)?,
+                    "copy" => open_copy_picker(terminal, app)?,
+                    "rename" => open_rename_prompt(terminal, app)?,
+                    "delete" => open_delete_confirm(terminal, app)?,
+                    "toggle_preview" => app.toggle_preview(),
+                    "calc_dir_size" => app.calc_chosen_dir_sizes(),
+                    "toggle_disk_usage" => app.toggle_disk_usage(),
+                    "git_stage" => app.git_stage(true),
+                    "git_unstage" => app.git_stage(false),
+                    "next_changed_file" => (0..n).for_each(|_| app.move_to_next_changed_file()),
+                    "help" => open_help(terminal, app)?,
+                    "quit" => break,
+                    "input" => app.shift_to_input_mode(),
+                    _ => app.push_command_log(format!("Unknown command: {}", cmd)),
                 }
             } else if app.mode() == &Mode::Input {
-                if let Ok(cmd) = key_matchings(key, &mut multi_input) {
-                    match cmd.as_str() {
-                        "next_dirtab" => app.next_dirtab(),
-                        "prev_dirtab" => app.prev_dirtab(),
-                        "quit" => return Ok(()),
-                        "normal" => app.shift_to_normal_mode(),
-                        "stacker" => app.shift_to_stacker_mode(),
-                        _ => app.push_command_log("No Comands".to_string()),
-                    }
+                match cmd.as_str() {
+                    "next_dirtab" => (0..n).for_each(|_| app.next_dirtab()),
+                    "prev_dirtab" => (0..n).for_each(|_| app.prev_dirtab()),
+                    "help" => open_help(terminal, app)?,
+                    "quit" => break,
+                    "normal" => app.shift_to_normal_mode(),
+                    "stacker" => app.shift_to_stacker_mode(),
+                    _ => app.push_command_log("No Comands".to_string()),
                 }
             } else if app.mode() == &Mode::Stacker {
-                if let Ok(cmd) = key_matchings(key, &mut multi_stacker) {
-                    match cmd.as_str() {
-                        "next_dirtab" => app.next_dirtab(),
-                        "prev_dirtab" => app.prev_dirtab(),
-                        "quit" => return Ok(()),
-                        "normal" => app.shift_to_normal_mode(),
-                        "input" => app.shift_to_input_mode(),
-                        _ => app.push_command_log("No Comands".to_string()),
-                    }
+                match cmd.as_str() {
+                    "next_dirtab" => (0..n).for_each(|_| app.next_dirtab()),
+                    "prev_dirtab" => (0..n).for_each(|_| app.prev_dirtab()),
+                    "help" => open_help(terminal, app)?,
+                    "quit" => break,
+                    "normal" => app.shift_to_normal_mode(),
+                    "input" => app.shift_to_input_mode(),
+                    _ => app.push_command_log("No Comands".to_string()),
                 }
             }
         }
     }
-}
-
-fn key_matchings(first: KeyEvent, keybinds: &mut UserKeybinds) -> io::Result<String> {
-    keybinds.set_keyevent(first);
-    if let Some(cmd) = keybinds.matching_single_keys() {
-        return Ok(cmd);
-    }
-    keybinds.filtering_multi_first_keys();
-    if keybinds.has_keycomb() {
-        if let Event::Key(second) = event::read()? {
-            keybinds.set_keyevent(second);
-            if let Some(cmd) = keybinds.matching_multi_second_keys() {
-                return Ok(cmd);
-            }
-        }
-    }
 
-    Ok(String::with_capacity(0))
+    app.save_session_data()
 }
@@ -0,0 +1,1147 @@
+// Zip and tar archives opened as read-only directories. The list of entries
+// is read when the archive is opened, and again when the file changes.
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use chrono::{Datelike, Local, TimeZone, Timelike};
+
+use crate::file_item_list::file_item::FileItem;
+use crate::file_item_list::{Kinds, LinkTarget};
+use crate::path_process::create_symlink;
+use crate::vfs::{local_stamp, not_found, read_only_error, Meta, Vfs};
+
+const DIR_MODE: u32 = 0o040755;
+const FILE_MODE: u32 = 0o100644;
+const LINK_MODE: u32 = 0o120777;
+const TYPE_BITS: u32 = 0o170000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveKind {
+    const EXTENSIONS: [(&'static str, ArchiveKind); 8] = [
+        (".zip", ArchiveKind::Zip),
+        (".tar.gz", ArchiveKind::TarGz),
+        (".tgz", ArchiveKind::TarGz),
+        (".tar.xz", ArchiveKind::TarXz),
+        (".txz", ArchiveKind::TarXz),
+        (".tar.zst", ArchiveKind::TarZst),
+        (".tzst", ArchiveKind::TarZst),
+        (".tar", ArchiveKind::Tar),
+    ];
+
+    // By the name only, the file isn't looked at
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        ArchiveKind::EXTENSIONS
+            .into_iter()
+            .find(|(ext, _)| name.ends_with(ext) && name.len() > ext.len())
+            .map(|(_, kind)| kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    is_dir: bool,
+    meta: Meta,
+    // None when the file can only be read through the archive's own reader
+    data: Option<Span>,
+    link: Option<Link>,
+}
+
+#[derive(Debug, Clone)]
+enum Link {
+    // the target as stored in the link
+    Symbolic(PathBuf),
+    // the path inside the archive of the entry it shares its data with, None
+    // when that isn't a plain path
+    Hard(Option<PathBuf>),
+}
+
+impl Entry {
+    fn item(&self, path: PathBuf) -> FileItem {
+        match &self.link {
+            // not followed inside the archive, so there is nothing to read
+            // through it
+            Some(Link::Symbolic(target)) => {
+                let target = LinkTarget::new(target.clone(), false, true);
+                let kinds = Kinds::Symlink(Kinds::is_hidden(&path), target);
+                FileItem::new(path, self.meta.clone(), kinds)
+            }
+            _ => FileItem::plain(path, self.meta.clone(), self.is_dir),
+        }
+    }
+
+    fn is_symlink(&self) -> bool {
+        matches!(self.link, Some(Link::Symbolic(_)))
+    }
+}
+
+// Where the bytes of a file are: in the archive file for zip, in the
+// decompressed stream for tar
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    offset: u64,
+    len: u64,
+    deflated: bool,
+}
+
+// by the path inside the archive, with the directories that are only implied
+// by the paths of their entries
+type Entries = BTreeMap<PathBuf, Entry>;
+
+#[derive(Debug)]
+struct Listing {
+    // of the archive file when the entries were read
+    stamp: Option<u64>,
+    entries: Entries,
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    kind: ArchiveKind,
+    listing: Mutex<Listing>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let kind = ArchiveKind::from_path(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "not a known archive type")
+        })?;
+        let mut archive = Archive {
+            path: path.to_path_buf(),
+            kind,
+            listing: Mutex::new(Listing {
+                stamp: local_stamp(path),
+                entries: Entries::new(),
+            }),
+        };
+        let entries = archive.read_entries()?;
+        archive.listing.get_mut().unwrap().entries = entries;
+        Ok(archive)
+    }
+
+    // The entries, read again when the archive file changed since. One that
+    // can't be read, say while it is being written, keeps the old ones.
+    fn listing(&self) -> MutexGuard<'_, Listing> {
+        let mut listing = self.listing.lock().unwrap_or_else(PoisonError::into_inner);
+        let stamp = local_stamp(&self.path);
+        if stamp != listing.stamp {
+            if let Ok(entries) = self.read_entries() {
+                listing.entries = entries;
+            }
+            listing.stamp = stamp;
+        }
+        listing
+    }
+
+    fn read_entries(&self) -> io::Result<Entries> {
+        let mut entries = Entries::new();
+        match self.kind {
+            ArchiveKind::Zip => self.read_zip_entries(&mut entries)?,
+            _ => self.read_tar_entries(&mut entries)?,
+        }
+        Ok(entries)
+    }
+
+    fn read_zip_entries(&self, entries: &mut Entries) -> io::Result<()> {
+        let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i)?;
+            let path = match file.enclosed_name().and_then(|p| clean_entry_path(&p)) {
+                Some(path) => path,
+                None => continue,
+            };
+            let link = match is_zip_symlink(&file) {
+                true => {
+                    let mut target = String::new();
+                    file.read_to_string(&mut target)?;
+                    Some(Link::Symbolic(PathBuf::from(target)))
+                }
+                false => None,
+            };
+            let is_dir = file.is_dir();
+            let default_mode = if is_dir { DIR_MODE } else { FILE_MODE };
+            let modified = file.last_modified().and_then(|t| {
+                Local
+                    .with_ymd_and_hms(
+                        t.year().into(),
+                        t.month().into(),
+                        t.day().into(),
+                        t.hour().into(),
+                        t.minute().into(),
+                        t.second().into(),
+                    )
+                    .single()
+                    .map(SystemTime::from)
+            });
+            let meta = Meta {
+                len: file.size(),
+                mode: Some(file.unix_mode().unwrap_or(default_mode)),
+                modified,
+                ..Meta::default()
+            };
+            let deflated = match file.compression() {
+                zip::CompressionMethod::Stored => Some(false),
+                zip::CompressionMethod::Deflated => Some(true),
+                _ => None,
+            };
+            let data = deflated
+                .filter(|_| link.is_none() && !is_dir && !file.encrypted())
+                .map(|deflated| Span {
+                    offset: file.data_start(),
+                    len: file.compressed_size(),
+                    deflated,
+                });
+            let entry = Entry {
+                is_dir,
+                meta,
+                data,
+                link,
+            };
+            add_entry(entries, path, entry);
+        }
+        Ok(())
+    }
+
+    fn tar_reader(&self) -> io::Result<Box<dyn Read + Send>> {
+        let file = BufReader::new(File::open(&self.path)?);
+        Ok(match self.kind {
+            ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+            ArchiveKind::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+            ArchiveKind::TarZst => Box::new(zstd::Decoder::with_buffer(file)?),
+            _ => Box::new(file),
+        })
+    }
+
+    fn read_tar_entries(&self, entries: &mut Entries) -> io::Result<()> {
+        let mut tar = tar::Archive::new(self.tar_reader()?);
+        for entry in tar.entries()? {
+            let entry = entry?;
+            let path = match clean_entry_path(&entry.path()?) {
+                Some(path) => path,
+                None => continue,
+            };
+            let header = entry.header();
+            let kind = header.entry_type();
+            let link = match (kind.is_symlink(), kind.is_hard_link()) {
+                (true, _) => Some(Link::Symbolic(
+                    entry.link_name()?.unwrap_or_default().into_owned(),
+                )),
+                (_, true) => Some(Link::Hard(
+                    entry.link_name()?.and_then(|name| clean_entry_path(&name)),
+                )),
+                _ => None,
+            };
+            let is_dir = kind.is_dir();
+            let type_bits = match (is_dir, kind.is_symlink()) {
+                (true, _) => DIR_MODE,
+                (_, true) => LINK_MODE,
+                _ => FILE_MODE,
+            } & TYPE_BITS;
+            let meta = Meta {
+                len: header.size().unwrap_or(0),
+                mode: header.mode().ok().map(|mode| type_bits | (mode & 0o7777)),
+                modified: header
+                    .mtime()
+                    .ok()
+                    .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+                uid: header.uid().ok().and_then(|id| u32::try_from(id).ok()),
+                gid: header.gid().ok().and_then(|id| u32::try_from(id).ok()),
+                ..Meta::default()
+            };
+            // sparse files and links are left to the tar reader
+            let data = kind.is_file().then(|| Span {
+                offset: entry.raw_file_position(),
+                len: entry.size(),
+                deflated: false,
+            });
+            let entry = Entry {
+                is_dir,
+                meta,
+                data,
+                link,
+            };
+            add_entry(entries, path, entry);
+        }
+        Ok(())
+    }
+
+    // The path inside the archive of a path below the archive's own
+    fn inner_path<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.path).ok()
+    }
+
+    // Writes the entries at `paths` and everything below them into `dest`,
+    // each under its own name. Returns how many files were written. Nothing
+    // that already exists is overwritten. `progress` gets the bytes written
+    // and the bytes to write.
+    pub fn extract(
+        &self,
+        paths: &[PathBuf],
+        dest: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> io::Result<usize> {
+        // (path inside the archive, what it is extracted under)
+        let chosen: Vec<(&Path, &Path)> = paths
+            .iter()
+            .filter_map(|path| self.inner_path(path))
+            .filter(|inner| !inner.as_os_str().is_empty())
+            .map(|inner| (inner, inner.parent().unwrap_or(Path::new(""))))
+            .collect();
+        let target = |path: &Path| {
+            chosen
+                .iter()
+                .find(|(inner, _)| path.starts_with(inner))
+                .and_then(|(_, base)| path.strip_prefix(base).ok())
+                .map(|rel| dest.join(rel))
+        };
+        let mut count = 0;
+        let (mut done, mut total) = (0, 0);
+        // everything is checked before anything is written, so a refused
+        // extraction leaves nothing behind
+        let mut dirs = Vec::new();
+        {
+            let listing = self.listing();
+            for (path, entry) in &listing.entries {
+                let out = match target(path) {
+                    Some(out) => out,
+                    None => continue,
+                };
+                let below_link = path
+                    .ancestors()
+                    .skip(1)
+                    .any(|dir| listing.entries.get(dir).is_some_and(Entry::is_symlink));
+                if below_link {
+                    return Err(outside_error(&out));
+                }
+                refuse_links_below(dest, &out)?;
+                match &entry.link {
+                    Some(Link::Symbolic(link_target)) => {
+                        refuse_outside_link(dest, &out, link_target)?
+                    }
+                    // it has to be extracted along with it, see below
+                    Some(Link::Hard(source)) if source.as_deref().and_then(target).is_none() => {
+                        return Err(outside_error(&out))
+                    }
+                    _ => {}
+                }
+                let is_dir_there = out.symlink_metadata().is_ok_and(|meta| meta.is_dir());
+                if !(entry.is_dir && is_dir_there) {
+                    refuse_existing(&out)?;
+                }
+                // the implied directories aren't in the archive itself
+                match entry.is_dir {
+                    true => dirs.push(out),
+                    false => total += entry.meta.len,
+                }
+            }
+        }
+        for dir in dirs {
+            fs::create_dir_all(dir)?;
+        }
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+                for i in 0..zip.len() {
+                    let mut file = zip.by_index(i)?;
+                    let path = match file.enclosed_name().and_then(|p| clean_entry_path(&p)) {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    let out = match target(&path) {
+                        Some(out) if !file.is_dir() => out,
+                        _ => continue,
+                    };
+                    refuse_existing(&out)?;
+                    refuse_links_below(dest, &out)?;
+                    if let Some(parent) = out.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    if is_zip_symlink(&file) {
+                        let mut link_target = String::new();
+                        file.read_to_string(&mut link_target)?;
+                        refuse_outside_link(dest, &out, Path::new(&link_target))?;
+                        create_symlink(Path::new(&link_target), &out)?;
+                    } else {
+                        let mut writer =
+                            OpenOptions::new().write(true).create_new(true).open(&out)?;
+                        io::copy(&mut file, &mut writer)?;
+                        set_mode(&out, file.unix_mode());
+                    }
+                    count += 1;
+                    done += file.size();
+                    progress(done, total);
+                }
+            }
+            _ => {
+                let mut tar = tar::Archive::new(self.tar_reader()?);
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    let path = match clean_entry_path(&entry.path()?) {
+                        Some(path) => path,
+                        None => continue,
+                    };
+                    let out = match target(&path) {
+                        Some(out) if !entry.header().entry_type().is_dir() => out,
+                        _ => continue,
+                    };
+                    refuse_existing(&out)?;
+                    refuse_links_below(dest, &out)?;
+                    if let Some(parent) = out.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let kind = entry.header().entry_type();
+                    if kind.is_symlink() {
+                        let link_target = entry.link_name()?.unwrap_or_default().into_owned();
+                        refuse_outside_link(dest, &out, &link_target)?;
+                        create_symlink(&link_target, &out)?;
+                    } else if kind.is_hard_link() {
+                        // named by its path in the archive, so it has to be
+                        // something that is extracted along with it
+                        let source = entry
+                            .link_name()?
+                            .and_then(|name| clean_entry_path(&name))
+                            .and_then(|name| target(&name))
+                            .ok_or_else(|| outside_error(&out))?;
+                        refuse_links_below(dest, &source)?;
+                        fs::hard_link(&source, &out)?;
+                    } else {
+                        // `unpack_in` would put it at its path in the archive,
+                        // not under the chosen entry's name, so the checks
+                        // above stand in for its own
+                        entry.unpack(&out)?;
+                    }
+                    count += 1;
+                    done += entry.header().size().unwrap_or(0);
+                    progress(done, total);
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    // Everything in the archive, into `dest`
+    pub fn extract_all(
+        &self,
+        dest: &Path,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> io::Result<usize> {
+        let top: Vec<PathBuf> = self
+            .listing()
+            .entries
+            .keys()
+            .filter(|path| path.parent() == Some(Path::new("")))
+            .map(|path| self.path.join(path))
+            .collect();
+        self.extract(&top, dest, progress)
+    }
+}
+
+fn add_entry(entries: &mut Entries, path: PathBuf, entry: Entry) {
+    for dir in path.ancestors().skip(1) {
+        if dir.as_os_str().is_empty() || entries.contains_key(dir) {
+            break;
+        }
+        let implied = Entry {
+            is_dir: true,
+            meta: Meta {
+                mode: Some(DIR_MODE),
+                ..Meta::default()
+            },
+            data: None,
+            link: None,
+        };
+        entries.insert(dir.to_path_buf(), implied);
+    }
+    entries.insert(path, entry);
+}
+
+// The directory an archive is extracted into, next to it and named after it:
+// "src.tar.gz" goes into "src", or "src (1)" when that is taken
+pub fn extraction_dir(archive: &Path) -> PathBuf {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let stem = ArchiveKind::EXTENSIONS
+        .iter()
+        .find_map(|(ext, _)| {
+            let cut = name.len().checked_sub(ext.len())?;
+            let tail = name.get(cut..)?;
+            (cut > 0 && tail.eq_ignore_ascii_case(ext)).then(|| &name[..cut])
+        })
+        .unwrap_or(&name)
+        .to_string();
+    let parent = archive.parent().unwrap_or(Path::new(""));
+    let mut dir = parent.join(&stem);
+    let mut n = 1;
+    while dir.symlink_metadata().is_ok() {
+        dir = parent.join(format!("{} ({})", stem, n));
+        n += 1;
+    }
+    dir
+}
+
+// Packs `sources` and everything in them into a new archive at `dest`, of the
+// kind its name says. Each source is stored under its own name. Returns how
+// many files were packed and the special files the archive can't hold, which
+// are left out; a half written archive is removed.
+pub fn create_archive(
+    sources: &[PathBuf],
+    dest: &Path,
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<(usize, Vec<PathBuf>)> {
+    let kind = ArchiveKind::from_path(dest).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the name has to end in .zip, .tar, .tar.gz, .tar.xz or .tar.zst",
+        )
+    })?;
+    let mut files = Vec::new();
+    for source in sources {
+        let base = source.parent().unwrap_or(Path::new(""));
+        collect_files(source, base, dest, &mut files)?;
+    }
+    let (files, skipped): (Vec<Packed>, Vec<Packed>) = files
+        .into_iter()
+        .partition(|(_, _, meta)| can_pack(kind, meta));
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dest.display(), e)))?;
+    let res = write_archive(kind, file, &files, progress);
+    if res.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    let skipped = skipped.into_iter().map(|(path, _, _)| path).collect();
+    res.map(|n| (n, skipped))
+}
+
+// Zip only holds files, directories and links, where a fifo would be read
+// forever. Tar holds fifos and devices too, but no sockets.
+fn can_pack(kind: ArchiveKind, meta: &fs::Metadata) -> bool {
+    let file_type = meta.file_type();
+    if file_type.is_file() || file_type.is_dir() || file_type.is_symlink() {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_socket() {
+            return false;
+        }
+    }
+    kind != ArchiveKind::Zip
+}
+
+// (path on disk, name in the archive, metadata)
+type Packed = (PathBuf, PathBuf, fs::Metadata);
+
+fn collect_files(path: &Path, base: &Path, dest: &Path, out: &mut Vec<Packed>) -> io::Result<()> {
+    if path == dest {
+        return Ok(());
+    }
+    let meta = path.symlink_metadata()?;
+    let name = path.strip_prefix(base).unwrap_or(path).to_path_buf();
+    let is_dir = meta.is_dir();
+    out.push((path.to_path_buf(), name, meta));
+    if is_dir {
+        for entry in fs::read_dir(path)? {
+            collect_files(&entry?.path(), base, dest, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_archive(
+    kind: ArchiveKind,
+    file: File,
+    files: &[Packed],
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<usize> {
+    let file = io::BufWriter::new(file);
+    match kind {
+        ArchiveKind::Zip => write_zip(file, files, progress),
+        ArchiveKind::Tar => write_tar(file, files, progress).map(|(_, n)| n),
+        ArchiveKind::TarGz => {
+            let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+            let (gz, n) = write_tar(gz, files, progress)?;
+            gz.finish()?;
+            Ok(n)
+        }
+        ArchiveKind::TarXz => {
+            let (xz, n) = write_tar(xz2::write::XzEncoder::new(file, 6), files, progress)?;
+            xz.finish()?;
+            Ok(n)
+        }
+        ArchiveKind::TarZst => {
+            let (zst, n) = write_tar(zstd::Encoder::new(file, 0)?, files, progress)?;
+            zst.finish()?;
+            Ok(n)
+        }
+    }
+}
+
+fn total_len(files: &[Packed]) -> u64 {
+    files
+        .iter()
+        .filter(|(_, _, meta)| meta.is_file())
+        .map(|(_, _, meta)| meta.len())
+        .sum()
+}
+
+fn write_tar<W: Write>(
+    w: W,
+    files: &[Packed],
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<(W, usize)> {
+    let (mut done, total) = (0, total_len(files));
+    let mut count = 0;
+    let mut tar = tar::Builder::new(w);
+    tar.follow_symlinks(false);
+    for (path, name, meta) in files {
+        tar.append_path_with_name(path, name)?;
+        if !meta.is_dir() {
+            count += 1;
+        }
+        if meta.is_file() {
+            done += meta.len();
+            progress(done, total);
+        }
+    }
+    Ok((tar.into_inner()?, count))
+}
+
+fn write_zip<W: Write + io::Seek>(
+    w: W,
+    files: &[Packed],
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<usize> {
+    let (mut done, total) = (0, total_len(files));
+    let mut count = 0;
+    let mut zip = zip::ZipWriter::new(w);
+    for (path, name, meta) in files {
+        // zip names are always separated by "/"
+        let name = name
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut options =
+            zip::write::SimpleFileOptions::default().unix_permissions(permissions(meta));
+        if let Some(time) = meta.modified().ok().and_then(zip_time) {
+            options = options.last_modified_time(time);
+        }
+        if meta.is_dir() {
+            zip.add_directory(name, options)?;
+            continue;
+        }
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            zip.add_symlink(name, target.to_string_lossy(), options)?;
+        } else {
+            zip.start_file(name, options)?;
+            io::copy(&mut File::open(path)?, &mut zip)?;
+            done += meta.len();
+            progress(done, total);
+        }
+        count += 1;
+    }
+    zip.finish()?;
+    Ok(count)
+}
+
+// Zip keeps the local time, to the even second
+fn zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    let t: chrono::DateTime<Local> = time.into();
+    zip::DateTime::from_date_and_time(
+        u16::try_from(t.year()).ok()?,
+        t.month() as u8,
+        t.day() as u8,
+        t.hour() as u8,
+        t.minute() as u8,
+        t.second() as u8,
+    )
+    .ok()
+}
+
+#[cfg(unix)]
+fn permissions(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn permissions(meta: &fs::Metadata) -> u32 {
+    match (meta.is_dir(), meta.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+impl Vfs for Archive {
+    fn read_dir(&self, dir: &Path) -> (Vec<FileItem>, Vec<String>) {
+        let inner = match self.inner_path(dir) {
+            Some(inner) => inner,
+            None => {
+                return (
+                    Vec::new(),
+                    vec![format!("{}: not in the archive", dir.display())],
+                )
+            }
+        };
+        let items = self
+            .listing()
+            .entries
+            .iter()
+            .filter(|(path, _)| path.parent() == Some(inner))
+            .map(|(path, entry)| entry.item(dir.join(path.file_name().unwrap_or_default())))
+            .collect();
+        (items, Vec::new())
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileItem> {
+        let inner = self.inner_path(path).ok_or_else(|| not_found(path))?;
+        // the archive itself, opened as a directory
+        if inner.as_os_str().is_empty() {
+            let meta = Meta {
+                mode: Some(DIR_MODE),
+                ..Meta::default()
+            };
+            return Ok(FileItem::plain(path.to_path_buf(), meta, true));
+        }
+        let listing = self.listing();
+        let entry = listing.entries.get(inner).ok_or_else(|| not_found(path))?;
+        Ok(entry.item(path.to_path_buf()))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let inner = self.inner_path(path).ok_or_else(|| not_found(path))?;
+        let mut data = Vec::new();
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&self.path)?))?;
+                for i in 0..zip.len() {
+                    let mut file = zip.by_index(i)?;
+                    let name = file.enclosed_name().and_then(|p| clean_entry_path(&p));
+                    if name.as_deref() == Some(inner) && !file.is_dir() {
+                        file.read_to_end(&mut data)?;
+                        return Ok(data);
+                    }
+                }
+            }
+            _ => {
+                let mut tar = tar::Archive::new(self.tar_reader()?);
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    let name = clean_entry_path(&entry.path()?);
+                    if name.as_deref() == Some(inner) && !entry.header().entry_type().is_dir() {
+                        entry.read_to_end(&mut data)?;
+                        return Ok(data);
+                    }
+                }
+            }
+        }
+        Err(not_found(path))
+    }
+
+    // Reads the file's bytes from where they are in the archive, without
+    // holding all of them
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let inner = self.inner_path(path).ok_or_else(|| not_found(path))?;
+        let span = self.listing().entries.get(inner).and_then(|e| e.data);
+        let span = match span {
+            Some(span) => span,
+            None => return Ok(Box::new(io::Cursor::new(self.read(path)?))),
+        };
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut file = File::open(&self.path)?;
+                file.seek(SeekFrom::Start(span.offset))?;
+                let raw = BufReader::new(file).take(span.len);
+                Ok(match span.deflated {
+                    true => Box::new(flate2::read::DeflateDecoder::new(raw)),
+                    false => Box::new(raw),
+                })
+            }
+            _ => {
+                let mut reader = self.tar_reader()?;
+                io::copy(&mut (&mut reader).take(span.offset), &mut io::sink())?;
+                Ok(Box::new(reader.take(span.len)))
+            }
+        }
+    }
+
+    fn create(&self, _path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        Err(read_only_error())
+    }
+
+    fn create_dir(&self, _path: &Path) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    fn remove(&self, _path: &Path) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    // The archive file being written again
+    fn watch_stamp(&self, _dir: &Path) -> Option<u64> {
+        local_stamp(&self.path)
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+// By the file type in the unix mode, which is only there when the archive
+// was made on unix
+fn is_zip_symlink<R: Read>(file: &zip::read::ZipFile<'_, R>) -> bool {
+    file.unix_mode()
+        .is_some_and(|mode| mode & TYPE_BITS == LINK_MODE & TYPE_BITS)
+}
+
+fn refuse_existing(path: &Path) -> io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        )),
+        Err(_) => Ok(()),
+    }
+}
+
+fn outside_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} leads outside of where it is extracted", path.display()),
+    )
+}
+
+// Nothing is written through a directory that is a link, whether an earlier
+// entry made it or it was there already, since it can lead anywhere
+fn refuse_links_below(dest: &Path, out: &Path) -> io::Result<()> {
+    let rel = out.strip_prefix(dest).map_err(|_| outside_error(out))?;
+    let mut dir = dest.to_path_buf();
+    for component in rel.parent().unwrap_or(Path::new("")).components() {
+        dir.push(component);
+        match dir.symlink_metadata() {
+            Ok(meta) if meta.file_type().is_symlink() => return Err(outside_error(out)),
+            Ok(_) => {}
+            // and neither does anything below it
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+// A link at `out` to `link_target` has to be relative and not climb out of
+// `dest` with its `..`
+fn refuse_outside_link(dest: &Path, out: &Path, link_target: &Path) -> io::Result<()> {
+    let rel = out.strip_prefix(dest).map_err(|_| outside_error(out))?;
+    let mut depth = rel.components().count() as isize - 1;
+    for component in link_target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return Err(outside_error(out)),
+        }
+    }
+    Ok(())
+}
+
+// Only plain names, so that no entry can end up outside of where it is
+// extracted
+fn clean_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => clean.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(mode) = mode {
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777));
+    }
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: Option<u32>) {}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::{self, Read, Write};
+    use std::path::Path;
+
+    use super::{create_archive, extraction_dir, Archive, ArchiveKind};
+    use crate::temp_dir::TempDir;
+    use crate::vfs::Vfs;
+
+    fn names(archive: &Archive, dir: &Path) -> Vec<String> {
+        let (items, errors) = archive.read_dir(dir);
+        assert!(errors.is_empty());
+        items.iter().map(|item| item.name()).collect()
+    }
+
+    #[test]
+    fn archives_are_listed_and_extracted() {
+        let dir = TempDir::new("archive");
+
+        let zip_path = dir.join("a.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("src/main.rs", options).unwrap();
+        zip.write_all(b"fn main() {}").unwrap();
+        zip.start_file("README", options).unwrap();
+        zip.finish().unwrap();
+
+        let tar_path = dir.join("b.tar.gz");
+        let gz = flate2::write::GzEncoder::new(
+            fs::File::create(&tar_path).unwrap(),
+            flate2::Compression::fast(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o600);
+        tar.append_data(&mut header, "docs/x/notes.txt", &b"abc"[..])
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(ArchiveKind::from_path(&tar_path), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_path(Path::new(".zip")), None);
+
+        let zip = Archive::open(&zip_path).unwrap();
+        assert_eq!(names(&zip, &zip_path), ["README", "src"]);
+        assert_eq!(names(&zip, &zip_path.join("src")), ["main.rs"]);
+
+        let tar = Archive::open(&tar_path).unwrap();
+        let (items, _) = tar.read_dir(&tar_path.join("docs/x"));
+        assert_eq!(items[0].get_permission(false), "-rw-------");
+
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        let mut none = |_, _| {};
+        assert_eq!(
+            zip.extract(&[zip_path.join("src")], &out, &mut none)
+                .unwrap(),
+            1
+        );
+        assert_eq!(fs::read(out.join("src/main.rs")).unwrap(), b"fn main() {}");
+        assert_eq!(
+            tar.extract(&[tar_path.join("docs/x")], &out, &mut none)
+                .unwrap(),
+            1
+        );
+        assert_eq!(fs::read(out.join("x/notes.txt")).unwrap(), b"abc");
+        // nothing is overwritten
+        assert!(tar
+            .extract(&[tar_path.join("docs/x")], &out, &mut none)
+            .is_err());
+
+        // packed again and unpacked next to the archive
+        let packed = dir.join("out.tar.zst");
+        let mut last = (0, 0);
+        let n = create_archive(std::slice::from_ref(&out), &packed, &mut |done, total| {
+            last = (done, total)
+        });
+        assert_eq!(n.unwrap(), (2, Vec::new()));
+        assert_eq!(last, (15, 15));
+        assert!(create_archive(std::slice::from_ref(&out), &packed, &mut none).is_err());
+        assert_eq!(extraction_dir(&packed), dir.join("out (1)"));
+        let unpack = dir.join("unpacked");
+        Archive::open(&packed)
+            .unwrap()
+            .extract_all(&unpack, &mut none)
+            .unwrap();
+        assert_eq!(fs::read(unpack.join("out/x/notes.txt")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn files_are_streamed_and_changes_read_again() {
+        let dir = TempDir::new("archive-stream");
+        let big: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let write_zip = |path: &Path, names: &[&str]| {
+            let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+            let stored = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored);
+            zip.start_file("stored", stored).unwrap();
+            zip.write_all(b"as it is").unwrap();
+            for name in names {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                    .unwrap();
+                zip.write_all(&big).unwrap();
+            }
+            zip.finish().unwrap();
+        };
+        let read_all = |archive: &Archive, path: &Path| {
+            let mut data = Vec::new();
+            archive.open(path).unwrap().read_to_end(&mut data).unwrap();
+            data
+        };
+
+        let zip_path = dir.join("a.zip");
+        write_zip(&zip_path, &["big"]);
+        let zip = Archive::open(&zip_path).unwrap();
+        assert_eq!(read_all(&zip, &zip_path.join("stored")), b"as it is");
+        assert_eq!(read_all(&zip, &zip_path.join("big")), big);
+        let streamed = |archive: &Archive, name: &str| {
+            archive.listing().entries[Path::new(name)].data.is_some()
+        };
+        assert!(streamed(&zip, "stored") && streamed(&zip, "big"));
+
+        let tar_path = dir.join("b.tar.xz");
+        let mut tar = tar::Builder::new(xz2::write::XzEncoder::new(
+            fs::File::create(&tar_path).unwrap(),
+            1,
+        ));
+        for (name, data) in [("one", &b"first"[..]), ("two", &big[..])] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            tar.append_data(&mut header, name, data).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+        let tar = Archive::open(&tar_path).unwrap();
+        assert_eq!(read_all(&tar, &tar_path.join("one")), b"first");
+        assert_eq!(read_all(&tar, &tar_path.join("two")), big);
+        assert!(streamed(&tar, "one") && streamed(&tar, "two"));
+
+        // written again with another file, a second later as far as the
+        // listing can tell
+        write_zip(&zip_path, &["big", "more"]);
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(1);
+        fs::File::options()
+            .write(true)
+            .open(&zip_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(names(&zip, &zip_path), ["big", "more", "stored"]);
+        assert_eq!(read_all(&zip, &zip_path.join("more")), big);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files_are_left_out() {
+        let dir = TempDir::new("archive-special");
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.txt"), b"a").unwrap();
+        let _socket = std::os::unix::net::UnixListener::bind(src.join("socket")).unwrap();
+
+        for name in ["out.zip", "out.tar"] {
+            let packed = dir.join(name);
+            let (n, skipped) =
+                create_archive(std::slice::from_ref(&src), &packed, &mut |_, _| {}).unwrap();
+            assert_eq!((n, skipped), (1, vec![src.join("socket")]));
+            let archive = Archive::open(&packed).unwrap();
+            assert!(archive.stat(&packed.join("src/socket")).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_cannot_lead_outside() {
+        let dir = TempDir::new("archive-links");
+        let outside = dir.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let mut none = |_, _| {};
+
+        // a link out of the extraction, then a file written through it
+        let zip_path = dir.join("evil.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_symlink("up", outside.to_str().unwrap(), options)
+            .unwrap();
+        zip.start_file("up/pwned", options).unwrap();
+        zip.write_all(b"x").unwrap();
+        zip.finish().unwrap();
+        let zip = Archive::open(&zip_path).unwrap();
+        assert!(zip.stat(&zip_path).unwrap().kinds().is_dir());
+        let up = zip.stat(&zip_path.join("up")).unwrap();
+        assert_eq!(up.kinds().link_target().unwrap().path(), outside);
+        assert!(zip.extract_all(&dir.join("a"), &mut none).is_err());
+        assert!(zip
+            .extract(&[zip_path.join("up")], &dir.join("b"), &mut none)
+            .is_err());
+
+        // a link that stays inside is fine, but isn't written through either
+        let tar_path = dir.join("evil.tar");
+        let mut tar = tar::Builder::new(fs::File::create(&tar_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "top/sub/file", &b"abc"[..])
+            .unwrap();
+        let mut link = |name: &str, target: &str, kind| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(kind);
+            header.set_size(0);
+            tar.append_link(&mut header, name, target).unwrap();
+        };
+        link("top/inside", "sub/file", tar::EntryType::Symlink);
+        link("top/hard", "top/sub/file", tar::EntryType::Link);
+        link("top/sub/up", "../../top/inside", tar::EntryType::Symlink);
+        tar.finish().unwrap();
+        drop(tar);
+        let mut tar = tar::Builder::new(fs::File::create(dir.join("through.tar")).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        tar.append_data(&mut header, "real/", io::empty()).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        tar.append_link(&mut header, "l", "real").unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "l/pwned", &b"x"[..]).unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+
+        let safe = Archive::open(&tar_path).unwrap();
+        let out = dir.join("c");
+        assert_eq!(safe.extract_all(&out, &mut none).unwrap(), 4);
+        assert_eq!(fs::read(out.join("top/inside")).unwrap(), b"abc");
+        assert_eq!(fs::read(out.join("top/hard")).unwrap(), b"abc");
+        // the same link climbs out when only the directory below is extracted
+        let sub = dir.join("d");
+        fs::create_dir_all(&sub).unwrap();
+        assert!(safe
+            .extract(&[tar_path.join("top/sub")], &sub, &mut none)
+            .is_err());
+        let through = Archive::open(&dir.join("through.tar")).unwrap();
+        assert!(through.extract_all(&dir.join("e"), &mut none).is_err());
+        // not even the directory is made
+        assert!(!dir.join("e").exists());
+
+        assert!(fs::read_dir(&outside).unwrap().next().is_none());
+        assert!(!dir.join("pwned").exists());
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::path_process::load_ron_file;
+
+pub const BOOKMARKS_FILE_NAME: &str = "bookmarks.ron";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    name: String,
+    path: PathBuf,
+}
+
+impl Bookmark {
+    pub fn new(path: PathBuf) -> Self {
+        let name = path.display().to_string();
+        Self { name, path }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// Directories marked with a single letter, `m<letter>` to set and `'<letter>` to jump.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bookmarks {
+    marks: BTreeMap<char, Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn is_mark_key(c: char) -> bool {
+        c.is_ascii_alphanumeric()
+    }
+
+    // Bookmarks written in config.ron only seed the bookmarks file, so one that
+    // is deleted stays deleted. A broken file is moved aside and the reason
+    // returned, then the bookmarks start over from config.ron.
+    pub fn load(path: &Path, defaults: &HashMap<char, PathBuf>) -> (Self, Option<String>) {
+        let (mut bookmarks, error): (Bookmarks, _) = load_ron_file(path);
+        if !path.exists() {
+            for (c, path) in defaults.iter() {
+                bookmarks.set(*c, path.clone());
+            }
+        }
+        (bookmarks, error)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, s)
+    }
+
+    pub fn set(&mut self, c: char, path: PathBuf) {
+        self.marks.insert(c, Bookmark::new(path));
+    }
+
+    pub fn get(&self, c: char) -> Option<&Bookmark> {
+        self.marks.get(&c)
+    }
+
+    pub fn rename(&mut self, c: char, name: String) -> bool {
+        match self.marks.get_mut(&c) {
+            Some(bookmark) => {
+                bookmark.name = name;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, c: char) -> Option<Bookmark> {
+        self.marks.remove(&c)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&char, &Bookmark)> {
+        self.marks.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::Bookmarks;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn config_bookmarks_only_seed_the_file() {
+        let dir = TempDir::new("bookmarks");
+        let path = dir.join("bookmarks.ron");
+        let defaults = HashMap::from([
+            ('a', PathBuf::from("/from/config")),
+            ('b', PathBuf::from("/from/config")),
+        ]);
+        let (mut bookmarks, error) = Bookmarks::load(&path, &defaults);
+        assert_eq!(error, None);
+        assert_eq!(bookmarks.iter().count(), 2);
+
+        bookmarks.set('a', PathBuf::from("/from/file"));
+        bookmarks.rename('a', "file".to_string());
+        bookmarks.remove('b');
+        bookmarks.save(&path).unwrap();
+
+        let (loaded, error) = Bookmarks::load(&path, &defaults);
+        assert_eq!(error, None);
+        assert_eq!(loaded.get('a').unwrap().path(), PathBuf::from("/from/file"));
+        assert_eq!(loaded.get('a').unwrap().name(), "file");
+        assert!(loaded.get('b').is_none());
+    }
+
+    #[test]
+    fn broken_file_is_reported_and_kept() {
+        let dir = TempDir::new("bookmarks-broken");
+        let path = dir.join("bookmarks.ron");
+        std::fs::write(&path, "{'a': (name: \"half").unwrap();
+
+        let (mut loaded, error) = Bookmarks::load(&path, &HashMap::new());
+        assert!(error.unwrap().contains("bookmarks.ron"));
+        loaded.set('b', PathBuf::from("/b"));
+        loaded.save(&path).unwrap();
+        let broken = std::fs::read_to_string(dir.join("bookmarks.ron.broken")).unwrap();
+        assert_eq!(broken, "{'a': (name: \"half");
+        assert_eq!(Bookmarks::load(&path, &HashMap::new()).1, None);
+    }
+}
@@ -0,0 +1,373 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::sftp::host_root;
+use crate::state::SortKey;
+use crate::theme::SettingTheme;
+
+pub const USAGE: &str = "\
+Usage: simple-tui-file-manager [OPTIONS] [PATH]...
+       simple-tui-file-manager init <bash|zsh|fish>
+
+Each PATH is opened as a tab. Without PATH, the current and home directories are opened.
+
+Options:
+  --config <file>       Read the config from <file>
+  --theme <name>        Use a theme: dark, light, dark_blue or themes/<name>.ron
+  --show-hidden         Show dotfiles and ignored items, the default
+  --hide-hidden         Hide dotfiles and ignored items
+  --sort <key>          Sort by name, size, modified or extension
+  --select <file>       Start with <file> selected
+  --choosedir <file>    Write the directory of the active tab to <file> on exit
+  --cwd-file <file>     Same as --choosedir
+  --choosefiles <file>  Write the marked or selected paths to <file> on exit
+  --check-config        Report problems in the config files and exit
+  -h, --help            Print this help
+  -V, --version         Print the version
+";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliOptions {
+    // opened as tabs in this order
+    pub paths: Vec<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub theme: Option<String>,
+    pub show_hidden: Option<bool>,
+    pub sort: Option<SortKey>,
+    pub select: Option<PathBuf>,
+    // write the directory of the active tab here on exit
+    pub choosedir: Option<PathBuf>,
+    // write the marked (or selected) paths here on exit, one per line
+    pub choosefiles: Option<PathBuf>,
+    // report config problems instead of starting
+    pub check_config: bool,
+}
+
+impl CliOptions {
+    // Checked before the terminal enters raw mode, so errors can simply be printed.
+    // Relative paths are made absolute so that moving to the parent works.
+    pub fn validate(mut self) -> Result<Self, String> {
+        let mut paths = Vec::with_capacity(self.paths.len());
+        for path in self.paths.iter() {
+            // connected to once the terminal is set up
+            if host_root(path).is_some() {
+                paths.push(path.clone());
+                continue;
+            }
+            let abs =
+                std::fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if !abs.is_dir() {
+                return Err(format!("{}: Not a directory", path.display()));
+            }
+            paths.push(abs);
+        }
+        self.paths = paths;
+
+        if let Some(select) = self.select.as_ref() {
+            let abs = std::fs::canonicalize(select)
+                .map_err(|e| format!("{}: {}", select.display(), e))?;
+            self.select = Some(abs);
+        }
+
+        if let Some(config) = self.config.as_ref() {
+            if !config.is_file() {
+                return Err(format!("{}: No such config file", config.display()));
+            }
+        }
+
+        if let Some(theme) = self.theme.as_ref() {
+            SettingTheme::from_name(theme).map_err(|e| e.to_string())?;
+        }
+
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliCommand {
+    Run(CliOptions),
+    PrintHelp,
+    PrintVersion,
+    // `init <shell>` prints a function that changes the shell's directory on exit
+    PrintShellInit(Shell),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+
+    pub fn init_script(&self) -> &'static str {
+        match self {
+            Shell::Bash | Shell::Zsh => POSIX_INIT_SCRIPT,
+            Shell::Fish => FISH_INIT_SCRIPT,
+        }
+    }
+}
+
+const POSIX_INIT_SCRIPT: &str = r#"stfm() {
+    local tmp dir
+    tmp="$(mktemp -t stfm-cwd.XXXXXX)"
+    command simple-tui-file-manager --choosedir "$tmp" "$@"
+    dir="$(cat -- "$tmp")"
+    rm -f -- "$tmp"
+    if [ -n "$dir" ] && [ "$dir" != "$PWD" ]; then
+        cd -- "$dir" || return
+    fi
+}
+"#;
+
+const FISH_INIT_SCRIPT: &str = r#"function stfm
+    set -l tmp (mktemp -t stfm-cwd.XXXXXX)
+    command simple-tui-file-manager --choosedir $tmp $argv
+    set -l dir (cat -- $tmp)
+    rm -f -- $tmp
+    if test -n "$dir"; and test "$dir" != "$PWD"
+        cd -- $dir
+    end
+end
+"#;
+
+// `--flag=value` split at the `=`. The value is kept as it is, it can be a
+// path that isn't UTF-8.
+fn split_inline(arg: &OsStr) -> Option<(&str, &OsStr)> {
+    let bytes = arg.as_encoded_bytes();
+    let eq = bytes.iter().position(|b| *b == b'=')?;
+    let flag = std::str::from_utf8(&bytes[..eq])
+        .ok()
+        .filter(|flag| flag.starts_with("--"))?;
+    // SAFETY: split right after an ASCII character, as from_encoded_bytes_unchecked allows
+    let value = unsafe { OsStr::from_encoded_bytes_unchecked(&bytes[eq + 1..]) };
+    Some((flag, value))
+}
+
+// `--flag value` and `--flag=value` are both accepted
+fn take_value(
+    flag: &str,
+    inline: Option<OsString>,
+    args: &mut impl Iterator<Item = OsString>,
+) -> Result<OsString, String> {
+    inline
+        .or_else(|| args.next())
+        .ok_or_else(|| format!("{} requires a value", flag))
+}
+
+// For the values that are names rather than paths
+fn take_str_value(
+    flag: &str,
+    inline: Option<OsString>,
+    args: &mut impl Iterator<Item = OsString>,
+) -> Result<String, String> {
+    take_value(flag, inline, args)?
+        .into_string()
+        .map_err(|value| format!("{}: not UTF-8: {}", flag, value.to_string_lossy()))
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<CliCommand, String> {
+    let mut args = args.into_iter();
+    let mut options = CliOptions::default();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match split_inline(&arg) {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_os_string())),
+            None => (arg.to_string_lossy().into_owned(), None),
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::PrintHelp),
+            "-V" | "--version" => return Ok(CliCommand::PrintVersion),
+            "--config" => {
+                let value = take_value(&flag, inline, &mut args)?;
+                options.config = Some(PathBuf::from(value));
+            }
+            "--theme" => options.theme = Some(take_str_value(&flag, inline, &mut args)?),
+            "--show-hidden" => options.show_hidden = Some(true),
+            "--hide-hidden" => options.show_hidden = Some(false),
+            "--check-config" => options.check_config = true,
+            "--sort" => {
+                let value = take_str_value(&flag, inline, &mut args)?;
+                let key = SortKey::from_name(&value).ok_or(format!(
+                    "Unknown sort key: {} (expected one of {})",
+                    value,
+                    SortKey::NAMES.join(", ")
+                ))?;
+                options.sort = Some(key);
+            }
+            "--select" => {
+                let value = take_value(&flag, inline, &mut args)?;
+                options.select = Some(PathBuf::from(value));
+            }
+            "--" => options.paths.extend(args.by_ref().map(PathBuf::from)),
+            "--choosedir" | "--cwd-file" => {
+                let value = take_value(&flag, inline, &mut args)?;
+                options.choosedir = Some(PathBuf::from(value));
+            }
+            "--choosefiles" => {
+                let value = take_value(&flag, inline, &mut args)?;
+                options.choosefiles = Some(PathBuf::from(value));
+            }
+            "init" => {
+                let name = args
+                    .next()
+                    .ok_or("init requires a shell name: bash, zsh or fish")?;
+                let name = name.to_string_lossy();
+                let shell =
+                    Shell::from_name(&name).ok_or(format!("Unsupported shell: {}", name))?;
+                return Ok(CliCommand::PrintShellInit(shell));
+            }
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unknown option: {}", flag))
+            }
+            _ => options.paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(CliCommand::Run(options))
+}
+
+// The bytes of each path as they are, display() would replace the ones
+// that aren't UTF-8 and the shell would get a path that doesn't exist
+pub fn write_paths(file: &Path, paths: &[PathBuf]) -> io::Result<()> {
+    let mut f = File::create(file)?;
+    for path in paths {
+        f.write_all(path.as_os_str().as_encoded_bytes())?;
+        f.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use super::{parse_args, CliCommand, CliOptions, Shell};
+    use crate::state::SortKey;
+
+    fn parse(args: &[&str]) -> Result<CliCommand, String> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn parse_choose_options() {
+        let expected = CliOptions {
+            choosedir: Some(PathBuf::from("/tmp/dir")),
+            choosefiles: Some(PathBuf::from("/tmp/files")),
+            ..CliOptions::default()
+        };
+        assert_eq!(
+            parse(&["--choosedir", "/tmp/dir", "--choosefiles=/tmp/files"]),
+            Ok(CliCommand::Run(expected.clone()))
+        );
+        assert_eq!(
+            parse(&["--cwd-file=/tmp/dir", "--choosefiles", "/tmp/files"]),
+            Ok(CliCommand::Run(expected))
+        );
+        assert!(parse(&["--choosedir"]).is_err());
+        assert!(parse(&["--what"]).is_err());
+    }
+
+    #[test]
+    fn parse_paths_and_options() {
+        let parsed = parse(&[
+            "src",
+            "--sort=size",
+            "--show-hidden",
+            "--theme",
+            "light",
+            "docs",
+        ]);
+        let expected = CliOptions {
+            paths: vec![PathBuf::from("src"), PathBuf::from("docs")],
+            theme: Some("light".to_string()),
+            show_hidden: Some(true),
+            sort: Some(SortKey::Size),
+            ..CliOptions::default()
+        };
+        assert_eq!(parsed, Ok(CliCommand::Run(expected)));
+        assert!(parse(&["--sort", "colour"]).is_err());
+        assert_eq!(
+            parse(&["--hide-hidden"]),
+            Ok(CliCommand::Run(CliOptions {
+                show_hidden: Some(false),
+                ..CliOptions::default()
+            }))
+        );
+        assert_eq!(
+            parse(&["--check-config"]),
+            Ok(CliCommand::Run(CliOptions {
+                check_config: true,
+                ..CliOptions::default()
+            }))
+        );
+        assert_eq!(parse(&["src", "--help"]), Ok(CliCommand::PrintHelp));
+        assert_eq!(
+            parse(&["--", "-odd-name"]),
+            Ok(CliCommand::Run(CliOptions {
+                paths: vec![PathBuf::from("-odd-name")],
+                ..CliOptions::default()
+            }))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_do_not_have_to_be_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+        let odd = || OsString::from_vec(b"odd\xff".to_vec());
+        let mut select = OsString::from("--select=");
+        select.push(odd());
+        let parsed = parse_args([odd(), select]);
+        let expected = CliOptions {
+            paths: vec![PathBuf::from(odd())],
+            select: Some(PathBuf::from(odd())),
+            ..CliOptions::default()
+        };
+        assert_eq!(parsed, Ok(CliCommand::Run(expected)));
+        assert!(parse_args([OsString::from("--sort"), odd()]).is_err());
+    }
+
+    #[test]
+    fn validate_reports_bad_paths() {
+        let options = CliOptions {
+            paths: vec![PathBuf::from("src"), PathBuf::from("no/such/dir")],
+            ..CliOptions::default()
+        };
+        assert!(options.validate().unwrap_err().starts_with("no/such/dir"));
+
+        let options = CliOptions {
+            paths: vec![PathBuf::from("Cargo.toml")],
+            ..CliOptions::default()
+        };
+        assert!(options.validate().is_err());
+
+        let options = CliOptions {
+            paths: vec![PathBuf::from("src")],
+            theme: Some("dark".to_string()),
+            ..CliOptions::default()
+        };
+        assert!(options.validate().unwrap().paths[0].is_absolute());
+    }
+
+    #[test]
+    fn parse_init() {
+        assert_eq!(
+            parse(&["init", "fish"]),
+            Ok(CliCommand::PrintShellInit(Shell::Fish))
+        );
+        assert!(parse(&["init", "tcsh"]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+// The columns of the directory table, chosen and ordered in the config.
+// The margin and the icon in front of the name are always drawn.
+use serde::Deserialize;
+use tui::{layout::Constraint, text::Span};
+
+use crate::file_item_list::file_item::{calc_file_item_size, format_time, FileItem, SizeUnits};
+use crate::git_status::GitStatus;
+use crate::load_config::some;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ColumnKind {
+    Name,
+    Size,
+    Modified,
+    Created,
+    Accessed,
+    Changed,
+    Permissions,
+    Owner,
+    Group,
+    Inode,
+    Links,
+    Extension,
+    GitStatus,
+}
+
+impl ColumnKind {
+    pub const ALL: [ColumnKind; 13] = [
+        ColumnKind::Name,
+        ColumnKind::Size,
+        ColumnKind::Modified,
+        ColumnKind::Created,
+        ColumnKind::Accessed,
+        ColumnKind::Changed,
+        ColumnKind::Permissions,
+        ColumnKind::Owner,
+        ColumnKind::Group,
+        ColumnKind::Inode,
+        ColumnKind::Links,
+        ColumnKind::Extension,
+        ColumnKind::GitStatus,
+    ];
+
+    // also the name used by `:column <name>`
+    pub fn header(self) -> &'static str {
+        match self {
+            ColumnKind::Name => "name",
+            ColumnKind::Size => "size",
+            ColumnKind::Modified => "modified",
+            ColumnKind::Created => "created",
+            ColumnKind::Accessed => "accessed",
+            ColumnKind::Changed => "changed",
+            ColumnKind::Permissions => "permissions",
+            ColumnKind::Owner => "owner",
+            ColumnKind::Group => "group",
+            ColumnKind::Inode => "inode",
+            ColumnKind::Links => "links",
+            ColumnKind::Extension => "ext",
+            ColumnKind::GitStatus => "git",
+        }
+    }
+
+    pub fn from_header(header: &str) -> Option<ColumnKind> {
+        ColumnKind::ALL
+            .into_iter()
+            .find(|kind| kind.header().eq_ignore_ascii_case(header))
+    }
+
+    fn default_width(self) -> u16 {
+        match self {
+            ColumnKind::Name => 20,
+            ColumnKind::Size => 9,
+            ColumnKind::Modified
+            | ColumnKind::Created
+            | ColumnKind::Accessed
+            | ColumnKind::Changed => 16,
+            ColumnKind::Permissions => 11,
+            ColumnKind::Owner | ColumnKind::Group => 8,
+            ColumnKind::Inode => 10,
+            ColumnKind::Links => 5,
+            ColumnKind::Extension => 5,
+            ColumnKind::GitStatus => 3,
+        }
+    }
+
+    fn default_align(self) -> Align {
+        match self {
+            ColumnKind::Size | ColumnKind::Inode | ColumnKind::Links => Align::Right,
+            _ => Align::Left,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Column {
+    kind: ColumnKind,
+    #[serde(default, deserialize_with = "some")]
+    width: Option<u16>,
+    #[serde(default, deserialize_with = "some")]
+    align: Option<Align>,
+    // strftime for the dates instead of `date_format`, "rwx" or "octal" for the
+    // permissions, "iec" or "si" for the size
+    #[serde(default, deserialize_with = "some")]
+    format: Option<String>,
+    // listed but not drawn until it is toggled on
+    #[serde(default)]
+    hidden: bool,
+}
+
+impl Column {
+    pub fn new(kind: ColumnKind) -> Self {
+        Self {
+            kind,
+            width: None,
+            align: None,
+            format: None,
+            hidden: false,
+        }
+    }
+
+    fn with_width(mut self, width: u16) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn kind(&self) -> ColumnKind {
+        self.kind
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width.unwrap_or_else(|| self.kind.default_width())
+    }
+
+    pub fn constraint(&self) -> Constraint {
+        Constraint::Length(self.width())
+    }
+
+    pub fn header(&self) -> String {
+        self.aligned(self.kind.header().to_string())
+    }
+
+    pub fn size_units(&self) -> SizeUnits {
+        self.format
+            .as_deref()
+            .and_then(SizeUnits::from_name)
+            .unwrap_or_default()
+    }
+
+    // `git` is the status of the item if it is in a repository, `total` what is
+    // in it if it is a directory that has been added up
+    pub fn cell(
+        &self,
+        item: &FileItem,
+        date_format: &str,
+        git: Option<GitStatus>,
+        total: Option<u64>,
+    ) -> String {
+        let date_format = self.format.as_deref().unwrap_or(date_format);
+        let text = match self.kind {
+            ColumnKind::Name => match item.kinds().link_target() {
+                Some(target) => format!("{} -> {}", item.name(), target.path().display()),
+                None => item.name(),
+            },
+            ColumnKind::Size => match total {
+                Some(total) => calc_file_item_size(total, self.size_units()),
+                None => item.get_file_item_size(self.size_units()),
+            },
+            ColumnKind::Modified => format_time(item.get_modified_time(), date_format),
+            ColumnKind::Created => format_time(item.get_created_time(), date_format),
+            ColumnKind::Accessed => format_time(item.get_accessed_time(), date_format),
+            ColumnKind::Changed => format_time(item.get_changed_time(), date_format),
+            ColumnKind::Permissions => item.get_permission(self.format.as_deref() == Some("octal")),
+            ColumnKind::Owner => item.get_owner().unwrap_or_else(|| "-".to_string()),
+            ColumnKind::Group => item.get_group().unwrap_or_else(|| "-".to_string()),
+            ColumnKind::Inode => item
+                .get_inode()
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            ColumnKind::Links => item
+                .get_link_count()
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            ColumnKind::Extension => item.get_extension().unwrap_or_default(),
+            ColumnKind::GitStatus => git.map(GitStatus::symbol).unwrap_or_default(),
+        };
+        self.aligned(text)
+    }
+
+    // The table cuts off what is too long, only shorter text needs padding
+    fn aligned(&self, text: String) -> String {
+        let width = self.width() as usize;
+        let len = Span::raw(text.as_str()).width();
+        if len >= width {
+            return text;
+        }
+        let pad = width - len;
+        match self.align.unwrap_or_else(|| self.kind.default_align()) {
+            Align::Left => text,
+            Align::Right => format!("{}{}", " ".repeat(pad), text),
+            Align::Center => format!("{}{}", " ".repeat(pad / 2), text),
+        }
+    }
+}
+
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::new(ColumnKind::Name).with_width(20),
+        Column::new(ColumnKind::Permissions),
+        Column::new(ColumnKind::Size),
+        Column::new(ColumnKind::Modified),
+    ]
+}
+
+// Shows or hides a column, adding it at the end if the config doesn't list it.
+// Returns whether it is shown now.
+pub fn toggle_column(columns: &mut Vec<Column>, kind: ColumnKind) -> bool {
+    match columns.iter_mut().find(|c| c.kind == kind) {
+        Some(column) => {
+            column.hidden = !column.hidden;
+            !column.hidden
+        }
+        None => {
+            columns.push(Column::new(kind));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{default_columns, toggle_column, Align, Column, ColumnKind, SizeUnits};
+    use crate::load_config::parse_ron;
+
+    #[test]
+    fn columns_from_config() {
+        let columns: Vec<Column> = parse_ron(
+            r#"[
+                (kind: Name),
+                (kind: Size, width: 6, align: Left, format: "si"),
+                (kind: Modified, format: "%Y", hidden: true),
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(columns[0], Column::new(ColumnKind::Name));
+        assert_eq!(columns[1].width(), 6);
+        assert_eq!(columns[1].align, Some(Align::Left));
+        assert_eq!(columns[1].size_units(), SizeUnits::Si);
+        assert_eq!(columns[0].size_units(), SizeUnits::Iec);
+        assert_eq!(columns[2].format.as_deref(), Some("%Y"));
+        assert!(columns[2].is_hidden());
+        assert_eq!(ColumnKind::from_header("GIT"), Some(ColumnKind::GitStatus));
+    }
+
+    #[test]
+    fn toggling_hides_and_adds_columns() {
+        let mut columns = default_columns();
+        assert!(!toggle_column(&mut columns, ColumnKind::Size));
+        assert!(columns[2].is_hidden());
+        assert!(toggle_column(&mut columns, ColumnKind::Size));
+        assert!(toggle_column(&mut columns, ColumnKind::Inode));
+        assert_eq!(columns.last().unwrap().kind(), ColumnKind::Inode);
+
+        let size = Column::new(ColumnKind::Size).with_width(6);
+        assert_eq!(size.header(), "  size");
+    }
+}
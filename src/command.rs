@@ -0,0 +1,54 @@
+// Every command a keybinding can run, with the description shown in popups
+pub const COMMANDS: [(&str, &str); 41] = [
+    ("move_to_next_file_item", "next item"),
+    ("move_to_prev_file_item", "previous item"),
+    ("move_to_parent_dir", "parent directory"),
+    ("move_to_child_dir", "open the selected directory"),
+    ("move_to_top_of_file_item", "first item"),
+    ("move_to_bottom_of_file_item", "last item"),
+    ("next_dirtab", "next tab"),
+    ("prev_dirtab", "previous tab"),
+    ("move_back_in_history", "back in history"),
+    ("move_forward_in_history", "forward in history"),
+    ("open_history_picker", "pick from history"),
+    ("set_bookmark", "set a bookmark"),
+    ("jump_to_bookmark", "jump to a bookmark"),
+    ("open_bookmark_manager", "manage bookmarks"),
+    ("open_frecency_picker", "frequent directories"),
+    ("command_line", "command line"),
+    ("toggle_mark", "mark the selected item"),
+    ("toggle_column", "show or hide a column"),
+    ("toggle_hidden", "show or hide dotfiles and ignored items"),
+    ("follow_symlink", "go to the target of a link"),
+    ("make_symlink", "symlink the chosen items"),
+    ("make_hardlink", "hard link the chosen items"),
+    ("edit_symlink", "change the target of a link"),
+    ("extract_entries", "extract the chosen archive entries"),
+    ("extract_archive", "unpack the selected archive next to it"),
+    ("compress", "pack the chosen items into an archive"),
+    ("copy", "copy the chosen items to another tab"),
+    ("rename", "rename the selected item"),
+    ("delete", "delete the chosen items"),
+    (
+        "toggle_preview",
+        "show or hide the preview of the selected item",
+    ),
+    ("calc_dir_size", "add up the size of the chosen directories"),
+    ("toggle_disk_usage", "sort by size with bars, like ncdu"),
+    ("git_stage", "git add the chosen items"),
+    ("git_unstage", "unstage the chosen items"),
+    ("next_changed_file", "next item changed in git"),
+    ("reload_config", "reload the config"),
+    ("help", "list keybindings"),
+    ("quit", "quit"),
+    ("normal", "normal mode"),
+    ("input", "input mode"),
+    ("stacker", "stacker mode"),
+];
+
+pub fn command_description(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, description)| *description)
+}
@@ -0,0 +1,242 @@
+// How much is in a directory, added up on a thread so that a big tree doesn't
+// hold up the listing. A total is kept while the directory has the same inode
+// and modification time. A change deeper down doesn't touch either, so
+// `refresh` adds it up again on demand.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
+
+use crate::file_item_list::file_item::FileItem;
+use crate::file_item_list::Kinds;
+use crate::vfs::{Meta, Vfs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DirSize {
+    inode: Option<u64>,
+    modified: Option<SystemTime>,
+    total: u64,
+}
+
+impl DirSize {
+    fn new(meta: Option<&Meta>, total: u64) -> Self {
+        Self {
+            inode: meta.and_then(|meta| meta.inode),
+            modified: meta.and_then(|meta| meta.modified),
+            total,
+        }
+    }
+
+    fn is_valid_for(&self, meta: Option<&Meta>) -> bool {
+        *self == Self::new(meta, self.total)
+    }
+}
+
+struct Request {
+    path: PathBuf,
+    meta: Option<Meta>,
+    vfs: Arc<dyn Vfs>,
+}
+
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Request").field("path", &self.path).finish()
+    }
+}
+
+// (path, size) of the requested directory and of every directory below it
+type Totals = Vec<(PathBuf, DirSize)>;
+
+#[derive(Debug)]
+pub struct DirSizes {
+    cache: HashMap<PathBuf, DirSize>,
+    pending: HashSet<PathBuf>,
+    // started with the first request, one directory is added up at a time
+    worker: Option<Sender<Request>>,
+    tx: Sender<(PathBuf, Totals)>,
+    rx: Receiver<(PathBuf, Totals)>,
+}
+
+impl Default for DirSizes {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            worker: None,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl DirSizes {
+    // The total of a directory, if it was added up since it last changed
+    pub fn get(&self, item: &FileItem) -> Option<u64> {
+        self.cache
+            .get(item.path())
+            .filter(|size| size.is_valid_for(item.meta()))
+            .map(|size| size.total)
+    }
+
+    pub fn is_pending(&self, path: &Path) -> bool {
+        self.pending.contains(path)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Starts adding up a directory unless its total is known or coming
+    pub fn request(&mut self, item: &FileItem, vfs: Arc<dyn Vfs>) {
+        if !matches!(item.kinds(), Kinds::Directory(_))
+            || self.get(item).is_some()
+            || self.is_pending(item.path())
+        {
+            return;
+        }
+        let request = Request {
+            path: item.path().to_path_buf(),
+            meta: item.meta().cloned(),
+            vfs,
+        };
+        let worker = self
+            .worker
+            .get_or_insert_with(|| spawn_worker(self.tx.clone()));
+        self.pending.insert(request.path.clone());
+        if let Err(e) = worker.send(request) {
+            // the worker is gone, the next request starts another one
+            self.pending.remove(&e.0.path);
+            self.worker = None;
+        }
+    }
+
+    // Forgets what is known about the directory and below, and adds it up again
+    pub fn refresh(&mut self, item: &FileItem, vfs: Arc<dyn Vfs>) {
+        self.cache.retain(|path, _| !path.starts_with(item.path()));
+        self.request(item, vfs);
+    }
+
+    // Picks up finished totals, true when there were any
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((path, totals)) = self.rx.try_recv() {
+            self.pending.remove(&path);
+            self.cache.extend(totals);
+            changed = true;
+        }
+        changed
+    }
+}
+
+fn spawn_worker(tx: Sender<(PathBuf, Totals)>) -> Sender<Request> {
+    let (request_tx, request_rx) = channel::<Request>();
+    thread::spawn(move || {
+        for request in request_rx {
+            let mut totals = Vec::new();
+            let mut seen = HashSet::new();
+            let dev = request.meta.as_ref().and_then(|meta| meta.dev);
+            let total = add_up(&*request.vfs, &request.path, dev, &mut seen, &mut totals);
+            totals.push((
+                request.path.clone(),
+                DirSize::new(request.meta.as_ref(), total),
+            ));
+            if tx.send((request.path, totals)).is_err() {
+                return;
+            }
+        }
+    });
+    request_tx
+}
+
+// The length of every file below `dir` on the device `dev`, like `du -x`. Links
+// aren't followed and a file with several hard links is counted once. What
+// can't be read counts as nothing.
+fn add_up(
+    vfs: &dyn Vfs,
+    dir: &Path,
+    dev: Option<u64>,
+    seen: &mut HashSet<(Option<u64>, u64)>,
+    totals: &mut Totals,
+) -> u64 {
+    let (items, _) = vfs.read_dir(dir);
+    let mut total = 0;
+    for item in items {
+        let meta = match item.meta() {
+            Some(meta) => meta,
+            None => continue,
+        };
+        match item.kinds() {
+            // another filesystem mounted below
+            Kinds::Directory(_) if dev.is_some() && meta.dev != dev => {}
+            Kinds::Directory(_) => {
+                let sub = add_up(vfs, item.path(), dev, seen, totals);
+                totals.push((item.path().to_path_buf(), DirSize::new(Some(meta), sub)));
+                total += sub;
+            }
+            Kinds::File(_) => {
+                let linked = meta.nlink.is_some_and(|n| n > 1);
+                if linked
+                    && meta
+                        .inode
+                        .is_some_and(|inode| !seen.insert((meta.dev, inode)))
+                {
+                    continue;
+                }
+                total += meta.len;
+            }
+            Kinds::Symlink(..) | Kinds::Special(..) => {}
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::DirSizes;
+    use crate::vfs::{MemFs, Vfs};
+
+    #[test]
+    fn totals_are_added_up_and_cached() {
+        let fs = Arc::new(MemFs::new(Path::new("/mem")));
+        fs.create_dir(Path::new("/mem/a")).unwrap();
+        fs.create_dir(Path::new("/mem/a/b")).unwrap();
+        fs.write(Path::new("/mem/a/one"), b"1").unwrap();
+        fs.write(Path::new("/mem/a/b/four"), b"1234").unwrap();
+        let item_at = |path: &str| fs.stat(Path::new(path)).unwrap();
+
+        let mut sizes = DirSizes::default();
+        let wait = |sizes: &mut DirSizes| {
+            let start = Instant::now();
+            while sizes.is_busy() && start.elapsed() < Duration::from_secs(5) {
+                sizes.poll();
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+        sizes.request(&item_at("/mem/a"), fs.clone());
+        assert!(sizes.is_pending(Path::new("/mem/a")));
+        wait(&mut sizes);
+        assert_eq!(sizes.get(&item_at("/mem/a")), Some(5));
+        // directories below are known without asking
+        assert_eq!(sizes.get(&item_at("/mem/a/b")), Some(4));
+        // files don't need adding up
+        sizes.request(&item_at("/mem/a/one"), fs.clone());
+        assert!(!sizes.is_busy());
+
+        // a change deeper down is only seen when asked for
+        fs.write(Path::new("/mem/a/b/more"), b"12").unwrap();
+        sizes.request(&item_at("/mem/a"), fs.clone());
+        assert!(!sizes.is_busy());
+        assert_eq!(sizes.get(&item_at("/mem/a")), Some(5));
+        sizes.refresh(&item_at("/mem/a"), fs.clone());
+        wait(&mut sizes);
+        assert_eq!(sizes.get(&item_at("/mem/a")), Some(7));
+        assert_eq!(sizes.get(&item_at("/mem/a/b")), Some(6));
+    }
+}
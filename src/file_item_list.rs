@@ -1,27 +1,165 @@
-use std::{fs::Metadata, path::Path};
-
-use crate::path_process::pathbuf_to_string_name;
+use std::{
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
 
 pub mod directory_item;
 pub mod file_item;
 
+// The bool is whether the item is hidden
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Kinds {
     File(bool),
     Directory(bool),
+    Symlink(bool, LinkTarget),
+    Special(bool, SpecialKind),
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct LinkTarget {
+    // as stored in the link, may be relative to the link's directory
+    path: PathBuf,
+    is_dir: bool,
+    // the target doesn't exist
+    broken: bool,
+}
+
+impl LinkTarget {
+    pub fn new(path: PathBuf, is_dir: bool, broken: bool) -> Self {
+        Self {
+            path,
+            is_dir,
+            broken,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+pub enum SpecialKind {
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
 }
 
 impl Kinds {
+    // `meta` has to come from symlink_metadata so a link is seen as a link
     pub fn classifiy_kinds(path: &Path, meta: &Metadata) -> Self {
-        if path.is_file() || meta.is_file() {
-            Self::File(Self::is_hidden(path))
+        let hidden = Self::is_hidden(path);
+        let file_type = meta.file_type();
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(path).unwrap_or_default();
+            let followed = path.metadata();
+            return Self::Symlink(
+                hidden,
+                LinkTarget {
+                    path: target,
+                    is_dir: followed.as_ref().is_ok_and(|m| m.is_dir()),
+                    // not when it only can't be looked at, say for permissions
+                    broken: followed
+                        .as_ref()
+                        .is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound),
+                },
+            );
+        }
+        if file_type.is_dir() {
+            return Self::Directory(hidden);
+        }
+        match Self::special_kind(meta) {
+            Some(kind) => Self::Special(hidden, kind),
+            None => Self::File(hidden),
+        }
+    }
+
+    #[cfg(unix)]
+    fn special_kind(meta: &Metadata) -> Option<SpecialKind> {
+        use std::os::unix::fs::FileTypeExt;
+        let file_type = meta.file_type();
+        if file_type.is_fifo() {
+            Some(SpecialKind::Fifo)
+        } else if file_type.is_socket() {
+            Some(SpecialKind::Socket)
+        } else if file_type.is_char_device() {
+            Some(SpecialKind::CharDevice)
+        } else if file_type.is_block_device() {
+            Some(SpecialKind::BlockDevice)
         } else {
-            Self::Directory(Self::is_hidden(path))
+            None
         }
     }
 
+    #[cfg(not(unix))]
+    fn special_kind(_meta: &Metadata) -> Option<SpecialKind> {
+        None
+    }
+
     pub fn is_hidden(path: &Path) -> bool {
-        let file_item_name = pathbuf_to_string_name(path);
-        file_item_name.as_bytes()[0] == b'.'
+        path.file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+    }
+
+    // A directory, or a link that leads to one, can be opened
+    pub fn is_dir(&self) -> bool {
+        match self {
+            Self::Directory(_) => true,
+            Self::Symlink(_, target) => target.is_dir(),
+            _ => false,
+        }
+    }
+
+    pub fn link_target(&self) -> Option<&LinkTarget> {
+        match self {
+            Self::Symlink(_, target) => Some(target),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+
+    use super::Kinds;
+    use crate::temp_dir::TempDir;
+
+    fn kinds(path: &Path) -> Kinds {
+        Kinds::classifiy_kinds(path, &path.symlink_metadata().unwrap())
+    }
+
+    #[test]
+    fn links_are_classified_by_what_they_point_to() {
+        let dir = TempDir::new("kinds");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("file"), "").unwrap();
+        symlink("sub", dir.join("to_dir")).unwrap();
+        symlink("missing", dir.join("broken")).unwrap();
+
+        assert_eq!(kinds(&dir.join("file")), Kinds::File(false));
+        assert_eq!(kinds(&dir.join("sub")), Kinds::Directory(false));
+
+        let to_dir = kinds(&dir.join("to_dir"));
+        assert!(to_dir.is_dir());
+        assert_eq!(to_dir.link_target().unwrap().path(), PathBuf::from("sub"));
+
+        let broken = kinds(&dir.join("broken"));
+        assert!(!broken.is_dir());
+        assert!(broken.link_target().unwrap().is_broken());
+
+        // it can't be followed, but what it names is there
+        symlink("loop", dir.join("loop")).unwrap();
+        assert!(!kinds(&dir.join("loop")).link_target().unwrap().is_broken());
     }
 }
@@ -1,19 +1,19 @@
 use std::path::PathBuf;
 
-use crate::path_process::pathbuf_to_string_name;
+use crate::sftp::is_host_root;
 
 #[derive(Debug, Clone)]
 pub struct Directory {
-    name: String,
     path: PathBuf,
     parent: PathBuf,
 }
 
 impl Directory {
     pub fn new(path: PathBuf) -> Self {
-        let name = pathbuf_to_string_name(&path);
         let mut parent = path.clone();
-        if !parent.pop() {
+        if is_host_root(&path) {
+            // nothing is above the root of a remote host
+        } else if !parent.pop() {
             // TODO: macos, windows, linux
             // parent = PathBuf::from("root");
 
@@ -27,7 +27,7 @@ impl Directory {
                 parent = PathBuf::from(r"/")
             }
         }
-        Self { name, path, parent }
+        Self { path, parent }
     }
 
     pub fn pathbuf(&self) -> &PathBuf {
@@ -37,10 +37,6 @@ impl Directory {
     pub fn parent(&self) -> &PathBuf {
         &self.parent
     }
-
-    pub fn name(&self) -> &str {
-        &self.name
-    }
 }
 
 #[cfg(test)]
@@ -62,10 +58,12 @@ mod test {
     }
 
     #[test]
-    #[cfg(target_os = "windows")]
-    fn directory_name_test() {
-        let path = PathBuf::from("sample/test/");
-        let dir = Directory::new(path);
-        assert_eq!(dir.name(), "test");
+    #[cfg(not(target_os = "windows"))]
+    fn root_parent_test() {
+        let dir = Directory::new(PathBuf::from("/"));
+        assert_eq!(dir.parent(), &PathBuf::from("/"));
+
+        let dir = Directory::new(PathBuf::from("/home"));
+        assert_eq!(dir.parent(), &PathBuf::from("/"));
     }
 }
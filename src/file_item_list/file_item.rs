@@ -1,156 +1,298 @@
-use super::Kinds;
-use chrono::{DateTime, Utc};
+use super::{Kinds, SpecialKind};
+use crate::path_process::{escape_os_str, pathbuf_to_string_name};
+use crate::vfs::Meta;
+use chrono::{DateTime, Local};
 use std::{
-    fs::Metadata,
+    ffi::{OsStr, OsString},
+    fmt::Write,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
+#[cfg(unix)]
+use uzers::{Groups, Users, UsersCache};
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub enum Extension {
-    C,
-    CPlusPlus,
-    CSharp,
-    Go,
-    Java,
-    JavaScript,
-    Markdown,
-    Rust,
-    Ruby,
-    Python,
-    Perl,
-    Toml,
-    Unknwon,
-}
-
-impl Extension {
-    pub fn classify_extension(path: &Path) -> Self {
-        match path.extension() {
-            None => Self::Unknwon,
-            Some(extension) => {
-                let extension = extension.to_str();
-                match extension {
-                    Some("c") => Self::C,
-                    Some("cpp") => Self::CPlusPlus,
-                    Some("cs") => Self::CSharp,
-                    Some("go") => Self::Go,
-                    Some("java") => Self::Java,
-                    Some("js") => Self::JavaScript,
-                    Some("md") => Self::Markdown,
-                    Some("pl") => Self::Perl,
-                    Some("py") => Self::Python,
-                    Some("rb") => Self::Ruby,
-                    Some("rs") => Self::Rust,
-                    Some("toml") => Self::Toml,
-                    _ => Self::Unknwon,
-                }
-            }
-        }
-    }
+// Looking up a name reads /etc/passwd or asks NSS, so each uid and gid is only
+// looked up once
+#[cfg(unix)]
+thread_local! {
+    static USERS: UsersCache = UsersCache::new();
 }
 
 #[derive(Debug, Clone)]
 pub struct FileItem {
+    // the name as it is on disk, and escaped for showing
+    file_name: OsString,
     name: String,
     path: PathBuf,
-    meta: Metadata,
+    // None when it couldn't be read, `error` says why
+    meta: Option<Meta>,
+    error: Option<String>,
     kinds: Kinds,
-    extension: Option<Extension>,
 }
 
 impl FileItem {
-    pub fn new(
-        name: String,
-        path: PathBuf,
-        meta: Metadata,
-        kinds: Kinds,
-        extension: Option<Extension>,
-    ) -> Self {
+    pub fn new(path: PathBuf, meta: Meta, kinds: Kinds) -> Self {
         Self {
-            name,
+            file_name: path.file_name().unwrap_or_default().to_os_string(),
+            name: pathbuf_to_string_name(&path),
             path,
-            meta,
+            meta: Some(meta),
+            error: None,
             kinds,
-            extension,
         }
     }
 
+    // An item of a backend that only has files and directories
+    pub fn plain(path: PathBuf, meta: Meta, is_dir: bool) -> Self {
+        let hidden = Kinds::is_hidden(&path);
+        let kinds = match is_dir {
+            true => Kinds::Directory(hidden),
+            false => Kinds::File(hidden),
+        };
+        Self::new(path, meta, kinds)
+    }
+
+    // An entry that is listed but whose metadata couldn't be read
+    pub fn unreadable(path: PathBuf, error: String) -> Self {
+        let kinds = Kinds::File(Kinds::is_hidden(&path));
+        Self {
+            file_name: path.file_name().unwrap_or_default().to_os_string(),
+            name: pathbuf_to_string_name(&path),
+            path,
+            meta: None,
+            error: Some(error),
+            kinds,
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
-    pub fn meta(&self) -> &Metadata {
-        &self.meta
+    pub fn meta(&self) -> Option<&Meta> {
+        self.meta.as_ref()
+    }
+
+    pub fn file_name(&self) -> &OsStr {
+        &self.file_name
     }
 
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
-    pub fn kinds(&self) -> Kinds {
-        self.kinds.clone()
+    pub fn kinds(&self) -> &Kinds {
+        &self.kinds
+    }
+
+    // The length of a directory says nothing about what is in it, so it is
+    // left out. Its total comes from DirSizes.
+    pub fn get_file_item_size(&self, units: SizeUnits) -> String {
+        match (&self.meta, self.kinds.is_dir()) {
+            (Some(meta), false) => calc_file_item_size(meta.len, units),
+            _ => "-".to_string(),
+        }
+    }
+
+    // "drwxr-xr-x", or "0755" in octal
+    pub fn get_permission(&self, octal: bool) -> String {
+        let mode = match self.meta.as_ref().and_then(|meta| meta.mode) {
+            Some(mode) => mode,
+            None => return "?".to_string(),
+        };
+        if octal {
+            return format!("{:04o}", mode & 0o7777);
+        }
+        mode_string(file_type_char(&self.kinds), mode)
+    }
+
+    pub fn get_modified_time(&self) -> Option<SystemTime> {
+        self.meta.as_ref()?.modified
+    }
+
+    pub fn get_accessed_time(&self) -> Option<SystemTime> {
+        self.meta.as_ref()?.accessed
+    }
+
+    pub fn get_created_time(&self) -> Option<SystemTime> {
+        self.meta.as_ref()?.created
+    }
+
+    // When the inode last changed, only unix keeps it
+    pub fn get_changed_time(&self) -> Option<SystemTime> {
+        self.meta.as_ref()?.changed
     }
 
-    pub fn get_file_item_size(&self) -> String {
-        let size = self.meta.len();
-        calc_file_item_size(size)
+    // uid, gid, inode and hard link count only exist on unix. Users and groups
+    // without a name show their id.
+    #[cfg(unix)]
+    pub fn get_owner(&self) -> Option<String> {
+        let uid = self.meta.as_ref()?.uid?;
+        let name = USERS.with(|users| users.get_user_by_uid(uid));
+        Some(name.map_or_else(
+            || uid.to_string(),
+            |user| user.name().to_string_lossy().into_owned(),
+        ))
     }
 
-    // true: write and read, false: only read
-    pub fn get_permission(&self) -> bool {
-        let perm = self.meta.permissions();
-        perm.readonly()
+    #[cfg(not(unix))]
+    pub fn get_owner(&self) -> Option<String> {
+        None
     }
 
-    pub fn get_created_date_and_time(&self) -> String {
-        let time = self.meta.created();
-        if time.is_err() {
-            return "-".to_string();
+    #[cfg(unix)]
+    pub fn get_group(&self) -> Option<String> {
+        let gid = self.meta.as_ref()?.gid?;
+        let name = USERS.with(|users| users.get_group_by_gid(gid));
+        Some(name.map_or_else(
+            || gid.to_string(),
+            |group| group.name().to_string_lossy().into_owned(),
+        ))
+    }
+
+    #[cfg(not(unix))]
+    pub fn get_group(&self) -> Option<String> {
+        None
+    }
+
+    pub fn get_inode(&self) -> Option<u64> {
+        self.meta.as_ref()?.inode
+    }
+
+    pub fn get_link_count(&self) -> Option<u64> {
+        self.meta.as_ref()?.nlink
+    }
+
+    pub fn get_extension(&self) -> Option<String> {
+        self.path.extension().map(escape_os_str)
+    }
+}
+
+// In the local time zone. An invalid format shows "-" rather than panicking
+// like to_string() would.
+pub fn format_time(time: Option<SystemTime>, format: &str) -> String {
+    let time: DateTime<Local> = match time {
+        Some(time) => time.into(),
+        None => return "-".to_string(),
+    };
+    let mut formatted = String::new();
+    match write!(formatted, "{}", time.format(format)) {
+        Ok(()) => formatted,
+        Err(_) => "-".to_string(),
+    }
+}
+
+// The type as `ls -l` shows it
+fn file_type_char(kinds: &Kinds) -> char {
+    match kinds {
+        Kinds::File(_) => '-',
+        Kinds::Directory(_) => 'd',
+        Kinds::Symlink(..) => 'l',
+        Kinds::Special(_, SpecialKind::Fifo) => 'p',
+        Kinds::Special(_, SpecialKind::Socket) => 's',
+        Kinds::Special(_, SpecialKind::CharDevice) => 'c',
+        Kinds::Special(_, SpecialKind::BlockDevice) => 'b',
+    }
+}
+
+// "drwxr-sr-t": the type, then rwx for user, group and others with the
+// setuid, setgid and sticky bits in place of x (upper case when x is not set)
+fn mode_string(kind: char, mode: u32) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    let special = [(0o4000, 's'), (0o2000, 's'), (0o1000, 't')];
+    for (i, shift) in [6, 3, 0].into_iter().enumerate() {
+        let bits = mode >> shift;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        let (bit, c) = special[i];
+        s.push(match (bits & 0o1 != 0, mode & bit != 0) {
+            (true, true) => c,
+            (false, true) => c.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+// IEC steps by 1024 (KiB, MiB), SI by 1000 (kB, MB)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeUnits {
+    #[default]
+    Iec,
+    Si,
+}
+
+impl SizeUnits {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "iec" => Some(Self::Iec),
+            "si" => Some(Self::Si),
+            _ => None,
         }
+    }
 
-        let created_time: DateTime<Utc> = time.unwrap().into();
-        created_time.format("%F-%R").to_string()
+    fn base(self) -> f64 {
+        match self {
+            Self::Iec => 1024.0,
+            Self::Si => 1000.0,
+        }
+    }
+
+    fn units(self) -> [&'static str; 7] {
+        match self {
+            Self::Iec => ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+            Self::Si => ["B", "kB", "MB", "GB", "TB", "PB", "EB"],
+        }
     }
 }
 
-const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
-const DECIMAL_PLACE: f64 = 100.0;
-fn calc_file_item_size(byte: u64) -> String {
-    if byte < 1 {
-        return format!("{:>5}", "-");
+// "512 B", "1.5 KiB", "340 MiB", one decimal below 10 like `ls -h`
+pub fn calc_file_item_size(byte: u64, units: SizeUnits) -> String {
+    let names = units.units();
+    let mut size = byte as f64;
+    let mut i = 0;
+    while size >= units.base() && i < names.len() - 1 {
+        size /= units.base();
+        i += 1;
+    }
+    match i == 0 || size >= 10.0 {
+        true => format!("{:.0} {}", size, names[i]),
+        false => format!("{:.1} {}", size, names[i]),
     }
-    let byte = byte as f64;
-    let i = byte.log(1024.0).round();
-    let size = byte / 1024.0f64.powf(i) * DECIMAL_PLACE;
-    format!("{:>5} {}", size.round() / DECIMAL_PLACE, UNITS[i as usize])
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::Path;
+    use super::{calc_file_item_size, mode_string, SizeUnits};
 
-    use super::Extension;
+    #[test]
+    fn mode_bits_read_like_ls() {
+        assert_eq!(mode_string('d', 0o755), "drwxr-xr-x");
+        assert_eq!(mode_string('-', 0o644), "-rw-r--r--");
+        assert_eq!(mode_string('-', 0o4755), "-rwsr-xr-x");
+        assert_eq!(mode_string('d', 0o1777), "drwxrwxrwt");
+        assert_eq!(mode_string('-', 0o2644), "-rw-r-Sr--");
+    }
 
     #[test]
-    fn test_type_extension() {
-        let files = [
-            ("sample.py", Extension::Python),
-            ("sample.rb", Extension::Ruby),
-            ("sample.c", Extension::C),
-            ("sample.cs", Extension::CSharp),
-            ("sample.cpp", Extension::CPlusPlus),
-            ("sample.go", Extension::Go),
-            ("sample.java", Extension::Java),
-            ("sample.js", Extension::JavaScript),
-            ("sample.md", Extension::Markdown),
-            ("sample.pl", Extension::Perl),
-            ("sample.rs", Extension::Rust),
-            ("sample.toml", Extension::Toml),
-            ("aaa", Extension::Unknwon),
+    fn sizes_are_shown_in_either_unit() {
+        let cases = [
+            (0, "0 B", "0 B"),
+            (999, "999 B", "999 B"),
+            (1536, "1.5 KiB", "1.5 kB"),
+            (1_000_000, "977 KiB", "1.0 MB"),
+            (5 * 1024 * 1024 * 1024, "5.0 GiB", "5.4 GB"),
+            (u64::MAX, "16 EiB", "18 EB"),
         ];
-        for (filename, types) in files.iter() {
-            let path = Path::new(filename);
-            let path_ex = Extension::classify_extension(path);
-            assert_eq!(path_ex, *types);
+        for (byte, iec, si) in cases {
+            assert_eq!(calc_file_item_size(byte, SizeUnits::Iec), iec);
+            assert_eq!(calc_file_item_size(byte, SizeUnits::Si), si);
         }
     }
 }
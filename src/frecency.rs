@@ -0,0 +1,293 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::path_process::load_ron_file;
+
+pub const FRECENCY_FILE_NAME: &str = "frecency.ron";
+
+// Same aging as zoxide: once the ranks add up to this, every rank is scaled down.
+const MAX_TOTAL_RANK: f64 = 10000.0;
+const AGING_FACTOR: f64 = 0.9;
+const MIN_RANK: f64 = 1.0;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+pub fn now_epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrecencyEntry {
+    path: PathBuf,
+    rank: f64,
+    last_access: u64,
+}
+
+impl FrecencyEntry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Frequency weighted by how recently the directory was visited
+    pub fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_access);
+        if age < HOUR {
+            self.rank * 4.0
+        } else if age < DAY {
+            self.rank * 2.0
+        } else if age < WEEK {
+            self.rank * 0.5
+        } else {
+            self.rank * 0.25
+        }
+    }
+
+    // Every fragment has to appear in order, and the last one in the last component.
+    fn is_match(&self, fragments: &[String]) -> bool {
+        let path = self.path.to_string_lossy().to_lowercase();
+        let mut rest = path.as_str();
+        for frag in fragments {
+            match rest.find(frag.as_str()) {
+                Some(i) => rest = &rest[i + frag.len()..],
+                None => return false,
+            }
+        }
+        match (fragments.last(), self.path.file_name()) {
+            (Some(last), Some(name)) => name.to_string_lossy().to_lowercase().contains(last),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FrecencyDb {
+    entries: Vec<FrecencyEntry>,
+}
+
+impl FrecencyDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A broken file is moved aside and the reason returned
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        load_ron_file(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, s)
+    }
+
+    pub fn visit(&mut self, path: &Path, now: u64) {
+        self.add(path, 1.0, now);
+        self.age();
+    }
+
+    fn add(&mut self, path: &Path, rank: f64, last_access: u64) {
+        match self.entries.iter_mut().find(|e| e.path == path) {
+            Some(entry) => {
+                entry.rank += rank;
+                entry.last_access = entry.last_access.max(last_access);
+            }
+            None => self.entries.push(FrecencyEntry {
+                path: path.to_path_buf(),
+                rank,
+                last_access,
+            }),
+        }
+    }
+
+    fn age(&mut self) {
+        let total: f64 = self.entries.iter().map(|e| e.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for entry in self.entries.iter_mut() {
+                entry.rank *= AGING_FACTOR;
+            }
+            self.entries.retain(|e| e.rank >= MIN_RANK);
+        }
+    }
+
+    // Best score first. Directories that no longer exist are left out.
+    pub fn ranked(&self, now: u64) -> Vec<(f64, &FrecencyEntry)> {
+        let mut ranked: Vec<(f64, &FrecencyEntry)> = self
+            .entries
+            .iter()
+            .filter(|e| e.path.is_dir())
+            .map(|e| (e.score(now), e))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranked
+    }
+
+    pub fn query(&self, fragments: &[&str], exclude: &Path, now: u64) -> Option<PathBuf> {
+        let fragments: Vec<String> = fragments.iter().map(|f| f.to_lowercase()).collect();
+        self.ranked(now)
+            .into_iter()
+            .map(|(_, e)| e)
+            .find(|e| e.path != exclude && e.is_match(&fragments))
+            .map(|e| e.path.clone())
+    }
+
+    // Reads a zoxide (db.zo), z (~/.z) or autojump (autojump.txt) database.
+    // Returns how many directories were imported.
+    pub fn import(&mut self, path: &Path) -> io::Result<usize> {
+        let bytes = std::fs::read(path)?;
+        let imported = if bytes.starts_with(&ZOXIDE_VERSION.to_le_bytes()) {
+            parse_zoxide(&bytes)?
+        } else {
+            parse_z_or_autojump(&String::from_utf8_lossy(&bytes))
+        };
+        let count = imported.len();
+        for (path, rank, last_access) in imported {
+            self.add(&path, rank, last_access);
+        }
+        self.age();
+        Ok(count)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+const ZOXIDE_VERSION: u32 = 3;
+
+// zoxide writes a version number and then bincode: u64 length, and for each directory
+// the path (u64 length + utf-8), the rank as f64 and the last access as u64.
+fn parse_zoxide(bytes: &[u8]) -> io::Result<Vec<(PathBuf, f64, u64)>> {
+    let mut pos: usize = 4;
+    let mut take = |n: usize| -> io::Result<&[u8]> {
+        let slice = pos
+            .checked_add(n)
+            .and_then(|end| bytes.get(pos..end))
+            .ok_or_else(|| invalid_data("unexpected end of zoxide database"))?;
+        pos += n;
+        Ok(slice)
+    };
+    let read_u64 = |b: &[u8]| u64::from_le_bytes(b.try_into().unwrap());
+
+    let len = read_u64(take(8)?);
+    let mut dirs = Vec::new();
+    for _ in 0..len {
+        let path_len = read_u64(take(8)?) as usize;
+        let path = String::from_utf8_lossy(take(path_len)?).to_string();
+        let rank = f64::from_le_bytes(take(8)?.try_into().unwrap());
+        let last_access = read_u64(take(8)?);
+        if rank.is_finite() {
+            dirs.push((PathBuf::from(path), rank, last_access));
+        }
+    }
+    Ok(dirs)
+}
+
+// z: `path|rank|time`, autojump: `weight<TAB>path`
+fn parse_z_or_autojump(text: &str) -> Vec<(PathBuf, f64, u64)> {
+    let now = now_epoch_secs();
+    // "NaN" and "inf" parse too, but can't be ranked
+    let rank = |s: &str| s.trim().parse::<f64>().ok().filter(|r| r.is_finite());
+    text.lines()
+        .filter_map(|line| {
+            if let Some((weight, path)) = line.split_once('\t') {
+                return Some((PathBuf::from(path), rank(weight)?, now));
+            }
+            let mut fields = line.rsplitn(3, '|');
+            let time = fields.next()?.trim().parse().ok()?;
+            let rank = rank(fields.next()?)?;
+            let path = fields.next()?;
+            Some((PathBuf::from(path), rank, time))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{parse_z_or_autojump, parse_zoxide, FrecencyDb, FrecencyEntry, DAY, WEEK};
+
+    fn entry(path: &str) -> FrecencyEntry {
+        FrecencyEntry {
+            path: PathBuf::from(path),
+            rank: 1.0,
+            last_access: 0,
+        }
+    }
+
+    #[test]
+    fn recent_visits_score_higher() {
+        let mut e = entry("/a");
+        e.last_access = 10 * WEEK;
+        assert_eq!(e.score(10 * WEEK + 10), 4.0);
+        assert_eq!(e.score(10 * WEEK + 2 * DAY), 0.5);
+        assert_eq!(e.score(20 * WEEK), 0.25);
+
+        let mut db = FrecencyDb::new();
+        db.visit(&PathBuf::from("/a"), 0);
+        db.visit(&PathBuf::from("/a"), 5);
+        assert_eq!(db.entries.len(), 1);
+        assert_eq!(db.entries[0].rank, 2.0);
+        assert_eq!(db.entries[0].last_access, 5);
+    }
+
+    #[test]
+    fn fragments_match_in_order() {
+        let e = entry("/home/user/projects/simple-tui");
+        let frags = |s: &[&str]| s.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert!(e.is_match(&frags(&["proj", "tui"])));
+        assert!(!e.is_match(&frags(&["tui", "proj"])));
+        // the last fragment must be in the last component
+        assert!(!e.is_match(&frags(&["proj"])));
+    }
+
+    #[test]
+    fn parse_other_databases() {
+        let z = "/home/user/src|12.5|1650000000\n/tmp|1|1650000001\n";
+        let dirs = parse_z_or_autojump(z);
+        assert_eq!(dirs[0], (PathBuf::from("/home/user/src"), 12.5, 1650000000));
+        assert_eq!(dirs.len(), 2);
+
+        let dirs = parse_z_or_autojump("/a|NaN|1\n/b|inf|1\nNaN\t/c\n");
+        assert!(dirs.is_empty());
+
+        let autojump = "22.4\t/home/user/src\n";
+        let dirs = parse_z_or_autojump(autojump);
+        assert_eq!(dirs[0].0, PathBuf::from("/home/user/src"));
+        assert_eq!(dirs[0].1, 22.4);
+
+        let mut zo: Vec<u8> = 3u32.to_le_bytes().to_vec();
+        zo.extend(1u64.to_le_bytes());
+        zo.extend(4u64.to_le_bytes());
+        zo.extend(b"/src");
+        zo.extend(3.0f64.to_le_bytes());
+        zo.extend(42u64.to_le_bytes());
+        let dirs = parse_zoxide(&zo).unwrap();
+        assert_eq!(dirs, vec![(PathBuf::from("/src"), 3.0, 42)]);
+        assert!(parse_zoxide(&zo[..zo.len() - 1]).is_err());
+
+        let mut nan = zo.clone();
+        let at = nan.len() - 16;
+        nan[at..at + 8].copy_from_slice(&f64::NAN.to_le_bytes());
+        assert!(parse_zoxide(&nan).unwrap().is_empty());
+
+        // a path length that would run past the end of memory
+        let mut huge = 3u32.to_le_bytes().to_vec();
+        huge.extend(1u64.to_le_bytes());
+        huge.extend(u64::MAX.to_le_bytes());
+        assert!(parse_zoxide(&huge).is_err());
+    }
+}
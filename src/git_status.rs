@@ -0,0 +1,346 @@
+// Git status of the repository the current directory is in. `git status` runs
+// on a thread so a big repository doesn't hold up the listing, and runs again
+// when the directory or the repository's index changes.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+use crate::vfs::local_stamp;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GitStatus {
+    staged: bool,
+    modified: bool,
+    untracked: bool,
+    ignored: bool,
+    conflicted: bool,
+}
+
+impl GitStatus {
+    // The two letters of `git status --porcelain`, index then work tree
+    fn from_xy(x: u8, y: u8) -> Self {
+        let mut status = GitStatus::default();
+        match (x, y) {
+            (b'?', b'?') => status.untracked = true,
+            (b'!', b'!') => status.ignored = true,
+            (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => status.conflicted = true,
+            _ => {
+                status.staged = x != b' ';
+                status.modified = y != b' ';
+            }
+        }
+        status
+    }
+
+    fn merge(&mut self, other: GitStatus) {
+        self.staged |= other.staged;
+        self.modified |= other.modified;
+        self.untracked |= other.untracked;
+        self.ignored |= other.ignored;
+        self.conflicted |= other.conflicted;
+    }
+
+    pub fn is_changed(self) -> bool {
+        self.staged || self.modified || self.untracked || self.conflicted
+    }
+
+    // "U" for a conflict, otherwise "+" when staged followed by "M" when
+    // modified or "?" when untracked, "!" when only ignored
+    pub fn symbol(self) -> String {
+        if self.conflicted {
+            return "U".to_string();
+        }
+        let mut s = String::new();
+        if self.staged {
+            s.push('+');
+        }
+        if self.modified {
+            s.push('M');
+        } else if self.untracked {
+            s.push('?');
+        }
+        if s.is_empty() && self.ignored {
+            s.push('!');
+        }
+        s
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoStatus {
+    root: PathBuf,
+    // None on a detached HEAD
+    branch: Option<String>,
+    ahead: usize,
+    behind: usize,
+    // the changed files and every directory above them
+    entries: HashMap<PathBuf, GitStatus>,
+    // untracked or ignored directories, git doesn't list what is inside
+    whole_dirs: Vec<(PathBuf, GitStatus)>,
+}
+
+impl RepoStatus {
+    // `output` is what `git status --porcelain -z --branch --ignored` printed
+    pub fn parse(root: PathBuf, output: &[u8]) -> Self {
+        let mut repo = RepoStatus {
+            root,
+            ..RepoStatus::default()
+        };
+        let mut fields = output.split(|b| *b == 0).filter(|f| !f.is_empty());
+        while let Some(field) = fields.next() {
+            if let Some(header) = field.strip_prefix(b"## ") {
+                repo.parse_branch(&String::from_utf8_lossy(header));
+                continue;
+            }
+            if field.len() < 4 {
+                continue;
+            }
+            let (x, y) = (field[0], field[1]);
+            // a rename or copy is followed by the old path
+            if x == b'R' || x == b'C' {
+                fields.next();
+            }
+            let status = GitStatus::from_xy(x, y);
+            let rel = &field[3..];
+            let path = repo
+                .root
+                .join(path_from_bytes(rel.strip_suffix(b"/").unwrap_or(rel)));
+            if rel.ends_with(b"/") {
+                repo.whole_dirs.push((path.clone(), status));
+            }
+            repo.add(path, status);
+        }
+        repo
+    }
+
+    // "main...origin/main [ahead 1, behind 2]", "No commits yet on main" or
+    // "HEAD (no branch)"
+    fn parse_branch(&mut self, header: &str) {
+        if header.starts_with("HEAD (no branch)") {
+            return;
+        }
+        let (name, counts) = match header.split_once(" [") {
+            Some((name, counts)) => (name, counts.trim_end_matches(']')),
+            None => (header, ""),
+        };
+        let name = name
+            .strip_prefix("No commits yet on ")
+            .or_else(|| name.strip_prefix("Initial commit on "))
+            .unwrap_or(name);
+        let name = name.split_once("...").map_or(name, |(local, _)| local);
+        self.branch = Some(name.to_string());
+        for count in counts.split(", ") {
+            let n = |s: &str| s.parse().unwrap_or(0);
+            if let Some(s) = count.strip_prefix("ahead ") {
+                self.ahead = n(s);
+            } else if let Some(s) = count.strip_prefix("behind ") {
+                self.behind = n(s);
+            }
+        }
+    }
+
+    // A directory holding something ignored isn't ignored itself
+    fn add(&mut self, path: PathBuf, status: GitStatus) {
+        let rolled_up = GitStatus {
+            ignored: false,
+            ..status
+        };
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) || dir == self.root {
+                break;
+            }
+            self.entries
+                .entry(dir.to_path_buf())
+                .or_default()
+                .merge(rolled_up);
+        }
+        self.entries.entry(path).or_default().merge(status);
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn status_of(&self, path: &Path) -> Option<GitStatus> {
+        if let Some(status) = self.entries.get(path) {
+            return Some(*status);
+        }
+        self.whole_dirs
+            .iter()
+            .find(|(dir, _)| path.starts_with(dir))
+            .map(|(_, status)| *status)
+    }
+
+    // "main ↑1 ↓2"
+    pub fn branch_summary(&self) -> String {
+        let mut s = self
+            .branch
+            .clone()
+            .unwrap_or_else(|| "detached".to_string());
+        if self.ahead > 0 {
+            s.push_str(&format!(" ↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            s.push_str(&format!(" ↓{}", self.behind));
+        }
+        s
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// None outside of a repository or without git
+fn read_status(dir: &Path) -> Option<RepoStatus> {
+    let git = || {
+        let mut cmd = Command::new("git");
+        // a status running in the background mustn't lock the index
+        cmd.arg("--no-optional-locks").arg("-C").arg(dir);
+        cmd
+    };
+    let prefix = git().args(["rev-parse", "--show-prefix"]).output().ok()?;
+    if !prefix.status.success() {
+        return None;
+    }
+    // the root is found from `dir` so it is spelled the same way as the listing
+    let depth = String::from_utf8_lossy(&prefix.stdout)
+        .trim_end()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .count();
+    let root = dir.ancestors().nth(depth)?.to_path_buf();
+    let output = git()
+        .args(["status", "--porcelain", "-z", "--branch", "--ignored"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(RepoStatus::parse(root, &output.stdout))
+}
+
+// Runs `git` with `args` followed by `paths` in `dir`, returns what it printed on failure
+pub fn run_git(dir: &Path, args: &[&str], paths: &[PathBuf]) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .arg("--")
+        .args(paths)
+        .output()
+        .map_err(|e| format!("git: {}", e))?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GitWatcher {
+    status: Option<RepoStatus>,
+    // the directory the last status was asked for
+    dir: Option<PathBuf>,
+    // .git/index of the repository `dir` is in, found without running git
+    index: Option<PathBuf>,
+    // the stamps of `dir` and `index` then, None to ask again
+    stamps: Option<(Option<u64>, Option<u64>)>,
+    pending: Option<Receiver<Option<RepoStatus>>>,
+}
+
+impl GitWatcher {
+    // Picks up a finished status and starts another one when `dir` is another
+    // one, or it or the index changed. Cheap enough to call every frame.
+    pub fn update(&mut self, dir: &Path) {
+        if let Some(rx) = &self.pending {
+            match rx.try_recv() {
+                Ok(status) => {
+                    self.status = status;
+                    self.pending = None;
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+        if self.dir.as_deref() != Some(dir) {
+            self.dir = Some(dir.to_path_buf());
+            self.index = dir
+                .ancestors()
+                .map(|dir| dir.join(".git"))
+                .find(|git| git.exists())
+                .map(|git| git.join("index"));
+            self.stamps = None;
+        }
+        let stamps = (
+            local_stamp(dir),
+            self.index.as_deref().and_then(local_stamp),
+        );
+        if self.stamps == Some(stamps) {
+            return;
+        }
+        let (tx, rx) = channel();
+        let owned = dir.to_path_buf();
+        thread::spawn(move || {
+            let _ = tx.send(read_status(&owned));
+        });
+        self.pending = Some(rx);
+        self.stamps = Some(stamps);
+    }
+
+    // Ask again on the next update, after something was staged
+    pub fn refresh(&mut self) {
+        self.stamps = None;
+    }
+
+    // The status of the repository `dir` is in, if it is known yet
+    pub fn status_for(&self, dir: &Path) -> Option<&RepoStatus> {
+        self.status
+            .as_ref()
+            .filter(|status| dir.starts_with(status.root()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::RepoStatus;
+
+    #[test]
+    fn porcelain_output_is_rolled_up() {
+        let output =
+            b"## main...origin/main [ahead 2, behind 1]\0 M src/app.rs\0M  src/ui/draw.rs\0\
+R  new.rs\0old.rs\0?? notes/\0!! target/\0UU merge.rs\0";
+        let repo = RepoStatus::parse(PathBuf::from("/repo"), output);
+        let symbol = |path: &str| repo.status_of(Path::new(path)).map(|s| s.symbol());
+
+        assert_eq!(repo.branch_summary(), "main ↑2 ↓1");
+        assert_eq!(symbol("/repo/src/app.rs").as_deref(), Some("M"));
+        assert_eq!(symbol("/repo/src/ui/draw.rs").as_deref(), Some("+"));
+        assert_eq!(symbol("/repo/src").as_deref(), Some("+M"));
+        assert_eq!(symbol("/repo/new.rs").as_deref(), Some("+"));
+        assert_eq!(symbol("/repo/old.rs"), None);
+        assert_eq!(symbol("/repo/notes/a/b.txt").as_deref(), Some("?"));
+        assert_eq!(symbol("/repo/target/debug").as_deref(), Some("!"));
+        assert_eq!(symbol("/repo/merge.rs").as_deref(), Some("U"));
+        assert_eq!(symbol("/repo/README.md"), None);
+        assert!(!repo
+            .status_of(Path::new("/repo/target"))
+            .unwrap()
+            .is_changed());
+
+        let repo = RepoStatus::parse(PathBuf::from("/repo"), b"## No commits yet on dev\0");
+        assert_eq!(repo.branch_summary(), "dev");
+        let repo = RepoStatus::parse(PathBuf::from("/repo"), b"## HEAD (no branch)\0");
+        assert_eq!(repo.branch_summary(), "detached");
+    }
+}
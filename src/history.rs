@@ -0,0 +1,152 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::path_process::load_ron_file;
+
+// TODO: Restrictions without reason, so think cost
+const MAX_TAB_HISTORY_SIZE: usize = 100;
+const MAX_RECENT_DIRS_SIZE: usize = 50;
+pub const RECENT_DIRS_FILE_NAME: &str = "recent_dirs.ron";
+
+// A visited directory and the name of the file item that was selected in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    path: PathBuf,
+    selected: Option<OsString>,
+}
+
+impl HistoryEntry {
+    pub fn new(path: PathBuf, selected: Option<OsString>) -> Self {
+        Self { path, selected }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_parts(self) -> (PathBuf, Option<OsString>) {
+        (self.path, self.selected)
+    }
+}
+
+// Back/forward stacks of a single tab, like the jump list of vim.
+#[derive(Debug, Clone, Default)]
+pub struct DirHistory {
+    back: Vec<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
+}
+
+impl DirHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record the directory that is being left. Going somewhere new drops the forward stack.
+    pub fn visit(&mut self, leaving: HistoryEntry) {
+        if self.back.last() != Some(&leaving) {
+            self.back.push(leaving);
+        }
+        if self.back.len() > MAX_TAB_HISTORY_SIZE {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    pub fn go_back(&mut self, current: HistoryEntry) -> Option<HistoryEntry> {
+        let prev = self.back.pop()?;
+        self.forward.push(current);
+        Some(prev)
+    }
+
+    pub fn go_forward(&mut self, current: HistoryEntry) -> Option<HistoryEntry> {
+        let next = self.forward.pop()?;
+        self.back.push(current);
+        Some(next)
+    }
+
+    // Oldest first
+    pub fn back_entries(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> {
+        self.back.iter()
+    }
+
+    // The forward stack is stored reversed, so it is flipped here.
+    pub fn forward_entries(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> {
+        self.forward.iter().rev()
+    }
+}
+
+// Directories visited in any tab, newest first. Saved between sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentDirs {
+    dirs: Vec<PathBuf>,
+}
+
+impl RecentDirs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, path: &Path) {
+        self.dirs.retain(|p| p != path);
+        self.dirs.insert(0, path.to_path_buf());
+        self.dirs.truncate(MAX_RECENT_DIRS_SIZE);
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    // A missing file means there is no history yet, a broken one is moved aside.
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        load_ron_file(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{DirHistory, HistoryEntry, RecentDirs};
+
+    fn entry(path: &str) -> HistoryEntry {
+        HistoryEntry::new(PathBuf::from(path), None)
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut hist = DirHistory::new();
+        hist.visit(entry("/a"));
+        hist.visit(entry("/b"));
+        assert_eq!(hist.go_back(entry("/c")), Some(entry("/b")));
+        assert_eq!(hist.go_back(entry("/b")), Some(entry("/a")));
+        assert_eq!(hist.go_back(entry("/a")), None);
+        assert_eq!(hist.go_forward(entry("/a")), Some(entry("/b")));
+        assert_eq!(hist.go_forward(entry("/b")), Some(entry("/c")));
+        assert_eq!(hist.forward_entries().count(), 0);
+
+        hist.go_back(entry("/c"));
+        hist.visit(entry("/b"));
+        assert_eq!(hist.forward_entries().count(), 0);
+    }
+
+    #[test]
+    fn recent_dirs_are_unique_and_newest_first() {
+        let mut recent = RecentDirs::new();
+        recent.push(&PathBuf::from("/a"));
+        recent.push(&PathBuf::from("/b"));
+        recent.push(&PathBuf::from("/a"));
+        assert_eq!(recent.dirs(), &[PathBuf::from("/a"), PathBuf::from("/b")]);
+    }
+}
@@ -0,0 +1,138 @@
+// Which items are left out of a listing while hidden items are not shown:
+// dotfiles, the globs from the config and, when turned on, whatever the
+// .gitignore and .ignore files of the directory and its parents ignore.
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use crate::file_item_list::file_item::FileItem;
+use crate::file_item_list::Kinds;
+
+// A file in a directory overrides the files of its parents, and .ignore
+// overrides .gitignore
+const IGNORE_FILE_NAMES: [&str; 2] = [".ignore", ".gitignore"];
+
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    globs: Gitignore,
+    gitignore: bool,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self {
+            globs: Gitignore::empty(),
+            gitignore: false,
+        }
+    }
+}
+
+impl IgnoreRules {
+    // Globs are written like lines of a .gitignore, `target/` only matches
+    // directories. The ones that can't be parsed are returned.
+    pub fn new(globs: &[String], gitignore: bool) -> (Self, Vec<String>) {
+        let mut builder = GitignoreBuilder::new("/");
+        let mut errors = Vec::new();
+        for glob in globs {
+            if let Err(e) = builder.add_line(None, glob) {
+                errors.push(format!("ignore glob \"{}\": {}", glob, e));
+            }
+        }
+        let globs = builder.build().unwrap_or_else(|e| {
+            errors.push(e.to_string());
+            Gitignore::empty()
+        });
+        (Self { globs, gitignore }, errors)
+    }
+
+    // Everything in `items` (all in `dir`) that is only shown with hidden items
+    pub fn hideable(&self, dir: &Path, items: &[FileItem]) -> Vec<PathBuf> {
+        let ignore_files = match self.gitignore {
+            true => ignore_files(dir),
+            false => Vec::new(),
+        };
+        items
+            .iter()
+            .filter(|item| {
+                let path = item.path();
+                let is_dir = item.kinds().is_dir();
+                Kinds::is_hidden(path)
+                    || self.globs.matched(path, is_dir).is_ignore()
+                    || is_ignored(&ignore_files, path, is_dir)
+            })
+            .map(|item| item.path().to_path_buf())
+            .collect()
+    }
+}
+
+// The ignore files of `dir` and its parents up to the root of the repository,
+// closest first
+fn ignore_files(dir: &Path) -> Vec<Gitignore> {
+    let mut files = Vec::new();
+    for ancestor in dir.ancestors() {
+        for name in IGNORE_FILE_NAMES {
+            let path = ancestor.join(name);
+            if path.is_file() {
+                // a line that can't be parsed is skipped, the rest still apply
+                let (gitignore, _) = Gitignore::new(&path);
+                files.push(gitignore);
+            }
+        }
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    files
+}
+
+fn is_ignored(files: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    for file in files {
+        match file.matched_path_or_any_parents(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::IgnoreRules;
+    use crate::path_process::make_info_files_from_dirpath;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn dotfiles_globs_and_ignore_files_are_hideable() {
+        let dir = TempDir::new("ignore");
+        let repo = dir.join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("target")).unwrap();
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join(".gitignore"), "*.log\n!keep.log\n").unwrap();
+        for name in ["a.log", "keep.log", "main.o", "README"] {
+            fs::write(repo.join(name), "").unwrap();
+        }
+
+        let (items, _) = make_info_files_from_dirpath(&repo);
+        let hideable = |rules: &IgnoreRules| {
+            let mut names: Vec<String> = rules
+                .hideable(&repo, &items)
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let (rules, errors) = IgnoreRules::new(&["target/".to_string(), "*.o".to_string()], false);
+        assert!(errors.is_empty());
+        assert_eq!(hideable(&rules), [".git", ".gitignore", "main.o", "target"]);
+
+        let (rules, _) = IgnoreRules::new(&[], true);
+        assert_eq!(hideable(&rules), [".git", ".gitignore", "a.log"]);
+    }
+}
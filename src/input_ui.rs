@@ -1,59 +1,57 @@
-use std::io::{self, Stdout};
+use std::io;
 
-use crossterm::{
-    event::{read, Event, KeyCode, KeyEvent},
-    execute,
-};
+use crossterm::event::{read, Event, KeyCode, KeyEvent};
 use tui::{
-    backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    widgets::{Block, Borders, Paragraph},
+    backend::Backend,
+    layout::Rect,
+    style::Style,
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame, Terminal,
 };
 
-pub fn init_input_area_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
-    let mut stdout = io::stdout();
-    execute!(stdout)?;
-    let backend = CrosstermBackend::new(stdout);
-    let terminal = Terminal::new(backend)?;
-    Ok(terminal)
-}
+use crate::application::App;
+use crate::picker::centered_rect;
+use crate::ui::ui;
 
-pub fn start_user_input<B: Backend>(
+// Ask for a line of text in a popup above the main window. Returns false if cancelled.
+pub fn run_input_prompt<B: Backend>(
     terminal: &mut Terminal<B>,
+    app: &mut App,
+    title: &str,
     line: &mut String,
-) -> io::Result<()> {
-    terminal.draw(|f| input_area_ui(f, line))?;
-    while let Event::Key(KeyEvent { code, .. }) = read().expect("Failed to get user input") {
-        match code {
-            KeyCode::Enter => break,
-            KeyCode::Char(c) => line.push(c),
-            KeyCode::Backspace => {
-                line.pop();
-            }
-            KeyCode::Esc => {
-                line.clear();
-                break;
+) -> io::Result<bool> {
+    loop {
+        let boader_style = app.theme().popup_style();
+        terminal.draw(|f| {
+            ui(f, app);
+            input_prompt_ui(f, title, line, boader_style);
+        })?;
+
+        if let Event::Key(KeyEvent { code, .. }) = read()? {
+            match code {
+                KeyCode::Enter => return Ok(true),
+                KeyCode::Esc => return Ok(false),
+                KeyCode::Char(c) => line.push(c),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                _ => {}
             }
-            _ => {}
         }
-        terminal.draw(|f| input_area_ui(f, line))?;
     }
-    Ok(())
 }
 
-pub fn input_area_ui<B: Backend>(f: &mut Frame<B>, line: &str) {
-    let input_area = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Percentage(90)])
-        .split(f.size())[0];
-
-    let input_style = Style::default().bg(Color::LightYellow).bg(Color::White);
+fn input_prompt_ui<B: Backend>(f: &mut Frame<B>, title: &str, line: &str, style: Style) {
+    let area = centered_rect(60, 20, f.size());
+    let area = Rect {
+        height: area.height.min(3),
+        ..area
+    };
+    f.render_widget(Clear, area);
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(input_style);
-
-    let para = Paragraph::new(line).block(block.clone());
-    f.render_widget(para, input_area);
+        .style(style)
+        .title(title);
+    let para = Paragraph::new(line).block(block);
+    f.render_widget(para, area);
 }
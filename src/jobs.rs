@@ -0,0 +1,144 @@
+// Work that runs on its own thread, like packing or unpacking an archive, so
+// the listing keeps responding. The event loop picks up progress and results.
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Progress is sent at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+enum JobEvent {
+    // (done, total), in whatever unit the job counts
+    Progress(u64, u64),
+    // the message shown when the job ends
+    Done(Result<String, String>),
+}
+
+pub struct Progress {
+    tx: Sender<JobEvent>,
+    last: Option<Instant>,
+}
+
+impl Progress {
+    pub fn report(&mut self, done: u64, total: u64) {
+        if self.last.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        let _ = self.tx.send(JobEvent::Progress(done, total));
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    title: String,
+    // the directory to read again when it is done
+    refresh: PathBuf,
+    rx: Receiver<JobEvent>,
+    progress: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Jobs {
+    running: Vec<Job>,
+}
+
+impl Jobs {
+    pub fn spawn<F>(&mut self, title: String, refresh: PathBuf, work: F)
+    where
+        F: FnOnce(&mut Progress) -> Result<String, String> + Send + 'static,
+    {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let mut progress = Progress {
+                tx: tx.clone(),
+                last: None,
+            };
+            let _ = tx.send(JobEvent::Done(work(&mut progress)));
+        });
+        self.running.push(Job {
+            title,
+            refresh,
+            rx,
+            progress: None,
+        });
+    }
+
+    // The jobs that ended since the last poll, with the directory each one
+    // changed
+    pub fn poll(&mut self) -> Vec<(String, PathBuf)> {
+        let mut finished = Vec::new();
+        self.running.retain_mut(|job| loop {
+            match job.rx.try_recv() {
+                Ok(JobEvent::Progress(done, total)) => job.progress = Some((done, total)),
+                Ok(JobEvent::Done(res)) => {
+                    let msg = res.unwrap_or_else(|e| format!("{} failed: {}", job.title, e));
+                    finished.push((msg, job.refresh.clone()));
+                    return false;
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => {
+                    finished.push((format!("{} stopped", job.title), job.refresh.clone()));
+                    return false;
+                }
+            }
+        });
+        finished
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    // "Packing a.zip 40%", followed by how many more are running
+    pub fn summary(&self) -> Option<String> {
+        let job = self.running.first()?;
+        let mut s = job.title.clone();
+        if let Some((done, total)) = job.progress.filter(|(_, total)| *total > 0) {
+            s.push_str(&format!(" {}%", done.min(total) * 100 / total));
+        }
+        if self.running.len() > 1 {
+            s.push_str(&format!(" (+{} more)", self.running.len() - 1));
+        }
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use super::Jobs;
+
+    #[test]
+    fn finished_jobs_are_picked_up() {
+        let mut jobs = Jobs::default();
+        jobs.spawn("Counting".to_string(), PathBuf::from("/tmp"), |progress| {
+            progress.report(1, 2);
+            Ok("Counted".to_string())
+        });
+        jobs.spawn("Failing".to_string(), PathBuf::from("/"), |_| {
+            Err("no".to_string())
+        });
+        assert!(jobs.summary().unwrap().starts_with("Counting"));
+
+        let mut finished = Vec::new();
+        let start = Instant::now();
+        while !jobs.is_empty() && start.elapsed() < Duration::from_secs(5) {
+            finished.extend(jobs.poll());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        finished.sort();
+        assert_eq!(
+            finished,
+            [
+                ("Counted".to_string(), PathBuf::from("/tmp")),
+                ("Failing failed: no".to_string(), PathBuf::from("/")),
+            ]
+        );
+        assert_eq!(jobs.summary(), None);
+    }
+}
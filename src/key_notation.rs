@@ -0,0 +1,290 @@
+// Key notation used in config.ron
+//
+//   a  A  ?  -            a character, upper case letters imply shift
+//   C-a  A-x  S-tab       ctrl, alt (also M-) and shift, combined as C-A-x
+//   <C-a>  <lt>           optional angle brackets, vim style
+//   tab  Enter  F5        named keys, case doesn't matter
+//   g g                   key sequences are separated by spaces
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyParseError {
+    Empty,
+    MissingKey(String),
+    UnknownKey(String),
+    DuplicateModifier(String),
+    UnclosedBracket(String),
+}
+
+impl fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyParseError::Empty => write!(f, "empty key"),
+            KeyParseError::MissingKey(s) => write!(f, "\"{}\" has modifiers but no key", s),
+            KeyParseError::UnknownKey(s) => write!(f, "unknown key name \"{}\"", s),
+            KeyParseError::DuplicateModifier(s) => write!(f, "\"{}\" repeats a modifier", s),
+            KeyParseError::UnclosedBracket(s) => write!(f, "\"{}\" is missing a closing '>'", s),
+        }
+    }
+}
+
+impl std::error::Error for KeyParseError {}
+
+const NAMED_KEYS: [(&str, KeyCode); 23] = [
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("enter", KeyCode::Enter),
+    ("return", KeyCode::Enter),
+    ("cr", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("escape", KeyCode::Esc),
+    ("space", KeyCode::Char(' ')),
+    ("backspace", KeyCode::Backspace),
+    ("bs", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("del", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("lt", KeyCode::Char('<')),
+    ("gt", KeyCode::Char('>')),
+];
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    let lower = name.to_ascii_lowercase();
+    if let Some((_, code)) = NAMED_KEYS.iter().find(|(n, _)| *n == lower) {
+        return Some(*code);
+    }
+    match lower.strip_prefix('f').map(str::parse::<u8>) {
+        Some(Ok(n)) if (1..=24).contains(&n) => Some(KeyCode::F(n)),
+        _ => None,
+    }
+}
+
+fn modifier(c: char) -> Option<KeyModifiers> {
+    match c.to_ascii_lowercase() {
+        'c' => Some(KeyModifiers::CONTROL),
+        'a' | 'm' => Some(KeyModifiers::ALT),
+        's' => Some(KeyModifiers::SHIFT),
+        _ => None,
+    }
+}
+
+// Terminals do not agree on how shift is reported, so both the parsed keys
+// and the keys read from the terminal are brought into the same form.
+pub fn normalize_key_event(key: KeyEvent) -> KeyEvent {
+    let KeyEvent {
+        mut code,
+        mut modifiers,
+    } = key;
+    match code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                code = KeyCode::Char(c.to_ascii_uppercase());
+            } else if c.is_ascii_uppercase() {
+                modifiers |= KeyModifiers::SHIFT;
+            }
+        }
+        // `?` or `:` need shift on most layouts, which says nothing about the key
+        KeyCode::Char(_) => modifiers.remove(KeyModifiers::SHIFT),
+        KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => code = KeyCode::BackTab,
+        KeyCode::BackTab => modifiers |= KeyModifiers::SHIFT,
+        _ => {}
+    }
+    KeyEvent { code, modifiers }
+}
+
+pub fn parse_key(notation: &str) -> Result<KeyEvent, KeyParseError> {
+    let inner = match notation.strip_prefix('<') {
+        Some(rest) if notation.len() > 1 => rest
+            .strip_suffix('>')
+            .ok_or_else(|| KeyParseError::UnclosedBracket(notation.to_string()))?,
+        _ => notation,
+    };
+    if inner.is_empty() {
+        return Err(KeyParseError::Empty);
+    }
+
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = inner;
+    loop {
+        let mut chars = rest.chars();
+        let (m, dash) = (chars.next(), chars.next());
+        match (m.and_then(modifier), dash) {
+            (Some(m), Some('-')) => {
+                let key = &rest[2..];
+                if key.is_empty() {
+                    return Err(KeyParseError::MissingKey(notation.to_string()));
+                }
+                if modifiers.contains(m) {
+                    return Err(KeyParseError::DuplicateModifier(notation.to_string()));
+                }
+                modifiers |= m;
+                rest = key;
+            }
+            _ => break,
+        }
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => named_key(rest).ok_or_else(|| KeyParseError::UnknownKey(rest.to_string()))?,
+    };
+    Ok(normalize_key_event(KeyEvent { code, modifiers }))
+}
+
+// "g g" -> [g, g]
+pub fn parse_key_sequence(notation: &str) -> Result<Vec<KeyEvent>, KeyParseError> {
+    let keys: Vec<KeyEvent> = notation
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<_, _>>()?;
+    if keys.is_empty() {
+        return Err(KeyParseError::Empty);
+    }
+    Ok(keys)
+}
+
+// The inverse of parse_key, for showing keys to the user
+pub fn key_to_notation(key: &KeyEvent) -> String {
+    let key = normalize_key_event(*key);
+    let mut s = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        s.push_str("C-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        s.push_str("A-");
+    }
+    let name = match key.code {
+        // shift is already visible in upper case letters and implied by backtab
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::BackTab => "BackTab".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Null => "Null".to_string(),
+        code => {
+            if key.modifiers.contains(KeyModifiers::SHIFT) {
+                s.push_str("S-");
+            }
+            let name = match code {
+                KeyCode::Tab => "Tab",
+                KeyCode::Enter => "Enter",
+                KeyCode::Esc => "Esc",
+                KeyCode::Backspace => "Backspace",
+                KeyCode::Delete => "Delete",
+                KeyCode::Insert => "Insert",
+                KeyCode::Home => "Home",
+                KeyCode::End => "End",
+                KeyCode::PageUp => "PageUp",
+                KeyCode::PageDown => "PageDown",
+                KeyCode::Up => "Up",
+                KeyCode::Down => "Down",
+                KeyCode::Left => "Left",
+                KeyCode::Right => "Right",
+                _ => "Null",
+            };
+            name.to_string()
+        }
+    };
+    s.push_str(&name);
+    s
+}
+
+pub fn key_sequence_to_notation(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .map(key_to_notation)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{key_to_notation, parse_key, parse_key_sequence, KeyParseError};
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    #[test]
+    fn parse_modifiers_and_names() {
+        let shift_i = key(KeyCode::Char('I'), KeyModifiers::SHIFT);
+        assert_eq!(parse_key("S-i"), Ok(shift_i));
+        assert_eq!(parse_key("s-i"), Ok(shift_i));
+        assert_eq!(parse_key("I"), Ok(shift_i));
+        assert_eq!(
+            parse_key("<C-a>"),
+            Ok(key(KeyCode::Char('a'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("C-A-x"),
+            Ok(key(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            parse_key("C--"),
+            Ok(key(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("-"),
+            Ok(key(KeyCode::Char('-'), KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key("TAB"), Ok(key(KeyCode::Tab, KeyModifiers::NONE)));
+        assert_eq!(parse_key("Backtab"), parse_key("S-tab"));
+        assert_eq!(parse_key("escape"), parse_key("Esc"));
+        assert_eq!(
+            parse_key("f12"),
+            Ok(key(KeyCode::F(12), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("<"),
+            Ok(key(KeyCode::Char('<'), KeyModifiers::NONE))
+        );
+        assert_eq!(parse_key("<lt>"), parse_key("<"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_key(""), Err(KeyParseError::Empty));
+        assert_eq!(
+            parse_key("C-"),
+            Err(KeyParseError::MissingKey("C-".to_string()))
+        );
+        assert_eq!(
+            parse_key("C-C-a"),
+            Err(KeyParseError::DuplicateModifier("C-C-a".to_string()))
+        );
+        assert_eq!(
+            parse_key("escpe"),
+            Err(KeyParseError::UnknownKey("escpe".to_string()))
+        );
+        assert_eq!(
+            parse_key("<C-a"),
+            Err(KeyParseError::UnclosedBracket("<C-a".to_string()))
+        );
+        assert!(parse_key_sequence("g escpe").is_err());
+    }
+
+    #[test]
+    fn notation_round_trip() {
+        for s in [
+            "a", "G", "C-a", "A-x", "C-A-x", "Tab", "BackTab", "S-Up", "F5", "Space", "?",
+        ] {
+            let parsed = parse_key(s).unwrap();
+            assert_eq!(key_to_notation(&parsed), s);
+        }
+        assert_eq!(parse_key_sequence("g g").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,338 @@
+// Keybindings stored as a prefix tree, one level per key of a sequence.
+// A node either runs a command or waits for more keys, never both, so there
+// is no need for a timeout to tell "g" from "g g".
+use std::collections::HashMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::key_notation::{
+    key_sequence_to_notation, normalize_key_event, parse_key_sequence, KeyParseError,
+};
+
+// Counts above this are clamped, `99999j` is not worth an overflow
+const MAX_COUNT: usize = 99_999;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeybindError {
+    Parse(String, KeyParseError),
+    // two notations for the same keys with different commands, e.g. "I" and "S-i"
+    Duplicate(String, String),
+    // the first binding can never run because the second one starts with it
+    Shadowed(String, String),
+}
+
+impl fmt::Display for KeybindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeybindError::Parse(key, e) => write!(f, "\"{}\": {}", key, e),
+            KeybindError::Duplicate(a, b) => {
+                write!(f, "\"{}\" and \"{}\" are the same keys", a, b)
+            }
+            KeybindError::Shadowed(short, long) => {
+                write!(f, "\"{}\" is shadowed by \"{}\"", short, long)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeybindError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTrie {
+    // the notation as written in the config, for error messages
+    notation: Option<String>,
+    command: Option<String>,
+    children: HashMap<KeyEvent, KeyTrie>,
+}
+
+impl KeyTrie {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_keybinds(keybinds: &HashMap<String, String>) -> Result<Self, KeybindError> {
+        let (trie, mut errors) = Self::build(keybinds);
+        match errors.is_empty() {
+            true => Ok(trie),
+            false => Err(errors.remove(0)),
+        }
+    }
+
+    // Skips the bindings that fail and keeps going, so every problem can be listed.
+    // Keys are inserted in sorted order so the reported conflict does not depend
+    // on the order of the HashMap.
+    pub fn build(keybinds: &HashMap<String, String>) -> (Self, Vec<KeybindError>) {
+        let mut sorted: Vec<(&String, &String)> = keybinds.iter().collect();
+        sorted.sort();
+        let mut trie = Self::new();
+        let mut errors = Vec::new();
+        for (notation, cmd) in sorted {
+            let res = parse_key_sequence(notation)
+                .map_err(|e| KeybindError::Parse(notation.to_owned(), e))
+                .and_then(|keys| trie.insert(notation, &keys, cmd));
+            if let Err(e) = res {
+                errors.push(e);
+            }
+        }
+        (trie, errors)
+    }
+
+    pub fn insert(
+        &mut self,
+        notation: &str,
+        keys: &[KeyEvent],
+        cmd: &str,
+    ) -> Result<(), KeybindError> {
+        let mut node = self;
+        for key in keys {
+            if let Some(prefix) = node.notation.as_ref() {
+                return Err(KeybindError::Shadowed(prefix.clone(), notation.to_string()));
+            }
+            node = node.children.entry(*key).or_default();
+        }
+        if let Some(existing) = node.notation.as_ref() {
+            if node.command.as_deref() != Some(cmd) {
+                return Err(KeybindError::Duplicate(
+                    existing.clone(),
+                    notation.to_string(),
+                ));
+            }
+        }
+        if let Some(longer) = node.first_notation() {
+            return Err(KeybindError::Shadowed(notation.to_string(), longer));
+        }
+        node.notation = Some(notation.to_string());
+        node.command = Some(cmd.to_string());
+        Ok(())
+    }
+
+    fn first_notation(&self) -> Option<String> {
+        let mut children: Vec<&KeyTrie> = self.children.values().collect();
+        children.sort_by_key(|c| c.notation.clone());
+        children
+            .into_iter()
+            .find_map(|c| c.notation.clone().or_else(|| c.first_notation()))
+    }
+
+    pub fn get(&self, keys: &[KeyEvent]) -> Option<&KeyTrie> {
+        keys.iter()
+            .try_fold(self, |node, key| node.children.get(key))
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    // Every binding below this node as (keys after this node, command)
+    pub fn bindings(&self) -> Vec<(Vec<KeyEvent>, String)> {
+        let mut bindings = Vec::new();
+        for (key, child) in self.children.iter() {
+            if let Some(cmd) = child.command.as_ref() {
+                bindings.push((vec![*key], cmd.clone()));
+            }
+            for (mut keys, cmd) in child.bindings() {
+                keys.insert(0, *key);
+                bindings.push((keys, cmd));
+            }
+        }
+        bindings.sort_by_key(|(keys, _)| key_sequence_to_notation(keys));
+        bindings
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyInput {
+    // the command and the count typed before it, if any
+    Command(String, Option<usize>),
+    // waiting for more keys
+    Pending,
+    // the keys typed so far do not lead anywhere
+    Unbound(Vec<KeyEvent>),
+    // Esc while keys were pending
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserKeybinds {
+    trie: KeyTrie,
+    pending: Vec<KeyEvent>,
+    count: Option<usize>,
+}
+
+impl UserKeybinds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_trie(trie: KeyTrie) -> Self {
+        Self {
+            trie,
+            ..Self::default()
+        }
+    }
+
+    pub fn trie(&self) -> &KeyTrie {
+        &self.trie
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty() || self.count.is_some()
+    }
+
+    pub fn pending_keys(&self) -> &[KeyEvent] {
+        &self.pending
+    }
+
+    // The count and keys typed so far, e.g. "3 d"
+    pub fn pending_notation(&self) -> Option<String> {
+        if !self.is_pending() {
+            return None;
+        }
+        let keys = key_sequence_to_notation(&self.pending);
+        Some(match self.count {
+            Some(n) if keys.is_empty() => n.to_string(),
+            Some(n) => format!("{} {}", n, keys),
+            None => keys,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+    }
+
+    // A digit starts a count unless it is bound itself; once a count has
+    // started, `0` continues it.
+    fn count_digit(&self, key: &KeyEvent) -> Option<usize> {
+        if !self.pending.is_empty() || key.modifiers != KeyModifiers::NONE {
+            return None;
+        }
+        let digit = match key.code {
+            KeyCode::Char(c) => c.to_digit(10)? as usize,
+            _ => return None,
+        };
+        if self.count.is_none() && (digit == 0 || self.trie.children.contains_key(key)) {
+            return None;
+        }
+        Some(digit)
+    }
+
+    pub fn feed(&mut self, key: KeyEvent) -> KeyInput {
+        let key = normalize_key_event(key);
+
+        if let Some(digit) = self.count_digit(&key) {
+            let count = self.count.unwrap_or(0).saturating_mul(10) + digit;
+            self.count = Some(count.min(MAX_COUNT));
+            return KeyInput::Pending;
+        }
+
+        self.pending.push(key);
+        let node = match self.trie.get(&self.pending) {
+            Some(node) => node,
+            None => {
+                let keys = std::mem::take(&mut self.pending);
+                let was_pending = keys.len() > 1 || self.count.is_some();
+                self.count = None;
+                if was_pending && key.code == KeyCode::Esc {
+                    return KeyInput::Cancelled;
+                }
+                return KeyInput::Unbound(keys);
+            }
+        };
+        match node.command() {
+            Some(cmd) => {
+                let input = KeyInput::Command(cmd.to_string(), self.count);
+                self.reset();
+                input
+            }
+            None => KeyInput::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{KeyInput, KeyTrie, KeybindError, UserKeybinds};
+    use crate::key_notation::{parse_key, parse_key_sequence};
+
+    fn keybinds(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, c)| (k.to_string(), c.to_string()))
+            .collect()
+    }
+
+    fn feed(binds: &mut UserKeybinds, notation: &str) -> Vec<KeyInput> {
+        parse_key_sequence(notation)
+            .unwrap()
+            .into_iter()
+            .map(|key| binds.feed(key))
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_are_reported() {
+        let trie =
+            KeyTrie::from_keybinds(&keybinds(&[("g g", "top"), ("g t", "tab"), ("j", "down")]));
+        assert!(trie.is_ok());
+        assert_eq!(trie.unwrap().bindings().len(), 3);
+
+        let shadowed = KeyTrie::from_keybinds(&keybinds(&[("g", "go"), ("g g", "top")]));
+        assert_eq!(
+            shadowed,
+            Err(KeybindError::Shadowed("g".to_string(), "g g".to_string()))
+        );
+
+        let duplicate = KeyTrie::from_keybinds(&keybinds(&[("I", "a"), ("S-i", "b")]));
+        assert_eq!(
+            duplicate,
+            Err(KeybindError::Duplicate("I".to_string(), "S-i".to_string()))
+        );
+        assert!(KeyTrie::from_keybinds(&keybinds(&[("I", "a"), ("S-i", "a")])).is_ok());
+    }
+
+    #[test]
+    fn sequences_and_counts() {
+        let trie = KeyTrie::from_keybinds(&keybinds(&[
+            ("d d", "delete"),
+            ("g g g", "deep"),
+            ("j", "down"),
+            ("1", "first_tab"),
+        ]))
+        .unwrap();
+        let mut binds = UserKeybinds::from_trie(trie);
+
+        assert_eq!(
+            feed(&mut binds, "g g g").last(),
+            Some(&KeyInput::Command("deep".to_string(), None))
+        );
+
+        assert_eq!(feed(&mut binds, "3 d"), vec![KeyInput::Pending; 2]);
+        assert_eq!(binds.pending_notation(), Some("3 d".to_string()));
+        assert_eq!(
+            binds.feed(parse_key("d").unwrap()),
+            KeyInput::Command("delete".to_string(), Some(3))
+        );
+        assert_eq!(binds.pending_notation(), None);
+
+        // "1" is bound, but continues a count that has started
+        assert_eq!(
+            feed(&mut binds, "2 1 0 j").last(),
+            Some(&KeyInput::Command("down".to_string(), Some(210)))
+        );
+        assert_eq!(
+            binds.feed(parse_key("1").unwrap()),
+            KeyInput::Command("first_tab".to_string(), None)
+        );
+
+        assert_eq!(feed(&mut binds, "g Esc").last(), Some(&KeyInput::Cancelled));
+        assert!(matches!(
+            binds.feed(parse_key("x").unwrap()),
+            KeyInput::Unbound(_)
+        ));
+        assert!(!binds.is_pending());
+    }
+}
@@ -0,0 +1,133 @@
+// Listings of remote directories, read on a thread so that a slow or stalled
+// host doesn't hold up the keys. The directory is shown empty until its items
+// come back.
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::file_item_list::file_item::FileItem;
+use crate::vfs::Vfs;
+
+struct Request {
+    path: PathBuf,
+    vfs: Arc<dyn Vfs>,
+}
+
+impl std::fmt::Debug for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Request").field("path", &self.path).finish()
+    }
+}
+
+// (items, what couldn't be read) of a directory
+pub type Listed = (PathBuf, Vec<FileItem>, Vec<String>);
+
+#[derive(Debug)]
+pub struct Listings {
+    pending: HashSet<PathBuf>,
+    // started with the first request, one directory is read at a time
+    worker: Option<Sender<Request>>,
+    tx: Sender<Listed>,
+    rx: Receiver<Listed>,
+}
+
+impl Default for Listings {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            pending: HashSet::new(),
+            worker: None,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Listings {
+    pub fn is_pending(&self, path: &Path) -> bool {
+        self.pending.contains(path)
+    }
+
+    // One of the directories being read, to show while waiting
+    pub fn reading(&self) -> Option<&Path> {
+        self.pending.iter().next().map(PathBuf::as_path)
+    }
+
+    // Starts reading a directory unless it is being read already
+    pub fn request(&mut self, path: PathBuf, vfs: Arc<dyn Vfs>) {
+        if self.is_pending(&path) {
+            return;
+        }
+        let worker = self
+            .worker
+            .get_or_insert_with(|| spawn_worker(self.tx.clone()));
+        self.pending.insert(path.clone());
+        if let Err(e) = worker.send(Request { path, vfs }) {
+            // the worker is gone, the next request starts another one
+            self.pending.remove(&e.0.path);
+            self.worker = None;
+        }
+    }
+
+    // The directories that were read since the last poll
+    pub fn poll(&mut self) -> Vec<Listed> {
+        let read: Vec<Listed> = self.rx.try_iter().collect();
+        for (path, ..) in &read {
+            self.pending.remove(path);
+        }
+        read
+    }
+}
+
+fn spawn_worker(tx: Sender<Listed>) -> Sender<Request> {
+    let (request_tx, request_rx) = channel::<Request>();
+    thread::spawn(move || {
+        for request in request_rx {
+            let (items, errors) = request.vfs.read_dir(&request.path);
+            if tx.send((request.path, items, errors)).is_err() {
+                return;
+            }
+        }
+    });
+    request_tx
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vfs::MemFs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn directories_are_read_on_the_worker() {
+        let fs = MemFs::new(Path::new("/r"));
+        fs.write(Path::new("/r/a"), b"a").unwrap();
+        fs.write(Path::new("/r/b"), b"b").unwrap();
+        let vfs: Arc<dyn Vfs> = Arc::new(fs);
+
+        let mut listings = Listings::default();
+        listings.request(PathBuf::from("/r"), vfs.clone());
+        listings.request(PathBuf::from("/r"), vfs.clone());
+        listings.request(PathBuf::from("/gone"), vfs);
+        assert!(listings.is_pending(Path::new("/r")));
+
+        let mut read = Vec::new();
+        let start = Instant::now();
+        while read.len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            read.extend(listings.poll());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(listings.reading().is_none());
+        assert_eq!(read.len(), 2);
+        let (path, items, errors) = &read[0];
+        assert_eq!(path, Path::new("/r"));
+        assert_eq!(items.len(), 2);
+        assert!(errors.is_empty());
+        let (path, items, errors) = &read[1];
+        assert_eq!(path, Path::new("/gone"));
+        assert!(items.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -1,29 +1,16 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use serde::Deserialize;
-use tui::style::{Color, Style};
 
-#[derive(Debug, Clone, Deserialize)]
-enum Colors {
-    White,
-    Black,
-    Yellow,
-    Blue,
-    Green,
-    Red,
-    Gray,
-    DarkGray,
-    Cyan,
-    Magenta,
-    LightBlue,
-    LightRed,
-    LightGreen,
-    LightMagenta,
-    LightYellow,
-    LightCyan,
-    Rgb(u8, u8, u8),
-}
+use crate::columns::{default_columns, Column};
+use crate::command::command_description;
+use crate::ignore_rules::IgnoreRules;
+use crate::key_notation::parse_key_sequence;
+use crate::key_trie::{KeyTrie, KeybindError};
+use crate::path_process::{get_config_directory_path, get_system_config_directory_paths};
+use crate::theme::{ColorSupport, PartialSettingTheme, SettingTheme, THEMES_DIR_NAME};
 
 pub fn default_vim_movements() -> ModeKeybinds {
     let mut normal: HashMap<String, String> = HashMap::new();
@@ -38,6 +25,37 @@ pub fn default_vim_movements() -> ModeKeybinds {
         ("right", "move_to_child_dir"),
         ("Tab", "next_dirtab"),
         ("Backtab", "prev_dirtab"),
+        ("C-o", "move_back_in_history"),
+        ("S-l", "move_forward_in_history"),
+        ("C-r", "open_history_picker"),
+        ("m", "set_bookmark"),
+        ("'", "jump_to_bookmark"),
+        ("S-b", "open_bookmark_manager"),
+        ("S-z", "open_frecency_picker"),
+        (":", "command_line"),
+        ("S-m", "toggle_mark"),
+        ("S-r", "reload_config"),
+        ("g g", "move_to_top_of_file_item"),
+        ("S-g", "move_to_bottom_of_file_item"),
+        ("S-c", "toggle_column"),
+        (".", "toggle_hidden"),
+        ("g l", "follow_symlink"),
+        ("g s", "make_symlink"),
+        ("g h", "make_hardlink"),
+        ("g e", "edit_symlink"),
+        ("g x", "extract_entries"),
+        ("g S-x", "extract_archive"),
+        ("g z", "compress"),
+        ("g a", "git_stage"),
+        ("g u", "git_unstage"),
+        ("g c", "next_changed_file"),
+        ("y", "copy"),
+        ("r", "rename"),
+        ("S-d", "delete"),
+        ("p", "toggle_preview"),
+        ("g d", "calc_dir_size"),
+        ("S-u", "toggle_disk_usage"),
+        ("?", "help"),
         ("q", "quit"),
         ("i", "input"),
         ("v", "stacker"),
@@ -95,1131 +113,646 @@ pub fn default_vim_movements() -> ModeKeybinds {
     }
 }
 
-pub fn default_arrow_key() -> ModeKeybinds {
-    let mut normal: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("Left", "move_to_parent_dir"),
-        ("Down", "move_to_next_file_item"),
-        ("Up", "move_to_prev_file_item"),
-        ("Right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("q", "quit"),
-        ("i", "input"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        normal.insert(name.to_string(), cmd.to_string());
-    }
-
-    let mut input: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("Left", "move_to_parent_dir"),
-        ("Down", "move_to_next_file_item"),
-        ("Up", "move_to_prev_file_item"),
-        ("Right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("escape", "normal"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        input.insert(name.to_string(), cmd.to_string());
-    }
-
-    let mut stacker: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("Left", "move_to_parent_dir"),
-        ("Down", "move_to_next_file_item"),
-        ("Up", "move_to_prev_file_item"),
-        ("Right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("q", "quit"),
-        ("escape", "normal"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        stacker.insert(name.to_string(), cmd.to_string());
-    }
-
-    ModeKeybinds {
-        normal,
-        input,
-        stacker,
-    }
+// A binding from a later config layer replaces the bindings it would conflict
+// with: the same keys written another way ("I" over "S-i"), or a prefix or
+// extension of its keys ("g" over "g g").
+fn layer_keybinds(base: &mut HashMap<String, String>, layer: HashMap<String, String>) {
+    // conflicts inside the layer itself are left for check_keybinds to report
+    let layer_keys: Vec<_> = layer
+        .keys()
+        .filter_map(|notation| parse_key_sequence(notation).ok())
+        .collect();
+    base.retain(|other, _| match parse_key_sequence(other) {
+        Ok(other) => !layer_keys
+            .iter()
+            .any(|keys| other.starts_with(keys) || keys.starts_with(&other)),
+        Err(_) => true,
+    });
+    base.extend(layer);
 }
 
-pub fn default_vim_ctrl_movements() -> ModeKeybinds {
-    let mut normal: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("C-h", "move_to_parent_dir"),
-        ("C-j", "move_to_next_file_item"),
-        ("C-k", "move_to_prev_file_item"),
-        ("C-l", "move_to_child_dir"),
-        ("down", "move_to_next_file_item"),
-        ("up", "move_to_prev_file_item"),
-        ("left", "move_to_parent_dir"),
-        ("right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("q", "quit"),
-        ("i", "input"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        normal.insert(name.to_string(), cmd.to_string());
-    }
-
-    let mut input: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("C-h", "move_to_parent_dir"),
-        ("C-j", "move_to_next_file_item"),
-        ("C-k", "move_to_prev_file_item"),
-        ("C-l", "move_to_child_dir"),
-        ("down", "move_to_next_file_item"),
-        ("up", "move_to_prev_file_item"),
-        ("left", "move_to_parent_dir"),
-        ("right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("escape", "normal"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        input.insert(name.to_string(), cmd.to_string());
-    }
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Hash)]
+pub enum FileItems {
+    File,
+    Directory,
+    Select,
+}
 
-    let mut stacker: HashMap<String, String> = HashMap::new();
-    let iter = [
-        ("C-h", "move_to_parent_dir"),
-        ("C-j", "move_to_next_file_item"),
-        ("C-k", "move_to_prev_file_item"),
-        ("C-l", "move_to_child_dir"),
-        ("down", "move_to_next_file_item"),
-        ("up", "move_to_prev_file_item"),
-        ("left", "move_to_parent_dir"),
-        ("right", "move_to_child_dir"),
-        ("Tab", "next_dirtab"),
-        ("Backtab", "prev_dirtab"),
-        ("q", "quit"),
-        ("escape", "normal"),
-        ("v", "stacker"),
-    ]
-    .into_iter();
-    for (name, cmd) in iter {
-        stacker.insert(name.to_string(), cmd.to_string());
-    }
+fn simple_symbols() -> HashMap<FileItems, String> {
+    let map: HashMap<FileItems, String> = HashMap::from([
+        (FileItems::File, " ".to_string()),
+        (FileItems::Directory, "▸".to_string()),
+        (FileItems::Select, ">>".to_string()),
+    ]);
+    map
+}
 
-    ModeKeybinds {
-        normal,
-        input,
-        stacker,
-    }
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModeKeybinds {
+    pub normal: HashMap<String, String>,
+    pub input: HashMap<String, String>,
+    pub stacker: HashMap<String, String>,
 }
 
-pub fn string_map_to_user_keyboad(keybinds: &HashMap<String, String>) -> HashMap<KeyEvent, String> {
-    let mut keybind: HashMap<KeyEvent, String> = HashMap::new();
-    for (key, cmd) in keybinds.iter() {
-        let user_keyboad = string_to_keyevent(key);
-        keybind.insert(user_keyboad, cmd.to_string());
-    }
-    keybind
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserConfig {
+    theme: SettingTheme,
+    symbols: HashMap<FileItems, String>,
+    user_keybinds: ModeKeybinds,
+    // letter -> directory, overridden by the bookmarks file
+    #[serde(default)]
+    bookmarks: HashMap<char, PathBuf>,
+    // reload automatically when a config file is written
+    #[serde(default)]
+    watch_config: bool,
+    // list the continuations of an unfinished key sequence after a delay
+    #[serde(default = "default_which_key")]
+    which_key: bool,
+    #[serde(default = "default_which_key_delay_ms")]
+    which_key_delay_ms: u64,
+    // click, double click, wheel and drag
+    #[serde(default = "default_mouse")]
+    mouse: bool,
+    // a built-in theme or themes/<name>.ron, `theme` is layered over it
+    #[serde(default)]
+    theme_name: Option<String>,
+    #[serde(default)]
+    colors: ColorSupport,
+    // the columns of the directory table in order
+    #[serde(default = "default_columns")]
+    columns: Vec<Column>,
+    // strftime, in the local time zone
+    #[serde(default = "default_date_format")]
+    date_format: String,
+    // whether new tabs show dotfiles and ignored items
+    #[serde(default = "default_show_hidden")]
+    show_hidden: bool,
+    // gitignore style globs of items to hide, e.g. "target/"
+    #[serde(default)]
+    ignore_globs: Vec<String>,
+    // also hide what .gitignore and .ignore files ignore
+    #[serde(default)]
+    gitignore: bool,
+    // the branch of the current repository in the title
+    #[serde(default = "default_git_branch")]
+    git_branch: bool,
+    // the private key for sftp:// tabs when ssh-agent has none the host
+    // takes, ~/.ssh/id_ed25519, id_ecdsa and id_rsa are tried without it
+    #[serde(default)]
+    ssh_key: Option<PathBuf>,
+    // add up the directories of the listing in the background, otherwise only
+    // `calc_dir_size` and the disk usage view do
+    #[serde(default)]
+    dir_sizes: bool,
 }
 
-pub fn multi_string_map_to_user_keyboad(
-    keybinds: &HashMap<String, String>,
-) -> HashMap<Vec<KeyEvent>, String> {
-    let mut keybind: HashMap<Vec<KeyEvent>, String> = HashMap::new();
-    for (key, cmd) in keybinds.iter() {
-        if key.split_whitespace().count() > 1 {
-            let keys: Vec<KeyEvent> = key.split_whitespace().map(string_to_keyevent).collect();
-            keybind.insert(keys, cmd.to_owned());
-        } else {
-            let user_keyboad = string_to_keyevent(key);
-            keybind.insert(vec![user_keyboad], cmd.to_owned());
-        }
-    }
-    keybind
+fn default_date_format() -> String {
+    "%F %R".to_string()
 }
 
-fn string_to_keyevent(s: &str) -> KeyEvent {
-    match s {
-        "a" => KeyEvent {
-            code: KeyCode::Char('a'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "b" => KeyEvent {
-            code: KeyCode::Char('b'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "c" => KeyEvent {
-            code: KeyCode::Char('c'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "d" => KeyEvent {
-            code: KeyCode::Char('d'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "e" => KeyEvent {
-            code: KeyCode::Char('e'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "f" => KeyEvent {
-            code: KeyCode::Char('f'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "g" => KeyEvent {
-            code: KeyCode::Char('g'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "h" => KeyEvent {
-            code: KeyCode::Char('h'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "i" => KeyEvent {
-            code: KeyCode::Char('i'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "j" => KeyEvent {
-            code: KeyCode::Char('j'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "k" => KeyEvent {
-            code: KeyCode::Char('k'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "l" => KeyEvent {
-            code: KeyCode::Char('l'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "m" => KeyEvent {
-            code: KeyCode::Char('m'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "n" => KeyEvent {
-            code: KeyCode::Char('n'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "o" => KeyEvent {
-            code: KeyCode::Char('o'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "p" => KeyEvent {
-            code: KeyCode::Char('p'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "q" => KeyEvent {
-            code: KeyCode::Char('q'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "r" => KeyEvent {
-            code: KeyCode::Char('r'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "s" => KeyEvent {
-            code: KeyCode::Char('s'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "t" => KeyEvent {
-            code: KeyCode::Char('t'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "u" => KeyEvent {
-            code: KeyCode::Char('u'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "v" => KeyEvent {
-            code: KeyCode::Char('v'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "w" => KeyEvent {
-            code: KeyCode::Char('w'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "x" => KeyEvent {
-            code: KeyCode::Char('x'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "y" => KeyEvent {
-            code: KeyCode::Char('y'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "z" => KeyEvent {
-            code: KeyCode::Char('z'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "#" => KeyEvent {
-            code: KeyCode::Char('#'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "!" => KeyEvent {
-            code: KeyCode::Char('!'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "$" => KeyEvent {
-            code: KeyCode::Char('$'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "%" => KeyEvent {
-            code: KeyCode::Char('%'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "&" => KeyEvent {
-            code: KeyCode::Char('&'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "'" => KeyEvent {
-            code: KeyCode::Char('\''),
-            modifiers: KeyModifiers::NONE,
-        },
-        "(" => KeyEvent {
-            code: KeyCode::Char('('),
-            modifiers: KeyModifiers::NONE,
-        },
-        ")" => KeyEvent {
-            code: KeyCode::Char(')'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "-" => KeyEvent {
-            code: KeyCode::Char('-'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "=" => KeyEvent {
-            code: KeyCode::Char('='),
-            modifiers: KeyModifiers::NONE,
-        },
-        "^" => KeyEvent {
-            code: KeyCode::Char('^'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "~" => KeyEvent {
-            code: KeyCode::Char('~'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "\\" => KeyEvent {
-            code: KeyCode::Char('\\'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "|" => KeyEvent {
-            code: KeyCode::Char('|'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "@" => KeyEvent {
-            code: KeyCode::Char('@'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "[" => KeyEvent {
-            code: KeyCode::Char('['),
-            modifiers: KeyModifiers::NONE,
-        },
-        "]" => KeyEvent {
-            code: KeyCode::Char(']'),
-            modifiers: KeyModifiers::NONE,
-        },
-        ";" => KeyEvent {
-            code: KeyCode::Char(';'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "+" => KeyEvent {
-            code: KeyCode::Char('+'),
-            modifiers: KeyModifiers::NONE,
-        },
-        ":" => KeyEvent {
-            code: KeyCode::Char(':'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "*" => KeyEvent {
-            code: KeyCode::Char('*'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "?" => KeyEvent {
-            code: KeyCode::Char('?'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "/" => KeyEvent {
-            code: KeyCode::Char('/'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "," => KeyEvent {
-            code: KeyCode::Char(','),
-            modifiers: KeyModifiers::NONE,
-        },
-        "." => KeyEvent {
-            code: KeyCode::Char('.'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "<" => KeyEvent {
-            code: KeyCode::Char('<'),
-            modifiers: KeyModifiers::NONE,
-        },
-        ">" => KeyEvent {
-            code: KeyCode::Char('>'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "_" => KeyEvent {
-            code: KeyCode::Char('_'),
-            modifiers: KeyModifiers::NONE,
-        },
-        "S-a" => KeyEvent {
-            code: KeyCode::Char('A'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-b" => KeyEvent {
-            code: KeyCode::Char('B'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-c" => KeyEvent {
-            code: KeyCode::Char('C'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-d" => KeyEvent {
-            code: KeyCode::Char('D'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-e" => KeyEvent {
-            code: KeyCode::Char('E'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-f" => KeyEvent {
-            code: KeyCode::Char('F'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-g" => KeyEvent {
-            code: KeyCode::Char('G'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-h" => KeyEvent {
-            code: KeyCode::Char('H'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-i" => KeyEvent {
-            code: KeyCode::Char('I'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-j" => KeyEvent {
-            code: KeyCode::Char('J'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-k" => KeyEvent {
-            code: KeyCode::Char('K'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-l" => KeyEvent {
-            code: KeyCode::Char('L'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-m" => KeyEvent {
-            code: KeyCode::Char('M'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-n" => KeyEvent {
-            code: KeyCode::Char('N'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-o" => KeyEvent {
-            code: KeyCode::Char('O'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-p" => KeyEvent {
-            code: KeyCode::Char('P'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-q" => KeyEvent {
-            code: KeyCode::Char('Q'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-r" => KeyEvent {
-            code: KeyCode::Char('R'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-s" => KeyEvent {
-            code: KeyCode::Char('S'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-t" => KeyEvent {
-            code: KeyCode::Char('T'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-u" => KeyEvent {
-            code: KeyCode::Char('U'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-v" => KeyEvent {
-            code: KeyCode::Char('V'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-w" => KeyEvent {
-            code: KeyCode::Char('W'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-x" => KeyEvent {
-            code: KeyCode::Char('X'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-y" => KeyEvent {
-            code: KeyCode::Char('Y'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "S-z" => KeyEvent {
-            code: KeyCode::Char('Z'),
-            modifiers: KeyModifiers::SHIFT,
-        },
-        "C-a" => KeyEvent {
-            code: KeyCode::Char('A'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-b" => KeyEvent {
-            code: KeyCode::Char('B'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-c" => KeyEvent {
-            code: KeyCode::Char('C'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-d" => KeyEvent {
-            code: KeyCode::Char('D'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-e" => KeyEvent {
-            code: KeyCode::Char('E'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-f" => KeyEvent {
-            code: KeyCode::Char('F'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-g" => KeyEvent {
-            code: KeyCode::Char('G'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-h" => KeyEvent {
-            code: KeyCode::Char('H'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-i" => KeyEvent {
-            code: KeyCode::Char('I'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-j" => KeyEvent {
-            code: KeyCode::Char('J'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-k" => KeyEvent {
-            code: KeyCode::Char('K'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-l" => KeyEvent {
-            code: KeyCode::Char('L'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-m" => KeyEvent {
-            code: KeyCode::Char('M'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-n" => KeyEvent {
-            code: KeyCode::Char('N'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-o" => KeyEvent {
-            code: KeyCode::Char('O'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-p" => KeyEvent {
-            code: KeyCode::Char('P'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-q" => KeyEvent {
-            code: KeyCode::Char('Q'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-r" => KeyEvent {
-            code: KeyCode::Char('R'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-s" => KeyEvent {
-            code: KeyCode::Char('S'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-t" => KeyEvent {
-            code: KeyCode::Char('T'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-u" => KeyEvent {
-            code: KeyCode::Char('U'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-v" => KeyEvent {
-            code: KeyCode::Char('V'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-w" => KeyEvent {
-            code: KeyCode::Char('W'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-x" => KeyEvent {
-            code: KeyCode::Char('X'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-y" => KeyEvent {
-            code: KeyCode::Char('Y'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "C-z" => KeyEvent {
-            code: KeyCode::Char('Z'),
-            modifiers: KeyModifiers::CONTROL,
-        },
-        "A-a" => KeyEvent {
-            code: KeyCode::Char('A'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-b" => KeyEvent {
-            code: KeyCode::Char('B'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-c" => KeyEvent {
-            code: KeyCode::Char('C'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-d" => KeyEvent {
-            code: KeyCode::Char('D'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-e" => KeyEvent {
-            code: KeyCode::Char('E'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-f" => KeyEvent {
-            code: KeyCode::Char('F'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-g" => KeyEvent {
-            code: KeyCode::Char('G'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-h" => KeyEvent {
-            code: KeyCode::Char('H'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-i" => KeyEvent {
-            code: KeyCode::Char('I'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-j" => KeyEvent {
-            code: KeyCode::Char('J'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-k" => KeyEvent {
-            code: KeyCode::Char('K'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-l" => KeyEvent {
-            code: KeyCode::Char('L'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-m" => KeyEvent {
-            code: KeyCode::Char('M'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-n" => KeyEvent {
-            code: KeyCode::Char('N'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-o" => KeyEvent {
-            code: KeyCode::Char('O'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-p" => KeyEvent {
-            code: KeyCode::Char('P'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-q" => KeyEvent {
-            code: KeyCode::Char('Q'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-r" => KeyEvent {
-            code: KeyCode::Char('R'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-s" => KeyEvent {
-            code: KeyCode::Char('S'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-t" => KeyEvent {
-            code: KeyCode::Char('T'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-u" => KeyEvent {
-            code: KeyCode::Char('U'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-v" => KeyEvent {
-            code: KeyCode::Char('V'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-w" => KeyEvent {
-            code: KeyCode::Char('W'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-x" => KeyEvent {
-            code: KeyCode::Char('X'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-y" => KeyEvent {
-            code: KeyCode::Char('Y'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "A-z" => KeyEvent {
-            code: KeyCode::Char('Z'),
-            modifiers: KeyModifiers::ALT,
-        },
-        "Up" => KeyEvent {
-            code: KeyCode::Up,
-            modifiers: KeyModifiers::NONE,
-        },
-        "Down" => KeyEvent {
-            code: KeyCode::Down,
-            modifiers: KeyModifiers::NONE,
-        },
-        "Left" => KeyEvent {
-            code: KeyCode::Left,
-            modifiers: KeyModifiers::NONE,
-        },
-        "Right" => KeyEvent {
-            code: KeyCode::Right,
-            modifiers: KeyModifiers::NONE,
-        },
-        "up" => KeyEvent {
-            code: KeyCode::Up,
-            modifiers: KeyModifiers::NONE,
-        },
-        "down" => KeyEvent {
-            code: KeyCode::Down,
-            modifiers: KeyModifiers::NONE,
-        },
-        "left" => KeyEvent {
-            code: KeyCode::Left,
-            modifiers: KeyModifiers::NONE,
-        },
-        "right" => KeyEvent {
-            code: KeyCode::Right,
-            modifiers: KeyModifiers::NONE,
-        },
-        _ => KeyEvent {
-            code: KeyCode::Null,
-            modifiers: KeyModifiers::NONE,
-        },
-    }
+fn default_mouse() -> bool {
+    true
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub struct UserKeyCode {
-    pub first: KeyEvent,
-    pub second: Option<KeyEvent>,
-    // combo: Vec<KeyEvent>,
-    combo: bool,
+fn default_show_hidden() -> bool {
+    true
 }
 
-impl UserKeyCode {
-    pub fn single_new(first: KeyEvent) -> Self {
-        Self {
-            first,
-            second: None,
-            // coc![],
-            combo: false,
-        }
-    }
+fn default_git_branch() -> bool {
+    true
+}
 
-    pub fn multi_new(first: KeyEvent, second: KeyEvent) -> Self {
-        Self {
-            first,
-            second: Some(second),
-            // combo,
-            combo: true,
-        }
-    }
+fn default_which_key() -> bool {
+    true
 }
 
-type Keybind = HashMap<UserKeyCode, String>;
-#[derive(Debug, PartialEq, Eq)]
-pub struct UserKeybinds {
-    single: Keybind,
-    multi: Keybind,
-    filtered_multi: Option<Keybind>,
-    key: KeyEvent,
+fn default_which_key_delay_ms() -> u64 {
+    500
 }
 
-impl UserKeybinds {
-    pub fn new() -> Self {
-        Self {
-            single: HashMap::new(),
-            multi: HashMap::new(),
-            filtered_multi: None,
-            key: KeyEvent {
-                code: KeyCode::Null,
-                modifiers: KeyModifiers::NONE,
-            },
+impl UserConfig {
+    pub fn default_dark() -> UserConfig {
+        UserConfig {
+            theme: SettingTheme::dark_theme(),
+            symbols: simple_symbols(),
+            user_keybinds: default_vim_movements(),
+            bookmarks: HashMap::new(),
+            watch_config: false,
+            which_key: default_which_key(),
+            which_key_delay_ms: default_which_key_delay_ms(),
+            mouse: default_mouse(),
+            theme_name: None,
+            colors: ColorSupport::Auto,
+            columns: default_columns(),
+            date_format: default_date_format(),
+            show_hidden: true,
+            ignore_globs: Vec::new(),
+            gitignore: false,
+            git_branch: true,
+            ssh_key: None,
+            dir_sizes: false,
         }
     }
 
-    pub fn set_keyevent(&mut self, key: KeyEvent) {
-        self.key = key;
+    pub fn symbols(&self) -> &HashMap<FileItems, String> {
+        &self.symbols
     }
 
-    pub fn matching_single_keys(&self) -> Option<String> {
-        let mut filtered = self
-            .single
-            .iter()
-            .filter(|(keycode, _cmd)| keycode.first == self.key && keycode.second.is_none());
-        if filtered.size_hint().1.unwrap() == 0 {
-            None
-        } else if let Some((_, cmd)) = filtered.next() {
-            Some(cmd.to_owned())
-        } else {
-            None
-        }
+    pub fn theme(&self) -> &SettingTheme {
+        &self.theme
     }
 
-    pub fn filtering_multi_first_keys(&mut self) {
-        let filtered_keybinds: HashMap<UserKeyCode, String> = self
-            .multi
-            .iter()
-            .filter(|(keycode, _cmd)| keycode.first == self.key && keycode.second.is_some())
-            .map(|(key, cmd)| (key.to_owned(), cmd.to_owned()))
-            .collect();
-        if filtered_keybinds.is_empty() {
-            return;
-        }
-
-        self.filtered_multi = Some(filtered_keybinds);
+    pub fn set_theme(&mut self, theme: SettingTheme) {
+        self.theme = theme;
+        self.theme.set_color_support(self.colors);
     }
 
-    pub fn matching_multi_second_keys(&self) -> Option<String> {
-        self.filtered_multi.as_ref()?;
-
-        if let Some((_, cmb_cmd)) = self
-            .filtered_multi
-            .as_ref()
-            .unwrap()
-            .iter()
-            .find(|(keycode, _)| keycode.second.is_some() && keycode.second.unwrap() == self.key)
-        {
-            return Some(cmb_cmd.to_owned());
-        }
+    pub fn bookmarks(&self) -> &HashMap<char, PathBuf> {
+        &self.bookmarks
+    }
 
-        None
+    pub fn watch_config(&self) -> bool {
+        self.watch_config
     }
 
-    pub fn has_keycomb(&self) -> bool {
-        self.filtered_multi.is_some()
+    pub fn mouse(&self) -> bool {
+        self.mouse
     }
 
-    pub fn make_single_keybinds(
-        mut self,
-        config_user_keybind: HashMap<Vec<KeyEvent>, String>,
-    ) -> Self {
-        let single: Keybind = config_user_keybind
-            .into_iter()
-            .filter(|(key, _cmd)| key.len() == 1)
-            .map(|(x, c)| (UserKeyCode::single_new(x[0]), c))
-            .collect();
-        self.single = single;
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
 
-        self
+    pub fn columns_mut(&mut self) -> &mut Vec<Column> {
+        &mut self.columns
     }
 
-    pub fn make_multiple_keybinds(
-        mut self,
-        config_user_keybind: HashMap<Vec<KeyEvent>, String>,
-    ) -> Self {
-        let multi: Keybind = config_user_keybind
-            .into_iter()
-            .filter(|(key, _cmd)| key.len() > 1)
-            .map(|(x, c)| (UserKeyCode::multi_new(x[0], x[1]), c))
-            .collect();
-        self.multi = multi;
+    pub fn date_format(&self) -> &str {
+        &self.date_format
+    }
 
-        self
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
     }
-}
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct SettingTheme {
-    background: Colors,
-    boader: Colors,
-    directory: Colors,
-    file_item: Colors,
-    select: Colors,
-    header: Colors,
-    // (bg, fg): normal, input, stacker
-    command: Vec<(Colors, Colors)>,
-}
+    pub fn ignore_globs(&self) -> &[String] {
+        &self.ignore_globs
+    }
 
-impl SettingTheme {
-    fn dark_theme() -> SettingTheme {
-        SettingTheme {
-            background: Colors::Black,
-            header: Colors::Cyan,
-            boader: Colors::White,
-            directory: Colors::Blue,
-            file_item: Colors::Gray,
-            select: Colors::LightMagenta,
-            command: vec![
-                (Colors::Green, Colors::Cyan),
-                (Colors::White, Colors::Blue),
-                (Colors::Magenta, Colors::Red),
-            ],
-        }
+    pub fn gitignore(&self) -> bool {
+        self.gitignore
     }
 
-    fn light_theme() -> SettingTheme {
-        SettingTheme {
-            background: Colors::White,
-            header: Colors::Green,
-            boader: Colors::Black,
-            directory: Colors::Blue,
-            file_item: Colors::Black,
-            select: Colors::LightRed,
-            command: vec![
-                (Colors::Blue, Colors::White),
-                (Colors::Green, Colors::White),
-                (Colors::Magenta, Colors::White),
-            ],
-        }
+    pub fn git_branch(&self) -> bool {
+        self.git_branch
     }
 
-    fn dark_blue_theme() -> SettingTheme {
-        SettingTheme {
-            background: Colors::Rgb(39, 67, 100),
-            header: Colors::Green,
-            boader: Colors::Rgb(97, 169, 252),
-            directory: Colors::Blue,
-            file_item: Colors::Gray,
-            select: Colors::Green,
-            command: vec![
-                (Colors::Blue, Colors::Black),
-                (Colors::Green, Colors::Blue),
-                (Colors::Magenta, Colors::Blue),
-            ],
-        }
+    pub fn ssh_key(&self) -> Option<&Path> {
+        self.ssh_key.as_deref()
     }
 
-    pub fn file_style(&self) -> Style {
-        let user_color = self.file_item.clone();
-        style_formatter(user_color, true, false)
+    pub fn dir_sizes(&self) -> bool {
+        self.dir_sizes
     }
 
-    pub fn dir_style(&self) -> Style {
-        let user_color = self.directory.clone();
-        style_formatter(user_color, true, false)
+    // None when the popup is turned off
+    pub fn which_key_delay(&self) -> Option<Duration> {
+        self.which_key
+            .then(|| Duration::from_millis(self.which_key_delay_ms))
     }
 
-    pub fn select_style(&self) -> Style {
-        let user_color = self.select.clone();
-        style_formatter(user_color, true, false)
+    pub fn normal_keybindings_map(&self) -> HashMap<String, String> {
+        self.user_keybinds.normal.clone()
     }
 
-    pub fn header_style(&self) -> Style {
-        let user_color = self.header.clone();
-        style_formatter(user_color, true, false)
+    pub fn input_keybindings_map(&self) -> HashMap<String, String> {
+        self.user_keybinds.input.clone()
     }
 
-    pub fn boader_style(&self) -> Style {
-        let user_color = self.boader.clone();
-        style_formatter(user_color, true, false)
+    pub fn stacker_keybindings_map(&self) -> HashMap<String, String> {
+        self.user_keybinds.stacker.clone()
     }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename = "ModeKeybinds", default)]
+struct PartialModeKeybinds {
+    normal: HashMap<String, String>,
+    input: HashMap<String, String>,
+    stacker: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename = "UserConfig", default)]
+struct PartialUserConfig {
+    #[serde(deserialize_with = "some")]
+    theme: Option<PartialSettingTheme>,
+    symbols: HashMap<FileItems, String>,
+    #[serde(deserialize_with = "some")]
+    user_keybinds: Option<PartialModeKeybinds>,
+    bookmarks: HashMap<char, PathBuf>,
+    #[serde(deserialize_with = "some")]
+    watch_config: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    which_key: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    which_key_delay_ms: Option<u64>,
+    #[serde(deserialize_with = "some")]
+    mouse: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    theme_name: Option<String>,
+    #[serde(deserialize_with = "some")]
+    colors: Option<ColorSupport>,
+    #[serde(deserialize_with = "some")]
+    columns: Option<Vec<Column>>,
+    #[serde(deserialize_with = "some")]
+    date_format: Option<String>,
+    #[serde(deserialize_with = "some")]
+    show_hidden: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    ignore_globs: Option<Vec<String>>,
+    #[serde(deserialize_with = "some")]
+    gitignore: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    git_branch: Option<bool>,
+    #[serde(deserialize_with = "some")]
+    ssh_key: Option<PathBuf>,
+    #[serde(deserialize_with = "some")]
+    dir_sizes: Option<bool>,
+}
 
-    pub fn command_style(&self) -> [Style; 3] {
-        let user_color = self.command.clone();
-        let mut styles: [Style; 3] = [Style::default(); 3];
-        for (i, (bg, fg)) in user_color.into_iter().enumerate() {
-            let bg = style_formatter(bg, true, false);
-            let fg = style_formatter(fg, false, true);
-            styles[i] = bg.patch(fg);
+// Lets `field: value` be read into an Option without writing `Some(value)`
+pub(crate) fn some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl UserConfig {
+    fn check_keybinds(&self) -> Result<(), ConfigError> {
+        let modes = [
+            ("normal", &self.user_keybinds.normal),
+            ("input", &self.user_keybinds.input),
+            ("stacker", &self.user_keybinds.stacker),
+        ];
+        for (mode, keybinds) in modes {
+            KeyTrie::from_keybinds(keybinds).map_err(|e| ConfigError::Keybind(mode, e))?;
         }
-        styles
+        Ok(())
     }
 
-    pub fn background_style(&self) -> Style {
-        let user_color = self.background.clone();
-        style_formatter(user_color, false, true)
+    fn layer(&mut self, partial: PartialUserConfig) {
+        if let Some(theme) = partial.theme {
+            self.theme.layer(theme);
+        }
+        if let Some(name) = partial.theme_name {
+            self.theme_name = Some(name);
+        }
+        if let Some(colors) = partial.colors {
+            self.colors = colors;
+        }
+        self.symbols.extend(partial.symbols);
+        if let Some(keybinds) = partial.user_keybinds {
+            layer_keybinds(&mut self.user_keybinds.normal, keybinds.normal);
+            layer_keybinds(&mut self.user_keybinds.input, keybinds.input);
+            layer_keybinds(&mut self.user_keybinds.stacker, keybinds.stacker);
+        }
+        self.bookmarks.extend(partial.bookmarks);
+        if let Some(watch) = partial.watch_config {
+            self.watch_config = watch;
+        }
+        if let Some(which_key) = partial.which_key {
+            self.which_key = which_key;
+        }
+        if let Some(delay) = partial.which_key_delay_ms {
+            self.which_key_delay_ms = delay;
+        }
+        if let Some(mouse) = partial.mouse {
+            self.mouse = mouse;
+        }
+        if let Some(columns) = partial.columns {
+            self.columns = columns;
+        }
+        if let Some(format) = partial.date_format {
+            self.date_format = format;
+        }
+        if let Some(show_hidden) = partial.show_hidden {
+            self.show_hidden = show_hidden;
+        }
+        if let Some(globs) = partial.ignore_globs {
+            self.ignore_globs = globs;
+        }
+        if let Some(gitignore) = partial.gitignore {
+            self.gitignore = gitignore;
+        }
+        if let Some(git_branch) = partial.git_branch {
+            self.git_branch = git_branch;
+        }
+        if let Some(key) = partial.ssh_key {
+            self.ssh_key = Some(key);
+        }
+        if let Some(dir_sizes) = partial.dir_sizes {
+            self.dir_sizes = dir_sizes;
+        }
     }
 }
 
-fn color_translator(color: Colors) -> Option<Color> {
-    let c = match color {
-        Colors::White => Color::White,
-        Colors::Black => Color::Black,
-        Colors::Yellow => Color::Yellow,
-        Colors::Blue => Color::Blue,
-        Colors::Green => Color::Green,
-        Colors::Red => Color::Red,
-        Colors::Gray => Color::Gray,
-        Colors::DarkGray => Color::DarkGray,
-        Colors::Cyan => Color::Cyan,
-        Colors::Magenta => Color::Magenta,
-        Colors::LightBlue => Color::LightBlue,
-        Colors::LightRed => Color::LightRed,
-        Colors::LightGreen => Color::LightGreen,
-        Colors::LightMagenta => Color::LightMagenta,
-        Colors::LightYellow => Color::LightYellow,
-        Colors::LightCyan => Color::LightCyan,
-        Colors::Rgb(r, g, b) => Color::Rgb(r, g, b),
-        _ => Color::Reset,
-    };
-    Some(c)
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    // ron::Error carries the line and column
+    Parse(PathBuf, ron::Error),
+    // the mode the binding is in
+    Keybind(&'static str, KeybindError),
+    UnknownTheme(String),
 }
 
-fn tui_color_transformer(color: Color) -> Colors {
-    match color {
-        Color::Reset => Colors::Rgb(0, 0, 0),
-        Color::Black => Colors::Black,
-        Color::Red => Colors::Red,
-        Color::Green => Colors::Green,
-        Color::Yellow => Colors::Yellow,
-        Color::Blue => Colors::Blue,
-        Color::Magenta => Colors::Magenta,
-        Color::Cyan => Colors::Cyan,
-        Color::Gray => Colors::Gray,
-        Color::DarkGray => Colors::DarkGray,
-        Color::LightRed => Colors::LightRed,
-        Color::LightGreen => Colors::LightGreen,
-        Color::LightYellow => Colors::LightYellow,
-        Color::LightBlue => Colors::LightBlue,
-        Color::LightMagenta => Colors::LightMagenta,
-        Color::LightCyan => Colors::LightCyan,
-        Color::White => Colors::White,
-        Color::Rgb(r, g, b) => Colors::Rgb(r, g, b),
-        Color::Indexed(_) => Colors::Rgb(255, 255, 255),
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{}:{}", path.display(), e),
+            ConfigError::Keybind(mode, e) => write!(f, "{} keybinding {}", mode, e),
+            ConfigError::UnknownTheme(name) => write!(
+                f,
+                "unknown theme \"{}\", expected one of {} or a file in {}/",
+                name,
+                SettingTheme::NAMES.join(", "),
+                THEMES_DIR_NAME
+            ),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Hash)]
-pub enum FileItems {
-    File,
-    Directory,
-    Select,
+impl std::error::Error for ConfigError {}
+
+const CONFIG_FILE_NAME: &str = "config.ron";
+
+// The user's own config.ron. Files the app writes next to it (bookmarks) go there too.
+pub fn user_config_file_path() -> PathBuf {
+    match get_config_directory_path() {
+        Some(dir) => dir.join(CONFIG_FILE_NAME),
+        None => PathBuf::from(CONFIG_FILE_NAME),
+    }
 }
 
-fn example_symbols() -> HashMap<FileItems, String> {
-    let map: HashMap<FileItems, String> = HashMap::from([
-        (FileItems::File, "📜".to_string()),
-        (FileItems::Directory, "📁".to_string()),
-        (FileItems::Select, "⋙".to_string()),
-    ]);
-    map
+// Lowest priority first: system wide configs, the user's config, config.ron in
+// the working directory and then `--config`
+pub fn config_file_layers(override_path: Option<&Path>) -> Vec<PathBuf> {
+    let mut layers: Vec<PathBuf> = get_system_config_directory_paths()
+        .into_iter()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .collect();
+    layers.push(user_config_file_path());
+    // without a config directory the user's config is this one
+    let local = PathBuf::from(CONFIG_FILE_NAME);
+    if !layers.contains(&local) {
+        layers.push(local);
+    }
+    if let Some(path) = override_path {
+        layers.push(path.to_path_buf());
+    }
+    layers
 }
 
-fn simple_symbols() -> HashMap<FileItems, String> {
-    let map: HashMap<FileItems, String> = HashMap::from([
-        (FileItems::File, " ".to_string()),
-        (FileItems::Directory, "▸".to_string()),
-        (FileItems::Select, ">>".to_string()),
-    ]);
-    map
+// ron leaves the position empty for errors such as an unknown variant,
+// so it is filled in from where the deserializer stopped.
+pub(crate) fn parse_ron<'a, T: Deserialize<'a>>(s: &'a str) -> Result<T, ron::Error> {
+    let mut de = ron::de::Deserializer::from_str(s)?;
+    let res = T::deserialize(&mut de).and_then(|t| de.end().map(|_| t));
+    res.map_err(|mut e| {
+        if e.position.line == 0 {
+            let consumed = &s[..s.len() - de.remainder().len()];
+            e.position.line = consumed.matches('\n').count() + 1;
+            e.position.col = consumed.len() - consumed.rfind('\n').map_or(0, |i| i + 1) + 1;
+        }
+        e
+    })
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct ModeKeybinds {
-    pub normal: HashMap<String, String>,
-    pub input: HashMap<String, String>,
-    pub stacker: HashMap<String, String>,
+fn load_partial_config_file(path: &Path) -> Result<PartialUserConfig, ConfigError> {
+    let s = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    parse_ron(&s).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct UserConfig {
-    theme: SettingTheme,
-    symbols: HashMap<FileItems, String>,
-    user_keybinds: ModeKeybinds,
+// Polls the modification time of each config layer
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
 }
 
-impl UserConfig {
-    pub fn default_dark() -> UserConfig {
-        UserConfig {
-            theme: SettingTheme::dark_theme(),
-            symbols: simple_symbols(),
-            user_keybinds: default_vim_movements(),
-        }
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified_time(&path);
+                (path, modified)
+            })
+            .collect();
+        Self { files }
     }
 
-    pub fn default_dark_blue() -> UserConfig {
-        UserConfig {
-            theme: SettingTheme::dark_blue_theme(),
-            symbols: example_symbols(),
-            user_keybinds: default_vim_ctrl_movements(),
+    // True once for every batch of changes, including files that appear or disappear
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in self.files.iter_mut() {
+            let modified = modified_time(path);
+            if modified != *last {
+                *last = modified;
+                changed = true;
+            }
         }
+        changed
     }
+}
 
-    pub fn default_light() -> UserConfig {
-        UserConfig {
-            theme: SettingTheme::light_theme(),
-            symbols: example_symbols(),
-            user_keybinds: default_arrow_key(),
-        }
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// The layers that exist. `--config` is always read, so a missing one is an error.
+fn existing_config_layers(override_path: Option<&Path>) -> Vec<PathBuf> {
+    config_file_layers(override_path)
+        .into_iter()
+        .filter(|path| Some(path.as_path()) == override_path || path.exists())
+        .collect()
+}
+
+// The named theme comes from whichever layer names one last, and the `theme`
+// fields of every layer go over it.
+fn layered_config(partials: Vec<PartialUserConfig>) -> Result<UserConfig, ConfigError> {
+    let mut config = UserConfig::default_dark();
+    if let Some(name) = partials.iter().rev().find_map(|p| p.theme_name.as_deref()) {
+        config.theme = SettingTheme::from_name(name)?;
     }
-    pub fn symbols(&self) -> &HashMap<FileItems, String> {
-        &self.symbols
+    for partial in partials {
+        config.layer(partial);
     }
+    config.theme.set_color_support(config.colors);
+    Ok(config)
+}
 
-    pub fn theme(&self) -> &SettingTheme {
-        &self.theme
-    }
+// Missing files are skipped, but a file that exists and cannot be read is an error.
+pub fn load_user_config_file(override_path: Option<&Path>) -> Result<UserConfig, ConfigError> {
+    let partials = existing_config_layers(override_path)
+        .iter()
+        .map(|path| load_partial_config_file(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = layered_config(partials)?;
+    config.check_keybinds()?;
+    Ok(config)
+}
 
-    fn keybindings_map(&self) -> ModeKeybinds {
-        self.user_keybinds.clone()
-    }
+// A RON map read as a list of its keys, so a key written twice is not merged away
+#[derive(Debug, Default)]
+struct MapKeys(Vec<String>);
 
-    pub fn normal_keybindings_map(&self) -> HashMap<String, String> {
-        self.user_keybinds.normal.clone()
-    }
+impl<'de> Deserialize<'de> for MapKeys {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeysVisitor;
+        impl<'de> serde::de::Visitor<'de> for KeysVisitor {
+            type Value = MapKeys;
 
-    pub fn input_keybindings_map(&self) -> HashMap<String, String> {
-        self.user_keybinds.input.clone()
-    }
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a map")
+            }
 
-    pub fn stacker_keybindings_map(&self) -> HashMap<String, String> {
-        self.user_keybinds.stacker.clone()
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<MapKeys, A::Error> {
+                let mut keys = Vec::new();
+                while let Some((key, _)) = map.next_entry::<String, serde::de::IgnoredAny>()? {
+                    keys.push(key);
+                }
+                Ok(MapKeys(keys))
+            }
+        }
+        deserializer.deserialize_map(KeysVisitor)
     }
 }
 
-fn style_formatter(color: Colors, is_fg: bool, is_bg: bool) -> Style {
-    let color = color_translator(color).unwrap();
-    match (is_fg, is_bg) {
-        (true, true) => panic!("is_fg and is_bg is not always true"), // review required
-        (true, false) => Style::default().fg(color),
-        (false, true) => Style::default().bg(color),
-        (false, false) => panic!("is_fg or is_bg is always true"),
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename = "ModeKeybinds", default)]
+struct ModeKeybindKeys {
+    normal: MapKeys,
+    input: MapKeys,
+    stacker: MapKeys,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename = "UserConfig", default)]
+struct KeybindKeys {
+    user_keybinds: ModeKeybindKeys,
+}
+
+fn duplicate_keybinds(path: &Path, s: &str) -> Vec<String> {
+    let keys: KeybindKeys = match parse_ron(s) {
+        Ok(keys) => keys,
+        Err(_) => return Vec::new(),
+    };
+    let modes = [
+        ("normal", keys.user_keybinds.normal),
+        ("input", keys.user_keybinds.input),
+        ("stacker", keys.user_keybinds.stacker),
+    ];
+    let mut problems = Vec::new();
+    for (mode, MapKeys(keys)) in modes {
+        for (i, key) in keys.iter().enumerate() {
+            // reported once, at the second occurrence
+            if keys[..i].iter().filter(|k| *k == key).count() == 1 {
+                problems.push(format!(
+                    "{}: {} keybinding \"{}\" is written more than once, only the last one is used",
+                    path.display(),
+                    mode,
+                    key
+                ));
+            }
+        }
     }
+    problems
+}
+
+// What `--check-config` reports. Loading stops at the first error, this keeps
+// going and also looks for things loading lets through: keys written twice in a
+// map, which the map silently merges, and commands that don't exist.
+pub fn check_config_files(override_path: Option<&Path>) -> Vec<String> {
+    check_config_layers(existing_config_layers(override_path))
 }
 
-pub fn load_user_config_file() -> UserConfig {
-    // Each Windows, Mac(Linux)
-    // Consider specifying PATH in each OS
-    let path = "config.ron";
-    match std::fs::File::open(path) {
-        Ok(f) => {
-            let config: Result<UserConfig, ron::de::Error> = ron::de::from_reader(f);
-            if let Ok(config) = config {
-                config
-            } else {
-                UserConfig::default_dark_blue()
+fn check_config_layers(layers: Vec<PathBuf>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut partials = Vec::new();
+    for path in layers {
+        let s = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                problems.push(ConfigError::Io(path, e).to_string());
+                continue;
+            }
+        };
+        match parse_ron(&s) {
+            Ok(partial) => partials.push(partial),
+            Err(e) => {
+                problems.push(ConfigError::Parse(path, e).to_string());
+                continue;
+            }
+        }
+        problems.extend(duplicate_keybinds(&path, &s));
+    }
+    let config = match layered_config(partials) {
+        Ok(config) => config,
+        Err(e) => {
+            problems.push(e.to_string());
+            return problems;
+        }
+    };
+
+    let modes = [
+        ("normal", &config.user_keybinds.normal),
+        ("input", &config.user_keybinds.input),
+        ("stacker", &config.user_keybinds.stacker),
+    ];
+    for (mode, keybinds) in modes {
+        let (_, errors) = KeyTrie::build(keybinds);
+        problems.extend(
+            errors
+                .into_iter()
+                .map(|e| ConfigError::Keybind(mode, e).to_string()),
+        );
+
+        let mut sorted: Vec<(&String, &String)> = keybinds.iter().collect();
+        sorted.sort();
+        for (key, cmd) in sorted {
+            if command_description(cmd).is_none() {
+                problems.push(format!(
+                    "{} keybinding \"{}\": unknown command \"{}\"",
+                    mode, key, cmd
+                ));
             }
         }
-        // TODO: logging this e
-        Err(e) => UserConfig::default_dark(),
     }
+    let (_, errors) = IgnoreRules::new(&config.ignore_globs, config.gitignore);
+    problems.extend(errors);
+    problems
 }
 
 #[cfg(test)]
 mod test {
     use ron::de;
+    use tui::style::{Color, Style};
 
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        check_config_layers, config_file_layers, parse_ron, ConfigError, ConfigWatcher,
+        PartialUserConfig,
+    };
     use crate::load_config::UserConfig;
+    use crate::temp_dir::TempDir;
 
     #[test]
     fn can_read_ron_file() {
@@ -1232,8 +765,130 @@ mod test {
             Err(e) => println!("{e:#?}"),
         }
         assert!(config.is_ok());
-        let config = config.unwrap();
-        let keybinds = config.keybindings_map();
-        println!("{:#?}", keybinds);
+    }
+
+    #[test]
+    fn partial_config_is_layered_over_defaults() {
+        let partial = r#"(
+            theme: SettingTheme(directory: Red),
+            user_keybinds: ModeKeybinds(normal: { "x": "quit" }),
+            which_key: false,
+        )"#;
+        let partial: PartialUserConfig = ron::de::from_str(partial).unwrap();
+        let mut config = UserConfig::default_dark();
+        config.layer(partial);
+
+        let default = UserConfig::default_dark();
+        assert_eq!(config.theme.dir_style(), Style::default().fg(Color::Red));
+        assert_eq!(config.theme.file_style(), default.theme.file_style());
+        assert_eq!(config.user_keybinds.normal["x"], "quit");
+        assert_eq!(config.user_keybinds.normal["j"], "move_to_next_file_item");
+        assert_eq!(config.symbols, default.symbols);
+        assert_eq!(config.which_key_delay(), None);
+
+        let sample = std::fs::read_to_string("config.ron").unwrap();
+        assert!(parse_ron::<PartialUserConfig>(&sample).is_ok());
+    }
+
+    #[test]
+    fn default_and_sample_keybinds_parse() {
+        let mut config = UserConfig::default_dark();
+        assert!(config.check_keybinds().is_ok());
+        let sample = std::fs::read_to_string("config.ron").unwrap();
+        config.layer(parse_ron(&sample).unwrap());
+        assert!(config.check_keybinds().is_ok());
+
+        config
+            .user_keybinds
+            .normal
+            .insert("C-escpe".into(), "quit".into());
+        let err = config.check_keybinds().unwrap_err();
+        assert!(err.to_string().contains("C-escpe"));
+    }
+
+    #[test]
+    fn later_layer_replaces_conflicting_keybinds() {
+        let mut config = UserConfig::default_dark();
+        let partial = r#"(user_keybinds: ModeKeybinds(normal: { "g": "quit", "s-g": "quit" }))"#;
+        config.layer(parse_ron(partial).unwrap());
+        let normal = &config.user_keybinds.normal;
+        assert!(!normal.contains_key("g g"));
+        assert!(!normal.contains_key("S-g"));
+        assert!(config.which_key_delay().is_some());
+        assert_eq!(normal["s-g"], "quit");
+        assert!(config.check_keybinds().is_ok());
+    }
+
+    #[test]
+    fn check_config_reports_every_problem() {
+        let dir = TempDir::new("check");
+        let path = dir.join("config.ron");
+        let config = r#"(user_keybinds: ModeKeybinds(
+            input: { "q": "quit", "x": "make_directory", "q": "quit" },
+            stacker: { "escpe": "normal", "z": "quit", "z z": "quit" },
+        ))"#;
+        std::fs::write(&path, config).unwrap();
+        let problems = check_config_layers(vec![path]);
+
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[0].contains("\"q\" is written more than once"));
+        assert!(problems
+            .iter()
+            .any(|p| p.contains("unknown key name \"escpe\"")));
+        assert!(problems
+            .iter()
+            .any(|p| p.contains("\"z\" is shadowed by \"z z\"")));
+        assert!(problems
+            .iter()
+            .any(|p| p.contains("unknown command \"make_directory\"")));
+    }
+
+    #[test]
+    fn check_config_reports_the_sample_duplicate() {
+        let problems = check_config_layers(vec!["config.ron".into()]);
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.contains("input") && p.contains("\"q\" is written more than once")),
+            "{:?}",
+            problems
+        );
+    }
+
+    #[test]
+    fn watcher_notices_created_and_removed_files() {
+        let dir = TempDir::new("watch");
+        let path = dir.join("config.ron");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()]);
+        assert!(!watcher.changed());
+        std::fs::write(&path, "()").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+
+    #[test]
+    fn working_directory_config_comes_before_override() {
+        let layers = config_file_layers(Some(Path::new("/x/over.ron")));
+        assert_eq!(
+            layers[layers.len() - 2..],
+            [PathBuf::from("config.ron"), PathBuf::from("/x/over.ron")]
+        );
+        let layers = config_file_layers(None);
+        assert_eq!(layers.last(), Some(&PathBuf::from("config.ron")));
+    }
+
+    #[test]
+    fn parse_error_has_line_and_column() {
+        let path = std::path::PathBuf::from("config.ron");
+        let e = parse_ron::<PartialUserConfig>("(\n  theme: Nope(,\n)").unwrap_err();
+        let msg = ConfigError::Parse(path.clone(), e).to_string();
+        assert!(msg.starts_with("config.ron:2:"), "{}", msg);
+
+        let s = "(\n  theme: SettingTheme(\n    header: Purple,\n  ),\n)";
+        let e = parse_ron::<PartialUserConfig>(s).unwrap_err();
+        let msg = ConfigError::Parse(path, e).to_string();
+        assert!(msg.starts_with("config.ron:3:"), "{}", msg);
     }
 }
@@ -1,45 +1,151 @@
 use application::{run_app, App};
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use cli::{parse_args, write_paths, CliCommand, USAGE};
+use crossterm::event::DisableMouseCapture;
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 
-use path_process::{current_dir_path, get_home_directory_path, pathbuf_to_string_name};
+use load_config::{check_config_files, load_user_config_file};
+use path_process::{current_dir_path, get_home_directory_path};
+use sftp::host_root;
 use std::{error::Error, io};
+use theme::SettingTheme;
 use tui::{backend::CrosstermBackend, Terminal};
 
 mod application;
+mod archive;
+mod bookmark;
+mod cli;
+mod columns;
+mod command;
+mod dir_size;
 mod file_item_list;
+mod frecency;
+mod git_status;
+mod history;
+mod ignore_rules;
 mod input_ui;
-mod keymapping;
+mod jobs;
+mod key_notation;
+mod key_trie;
+mod listing;
 mod load_config;
+mod mouse;
 mod path_process;
+mod picker;
+mod preview;
+mod sftp;
 mod state;
+#[cfg(test)]
+mod temp_dir;
+mod theme;
 mod ui;
+mod vfs;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = match parse_args(std::env::args_os().skip(1)).and_then(|cmd| match cmd {
+        CliCommand::Run(options) => options.validate().map(CliCommand::Run),
+        _ => Ok(cmd),
+    }) {
+        Ok(CliCommand::Run(options)) => options,
+        Ok(CliCommand::PrintHelp) => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(CliCommand::PrintVersion) => {
+            println!("simple-tui-file-manager {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Ok(CliCommand::PrintShellInit(shell)) => {
+            print!("{}", shell.init_script());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("simple-tui-file-manager: {}", e);
+            eprintln!("Try 'simple-tui-file-manager --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+
+    if options.check_config {
+        let problems = check_config_files(options.config.as_deref());
+        for problem in problems.iter() {
+            println!("{}", problem);
+        }
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+        println!("No problems found");
+        return Ok(());
+    }
+
+    let config = match load_user_config_file(options.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("simple-tui-file-manager: failed to load config");
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // mouse capture follows the config, see run_app
+    execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let crr_dir_path = current_dir_path();
-    let dir_name = pathbuf_to_string_name(&crr_dir_path);
-    let mut app = App::new();
-    app.insert_new_statefuldir(crr_dir_path);
-    app.push_new_dirname_to_dirtab(dir_name);
+    let mut app = App::new(config, options.config);
+    // already checked by validate
+    if let Some(Ok(theme)) = options.theme.as_deref().map(SettingTheme::from_name) {
+        app.set_theme(theme);
+    }
+    if let Some(show) = options.show_hidden {
+        app.set_show_hidden(show);
+    }
+    if let Some(key) = options.sort {
+        app.set_sort_key(key);
+    }
 
-    let home_dir_path = get_home_directory_path();
-    if let Some(path) = home_dir_path {
-        let dir_name = pathbuf_to_string_name(&path);
-        app.insert_new_statefuldir(path);
-        app.push_new_dirname_to_dirtab(dir_name);
+    // --select opens the directory of the file in front of the other tabs
+    let mut start_paths = options.paths;
+    if let Some(select) = options.select.as_ref() {
+        if let Some(parent) = select.parent() {
+            start_paths.insert(0, parent.to_path_buf());
+        }
+    }
+    if start_paths.is_empty() {
+        start_paths.push(current_dir_path());
+        if let Some(path) = get_home_directory_path() {
+            start_paths.push(path);
+        }
+    }
+    for path in start_paths {
+        let path = match app.resolve_remote(path) {
+            Ok(Some(path)) => path,
+            // a tab is opened once connected
+            Ok(None) => continue,
+            Err(e) => {
+                app.push_command_log(e);
+                continue;
+            }
+        };
+        app.insert_new_statefuldir(path.clone());
+        app.push_new_dirtab(path);
+    }
+    // only hosts, which are still being connected to
+    if app.dirtab().is_empty() {
+        let path = current_dir_path();
+        app.insert_new_statefuldir(path.clone());
+        app.push_new_dirtab(path);
+    }
+    if let Some(select) = options.select.as_ref() {
+        app.select_path(select);
     }
 
-    let res = run_app(&mut terminal, app);
+    let res = run_app(&mut terminal, &mut app);
 
     // restore terminal
     disable_raw_mode()?;
@@ -52,7 +158,21 @@ fn main() -> Result<(), Box<dyn Error>> {
     terminal.clear()?;
 
     if let Err(err) = res {
-        println!("{:?}", err)
+        println!("{:?}", err);
+        return Ok(());
+    }
+
+    if let Some(file) = options.choosedir {
+        // nothing for a remote tab, the shell can't go there
+        let dir = app.crr_local_dir();
+        let dirs = match host_root(&dir) {
+            Some(_) => Vec::new(),
+            None => vec![dir],
+        };
+        write_paths(&file, &dirs)?;
+    }
+    if let Some(file) = options.choosefiles {
+        write_paths(&file, &app.chosen_disk_paths())?;
     }
 
     Ok(())
@@ -0,0 +1,193 @@
+// Where things were drawn in the last frame, so a mouse position can be
+// turned back into a row, a tab or a part of the path in the title.
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use std::time::Duration;
+
+use tui::layout::Rect;
+use tui::text::Span;
+
+use crate::path_process::escape_os_str;
+use crate::sftp::host_root;
+
+// Two clicks on the same row within this time open it
+pub const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MouseTarget {
+    Row(usize),
+    Tab(usize),
+    // an ancestor of the current directory in the title
+    Path(PathBuf),
+}
+
+// (first column, column after the last, what is there)
+type Extent<T> = (u16, u16, T);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MouseLayout {
+    tabs_y: u16,
+    tabs: Vec<Extent<usize>>,
+    title_y: u16,
+    title: Vec<Extent<PathBuf>>,
+    rows: Rect,
+    row_offset: usize,
+    row_count: usize,
+}
+
+fn find_at<T>(extents: &[Extent<T>], column: u16) -> Option<&T> {
+    extents
+        .iter()
+        .find(|(start, end, _)| (*start..*end).contains(&column))
+        .map(|(_, _, t)| t)
+}
+
+fn width(s: &str) -> u16 {
+    Span::raw(s).width() as u16
+}
+
+impl MouseLayout {
+    // Tabs are drawn inside a border as ` title │ title ...`
+    pub fn set_tabs(&mut self, area: Rect, titles: &[String]) {
+        self.tabs_y = area.y + 1;
+        self.tabs.clear();
+        let right = area.right().saturating_sub(1);
+        let mut x = area.x + 1;
+        for (i, title) in titles.iter().enumerate() {
+            let start = x + 1;
+            let end = (start + width(title)).min(right);
+            if start >= right {
+                break;
+            }
+            self.tabs.push((start, end, i));
+            x = end + 2;
+        }
+    }
+
+    // The title starts right after the left border
+    pub fn set_title(&mut self, area: Rect, parts: Vec<(String, PathBuf)>) {
+        self.title_y = area.y;
+        self.title.clear();
+        let mut x = area.x + 1;
+        for (text, path) in parts {
+            let end = x + width(&text);
+            self.title.push((x, end, path));
+            x = end;
+        }
+    }
+
+    // The table keeps the selected row in view the same way every frame, since
+    // it is drawn from a fresh state, so the first visible row can be worked out.
+    pub fn set_rows(&mut self, rows: Rect, selected: Option<usize>, row_count: usize) {
+        let height = rows.height as usize;
+        let selected = selected.unwrap_or(0);
+        self.row_offset = if height > 0 && selected >= height {
+            selected + 1 - height
+        } else {
+            0
+        };
+        self.rows = rows;
+        self.row_count = row_count;
+    }
+
+    pub fn target(&self, column: u16, row: u16) -> Option<MouseTarget> {
+        if row == self.tabs_y && !self.tabs.is_empty() {
+            return find_at(&self.tabs, column).map(|i| MouseTarget::Tab(*i));
+        }
+        if row == self.title_y {
+            return find_at(&self.title, column).map(|path| MouseTarget::Path(path.clone()));
+        }
+        let rows = self.rows;
+        if (rows.x..rows.right()).contains(&column) && (rows.y..rows.bottom()).contains(&row) {
+            let i = self.row_offset + (row - rows.y) as usize;
+            return (i < self.row_count).then_some(MouseTarget::Row(i));
+        }
+        None
+    }
+}
+
+// "/home/user/src" -> "/" "home" "/user" "/src", each part leading to its own
+// directory, so a click on "user" goes to /home/user.
+pub fn title_parts(path: &Path) -> Vec<(String, PathBuf)> {
+    let mut parts = Vec::new();
+    let mut acc = PathBuf::new();
+    let mut needs_separator = false;
+    let mut components = path.components();
+    // "sftp://user@host" is the root of a remote path
+    if let Some(root) = host_root(path) {
+        components.nth(1);
+        parts.push((escape_os_str(root.as_os_str()), root.clone()));
+        acc = root;
+        needs_separator = true;
+    }
+    for component in components {
+        acc.push(component);
+        // the root is left as it is, on windows it is a backslash
+        let name = match component {
+            Component::Normal(name) => escape_os_str(name),
+            _ => component.as_os_str().to_string_lossy().into_owned(),
+        };
+        let mut text = String::new();
+        if needs_separator {
+            text.push(MAIN_SEPARATOR);
+        }
+        text.push_str(&name);
+        needs_separator = !name.ends_with(MAIN_SEPARATOR);
+        parts.push((text, acc.clone()));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use tui::layout::Rect;
+
+    use super::{title_parts, MouseLayout, MouseTarget};
+
+    #[test]
+    fn positions_map_back_to_targets() {
+        let mut layout = MouseLayout::default();
+        layout.set_tabs(
+            Rect::new(0, 0, 40, 3),
+            &["src".to_string(), "home".to_string()],
+        );
+        // "│ src │ home"
+        assert_eq!(layout.target(2, 1), Some(MouseTarget::Tab(0)));
+        assert_eq!(layout.target(5, 1), None);
+        assert_eq!(layout.target(8, 1), Some(MouseTarget::Tab(1)));
+
+        layout.set_title(
+            Rect::new(0, 3, 40, 20),
+            title_parts(Path::new("/home/user")),
+        );
+        assert_eq!(
+            layout.target(3, 3),
+            Some(MouseTarget::Path(PathBuf::from("/home")))
+        );
+
+        // 10 visible rows, the 15th selected, so rows 5.. are shown
+        layout.set_rows(Rect::new(1, 6, 38, 10), Some(14), 30);
+        assert_eq!(layout.target(4, 6), Some(MouseTarget::Row(5)));
+        assert_eq!(layout.target(4, 15), Some(MouseTarget::Row(14)));
+        assert_eq!(layout.target(4, 16), None);
+        layout.set_rows(Rect::new(1, 6, 38, 10), Some(0), 3);
+        assert_eq!(layout.target(4, 9), None);
+    }
+
+    #[test]
+    fn title_parts_lead_to_ancestors() {
+        let parts = title_parts(Path::new("/home/user"));
+        let texts: Vec<&str> = parts.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(texts, ["/", "home", "/user"]);
+        assert_eq!(parts[1].1, PathBuf::from("/home"));
+
+        let parts = title_parts(Path::new("/tmp/new\nline"));
+        assert_eq!(parts[2].0, "/new\\nline");
+
+        let parts = title_parts(Path::new("sftp://me@host/srv"));
+        let texts: Vec<&str> = parts.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(texts, ["sftp://me@host", "/srv"]);
+        assert_eq!(parts[0].1, PathBuf::from("sftp://me@host"));
+    }
+}
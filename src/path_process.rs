@@ -1,55 +1,78 @@
 use std::env::current_dir;
+use std::ffi::OsStr;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
 
-use crate::application::App;
-use crate::file_item_list::file_item::Extension;
+use serde::de::DeserializeOwned;
+
 use crate::file_item_list::file_item::FileItem;
 use crate::file_item_list::Kinds;
+use crate::vfs::Meta;
 
+// For showing and searching only, anything done to the file uses its path
 pub fn pathbuf_to_string_name(path: &Path) -> String {
     let path_name = path.file_name();
     if let Some(name) = path_name {
-        return name.to_str().unwrap().to_string();
+        return escape_os_str(name);
     }
     "root".to_string()
 }
 
-pub fn make_info_files_from_dirpath(path: &Path) -> Vec<FileItem> {
+// Bytes that aren't UTF-8 are shown as `\xff` and control characters are
+// escaped, so an odd name can neither panic nor mess up the terminal. A
+// backslash is doubled so a name that holds `\n` itself reads differently.
+pub fn escape_os_str(name: &OsStr) -> String {
+    let mut escaped = String::new();
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() || c == '\\' {
+                escaped.extend(c.escape_default());
+            } else {
+                escaped.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+// The items of a directory and what couldn't be read, as "name: error".
+// An entry whose metadata can't be read is still listed, marked with the error.
+pub fn make_info_files_from_dirpath(path: &Path) -> (Vec<FileItem>, Vec<String>) {
     let mut files_item: Vec<FileItem> = Vec::new();
+    let mut errors = Vec::new();
 
-    if let Ok(dir) = path.read_dir() {
-        for entry in dir {
-            let entry = entry.unwrap();
-            let file_path = entry.path();
-            let file_name = pathbuf_to_string_name(&file_path);
-            let meta = entry.metadata().unwrap();
-            let kinds = Kinds::classifiy_kinds(path, &meta);
-            let hidden = Kinds::is_hidden(path);
-            let extension = if kinds == Kinds::Directory(true) || hidden {
-                None
-            } else {
-                Some(Extension::classify_extension(&file_path))
-            };
-            files_item.push(FileItem::new(file_name, file_path, meta, kinds, extension));
+    let dir = match path.read_dir() {
+        Ok(dir) => dir,
+        Err(e) => return (files_item, vec![format!("{}: {}", path.display(), e)]),
+    };
+    for entry in dir {
+        match entry {
+            Ok(entry) => {
+                let item = make_a_info_files_from_dirpath(&entry.path());
+                if let Some(e) = item.error() {
+                    errors.push(format!("{}: {}", item.name(), e));
+                }
+                files_item.push(item);
+            }
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
         }
     }
 
-    files_item
+    (files_item, errors)
 }
 
 pub fn make_a_info_files_from_dirpath(file_path: &Path) -> FileItem {
-    let file_name = pathbuf_to_string_name(file_path);
-    let meta = file_path.metadata().expect("Failed to get metadata");
-    let kinds = Kinds::classifiy_kinds(file_path, &meta);
-    let hidden = Kinds::is_hidden(file_path);
-    let extension = if kinds == Kinds::Directory(true) || hidden {
-        None
-    } else {
-        Some(Extension::classify_extension(file_path))
+    // the link itself rather than what it points to
+    let meta = match file_path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(e) => return FileItem::unreadable(file_path.to_path_buf(), e.to_string()),
     };
-    FileItem::new(file_name, file_path.to_path_buf(), meta, kinds, extension)
+    let kinds = Kinds::classifiy_kinds(file_path, &meta);
+    FileItem::new(file_path.to_path_buf(), Meta::from_std(&meta), kinds)
 }
 
 pub fn current_dir_path() -> PathBuf {
@@ -65,7 +88,7 @@ pub fn get_home_directory_path() -> Option<PathBuf> {
     let home_dir_name = "USERPROFILE";
     match std::env::var(home_dir_name) {
         Ok(path) => Some(PathBuf::from(path)),
-        Err(e) => None,
+        Err(_) => None,
     }
 }
 
@@ -75,15 +98,165 @@ pub fn get_home_directory_path() -> Option<PathBuf> {
     let home_dir = "HOME";
     match std::env::var(home_dir) {
         Ok(path) => Some(PathBuf::from(path)),
-        Err(e) => None,
+        Err(_) => None,
+    }
+}
+
+pub const APP_DIR_NAME: &str = "simple-tui-file-manager";
+
+// Where files that the app writes by itself (history etc.) are kept
+//  C:\Users\UserName\AppData\Roaming\simple-tui-file-manager
+#[cfg(target_os = "windows")]
+pub fn get_data_directory_path() -> Option<PathBuf> {
+    match std::env::var("APPDATA") {
+        Ok(path) => Some(PathBuf::from(path).join(APP_DIR_NAME)),
+        Err(_) => None,
+    }
+}
+
+//  $XDG_DATA_HOME/simple-tui-file-manager or ~/.local/share/simple-tui-file-manager
+#[cfg(target_os = "linux")]
+pub fn get_data_directory_path() -> Option<PathBuf> {
+    match std::env::var("XDG_DATA_HOME") {
+        Ok(path) if !path.is_empty() => Some(PathBuf::from(path).join(APP_DIR_NAME)),
+        _ => get_home_directory_path().map(|home| home.join(".local/share").join(APP_DIR_NAME)),
     }
 }
 
-pub fn create_dir_by_relpath(app: &mut App, relpath: impl AsRef<Path>) -> io::Result<()> {
-    let fullpath = app
-        .peek_selected_statefuldir()
-        .directory()
-        .pathbuf()
-        .join(relpath);
-    std::fs::create_dir_all(fullpath)
+// Where the user's config.ron is looked for
+//  C:\Users\UserName\AppData\Roaming\simple-tui-file-manager
+#[cfg(target_os = "windows")]
+pub fn get_config_directory_path() -> Option<PathBuf> {
+    get_data_directory_path()
+}
+
+//  $XDG_CONFIG_HOME/simple-tui-file-manager or ~/.config/simple-tui-file-manager
+#[cfg(target_os = "linux")]
+pub fn get_config_directory_path() -> Option<PathBuf> {
+    match std::env::var("XDG_CONFIG_HOME") {
+        Ok(path) if !path.is_empty() => Some(PathBuf::from(path).join(APP_DIR_NAME)),
+        _ => get_home_directory_path().map(|home| home.join(".config").join(APP_DIR_NAME)),
+    }
+}
+
+// Config shared by all users, lowest priority first
+//  C:\ProgramData\simple-tui-file-manager
+#[cfg(target_os = "windows")]
+pub fn get_system_config_directory_paths() -> Vec<PathBuf> {
+    match std::env::var("PROGRAMDATA") {
+        Ok(path) => vec![PathBuf::from(path).join(APP_DIR_NAME)],
+        Err(_) => Vec::new(),
+    }
+}
+
+//  /etc/simple-tui-file-manager, then each of $XDG_CONFIG_DIRS (default /etc/xdg)
+#[cfg(target_os = "linux")]
+pub fn get_system_config_directory_paths() -> Vec<PathBuf> {
+    let xdg_dirs = match std::env::var("XDG_CONFIG_DIRS") {
+        Ok(dirs) if !dirs.is_empty() => dirs,
+        _ => "/etc/xdg".to_string(),
+    };
+    let mut paths = vec![PathBuf::from("/etc").join(APP_DIR_NAME)];
+    // the first directory in XDG_CONFIG_DIRS is the most important one
+    for dir in xdg_dirs.split(':').rev().filter(|d| !d.is_empty()) {
+        paths.push(PathBuf::from(dir).join(APP_DIR_NAME));
+    }
+    paths
+}
+
+// A link at `link` that points to `target`
+#[cfg(unix)]
+pub fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Windows needs to know whether the target is a directory
+#[cfg(windows)]
+pub fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    let resolved = link
+        .parent()
+        .map_or(target.to_path_buf(), |dir| dir.join(target));
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+// Where each of `sources` gets a link: inside `dest` when it is a directory,
+// otherwise at `dest` itself, which only works for a single source.
+pub fn link_destinations(sources: &[PathBuf], dest: &Path) -> Result<Vec<PathBuf>, String> {
+    if dest.is_dir() {
+        return Ok(sources
+            .iter()
+            .map(|source| dest.join(source.file_name().unwrap_or_default()))
+            .collect());
+    }
+    match sources {
+        [_] => Ok(vec![dest.to_path_buf()]),
+        _ => Err(format!("{} is not a directory", dest.display())),
+    }
+}
+
+// A ron file that was saved by us. When it doesn't parse, it is moved aside
+// so that the next save doesn't throw it away, and the reason is returned.
+pub fn load_ron_file<T: DeserializeOwned + Default>(path: &Path) -> (T, Option<String>) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return (T::default(), None),
+        Err(e) => return (T::default(), Some(format!("{}: {}", path.display(), e))),
+    };
+    let e = match ron::from_str(&text) {
+        Ok(value) => return (value, None),
+        Err(e) => e,
+    };
+    let mut aside = path.as_os_str().to_os_string();
+    aside.push(".broken");
+    let aside = PathBuf::from(aside);
+    let message = match std::fs::rename(path, &aside) {
+        Ok(()) => format!("{}: {}, moved to {}", path.display(), e, aside.display()),
+        Err(rename) => format!(
+            "{}: {}, and it couldn't be moved aside: {}",
+            path.display(),
+            e,
+            rename
+        ),
+    };
+    (T::default(), Some(message))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::{escape_os_str, make_info_files_from_dirpath};
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn odd_entries_are_listed_without_panicking() {
+        let dir = TempDir::new("read");
+        std::fs::write(dir.join(OsStr::from_bytes(b"bad\xffname")), "").unwrap();
+
+        let (items, errors) = make_info_files_from_dirpath(&dir);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name(), "bad\\xffname");
+        assert_eq!(items[0].file_name().as_bytes(), b"bad\xffname");
+        assert!(errors.is_empty());
+
+        let (items, errors) = make_info_files_from_dirpath(&dir.join("missing"));
+        assert!(items.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn names_are_escaped_for_display() {
+        assert_eq!(escape_os_str(OsStr::new("plain.txt")), "plain.txt");
+        assert_eq!(escape_os_str(OsStr::new("new\nline")), "new\\nline");
+        assert_eq!(escape_os_str(OsStr::new("back\\nslash")), "back\\\\nslash");
+        assert_eq!(
+            escape_os_str(OsStr::from_bytes(b"\xe3\x81\x82\xfe")),
+            "\u{3042}\\xfe"
+        );
+    }
 }
@@ -0,0 +1,225 @@
+use std::io;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use crate::application::App;
+use crate::ui::ui;
+
+// A popup list drawn above the directory table. Typing narrows the items down.
+#[derive(Debug, Clone)]
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    filter: String,
+    // indices into `items` that match `filter`
+    matched: Vec<usize>,
+    state: ListState,
+}
+
+impl Picker {
+    pub fn new(title: impl Into<String>, items: Vec<String>) -> Self {
+        let mut picker = Self {
+            title: title.into(),
+            items,
+            filter: String::new(),
+            matched: Vec::new(),
+            state: ListState::default(),
+        };
+        picker.update_matched();
+        picker
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn push_filter(&mut self, c: char) {
+        self.filter.push(c);
+        self.update_matched();
+    }
+
+    pub fn pop_filter(&mut self) {
+        self.filter.pop();
+        self.update_matched();
+    }
+
+    fn update_matched(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.matched = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.to_lowercase().contains(&filter))
+            .map(|(i, _)| i)
+            .collect();
+        let selected = if self.matched.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.state.select(selected);
+    }
+
+    pub fn matched_items(&self) -> impl Iterator<Item = &String> {
+        self.matched.iter().map(|&i| &self.items[i])
+    }
+
+    pub fn select_next(&mut self) {
+        if self.matched.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.matched.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn select_previous(&mut self) {
+        if self.matched.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.matched.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    // The index in the original items, not in the filtered list
+    pub fn selected_index(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.matched.get(i).copied())
+    }
+
+    pub fn state(&self) -> ListState {
+        self.state.clone()
+    }
+}
+
+pub fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+pub fn picker_ui<B: Backend>(
+    f: &mut Frame<B>,
+    picker: &Picker,
+    boader_style: Style,
+    select_style: Style,
+) {
+    let area = centered_rect(70, 60, f.size());
+    f.render_widget(Clear, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let filter = Paragraph::new(Spans::from(vec![
+        Span::raw("> "),
+        Span::raw(picker.filter()),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(boader_style)
+            .title(picker.title()),
+    );
+    f.render_widget(filter, chunks[0]);
+
+    let items: Vec<ListItem> = picker
+        .matched_items()
+        .map(|item| ListItem::new(item.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).style(boader_style))
+        .highlight_style(select_style.add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, chunks[1], &mut picker.state());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerEvent {
+    Select(usize),
+    // Ctrl + char pressed on the item
+    Action(char, usize),
+}
+
+// Blocks until an item is chosen (Some) or the picker is cancelled (None).
+pub fn run_picker<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    picker: &mut Picker,
+) -> io::Result<Option<usize>> {
+    match run_picker_with_actions(terminal, app, picker, &[])? {
+        Some(PickerEvent::Select(i)) => Ok(Some(i)),
+        _ => Ok(None),
+    }
+}
+
+// Same as run_picker, but Ctrl + one of `actions` also closes the picker.
+pub fn run_picker_with_actions<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    picker: &mut Picker,
+    actions: &[char],
+) -> io::Result<Option<PickerEvent>> {
+    loop {
+        let boader_style = app.theme().popup_style();
+        let select_style = app.theme().select_style();
+        terminal.draw(|f| {
+            ui(f, app);
+            picker_ui(f, picker, boader_style, select_style);
+        })?;
+
+        if let Event::Key(KeyEvent { code, modifiers }) = event::read()? {
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+            match code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Enter => return Ok(picker.selected_index().map(PickerEvent::Select)),
+                KeyCode::Down | KeyCode::Tab => picker.select_next(),
+                KeyCode::Up | KeyCode::BackTab => picker.select_previous(),
+                KeyCode::Char(c) if ctrl && actions.contains(&c) => {
+                    if let Some(i) = picker.selected_index() {
+                        return Ok(Some(PickerEvent::Action(c, i)));
+                    }
+                }
+                KeyCode::Char('n' | 'j') if ctrl => picker.select_next(),
+                KeyCode::Char('p' | 'k') if ctrl => picker.select_previous(),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Char(c) => picker.push_filter(c),
+                KeyCode::Backspace => picker.pop_filter(),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// The start of the selected file, or the names in the selected directory. It is
+// read on a thread so a big file or a slow remote host doesn't hold up moving
+// the selection.
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use crate::vfs::Vfs;
+
+// only this much of a file is read
+const PREVIEW_BYTES: u64 = 64 * 1024;
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Default)]
+pub struct Previewer {
+    // the item shown or being read
+    path: Option<PathBuf>,
+    lines: Vec<String>,
+    pending: Option<Receiver<Vec<String>>>,
+}
+
+impl Previewer {
+    // Picks up what was read and starts reading `path` when the selection
+    // moved. Cheap enough to call every frame.
+    pub fn update(&mut self, path: Option<&Path>, is_dir: bool, vfs: Arc<dyn Vfs>) {
+        if let Some(rx) = &self.pending {
+            match rx.try_recv() {
+                Ok(lines) => {
+                    self.lines = lines;
+                    self.pending = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+        if self.path.as_deref() == path {
+            return;
+        }
+        self.path = path.map(Path::to_path_buf);
+        self.lines = Vec::new();
+        // what is still being read for the old selection is dropped
+        self.pending = None;
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => return,
+        };
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let lines = match is_dir {
+                true => dir_lines(&*vfs, &path),
+                false => file_lines(&*vfs, &path),
+            };
+            let _ = tx.send(lines);
+        });
+        self.pending = Some(rx);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+fn file_lines(vfs: &dyn Vfs, path: &Path) -> Vec<String> {
+    let mut data = Vec::new();
+    let res = vfs
+        .open(path)
+        .and_then(|file| file.take(PREVIEW_BYTES).read_to_end(&mut data));
+    match res {
+        Ok(_) => preview_lines(&data),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+// Directories first, with a slash after them
+fn dir_lines(vfs: &dyn Vfs, path: &Path) -> Vec<String> {
+    let (items, errors) = vfs.read_dir(path);
+    let mut names: Vec<(bool, String)> = items
+        .iter()
+        .map(|item| (!item.kinds().is_dir(), item.name()))
+        .collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|(is_file, name)| match is_file {
+            true => name,
+            false => format!("{}/", name),
+        })
+        .chain(errors)
+        .collect()
+}
+
+// Text with tabs expanded and other control characters shown as `?`, so they
+// can't move the cursor. A NUL byte means it isn't text.
+pub fn preview_lines(data: &[u8]) -> Vec<String> {
+    if data.contains(&0) {
+        return vec!["(binary)".to_string()];
+    }
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(|line| {
+            let mut out = String::with_capacity(line.len());
+            for c in line.chars() {
+                match c {
+                    '\t' => {
+                        let width = TAB_WIDTH - out.chars().count() % TAB_WIDTH;
+                        out.extend(std::iter::repeat_n(' ', width));
+                    }
+                    c if c.is_control() => out.push('?'),
+                    c => out.push(c),
+                }
+            }
+            out
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{preview_lines, Previewer};
+    use crate::vfs::{MemFs, Vfs};
+
+    #[test]
+    fn text_is_made_safe_to_draw() {
+        assert_eq!(
+            preview_lines(b"fn main() {\r\n\tx\x1b[2J\n}"),
+            ["fn main() {", "    x?[2J", "}"]
+        );
+        assert_eq!(preview_lines(b"ab\tc"), ["ab  c"]);
+        assert_eq!(preview_lines(b"\x7fELF\0\0"), ["(binary)"]);
+    }
+
+    #[test]
+    fn selection_is_read_in_the_background() {
+        let fs = Arc::new(MemFs::new(Path::new("/mem")));
+        fs.write(Path::new("/mem/a.txt"), b"one\ntwo").unwrap();
+        fs.create_dir(Path::new("/mem/sub")).unwrap();
+        let mut previewer = Previewer::default();
+        let mut wait_for = |path: &str, is_dir: bool| {
+            let start = Instant::now();
+            loop {
+                previewer.update(Some(Path::new(path)), is_dir, fs.clone());
+                if !previewer.lines().is_empty() || start.elapsed() > Duration::from_secs(5) {
+                    return previewer.lines().to_vec();
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+        assert_eq!(wait_for("/mem/a.txt", false), ["one", "two"]);
+        assert_eq!(wait_for("/mem", true), ["sub/", "a.txt"]);
+    }
+}
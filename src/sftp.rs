@@ -0,0 +1,419 @@
+// Directories on another machine over SFTP, opened from
+// `sftp://user@host:port/path`. Paths on the host are shown below
+// `sftp://user@host`, and one connection per user, host and port is shared by
+// every tab showing it. Calls block until the host answers, and a connection
+// that was lost is noted so the next use connects again.
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+use ssh2::{
+    CheckResult, ErrorCode, FileStat, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp,
+};
+
+use crate::file_item_list::file_item::FileItem;
+use crate::file_item_list::{Kinds, LinkTarget};
+use crate::path_process::get_home_directory_path;
+use crate::vfs::{not_found, Meta, Vfs};
+
+pub const SCHEME: &str = "sftp://";
+const DEFAULT_PORT: u16 = 22;
+// for connecting and for every call after it
+const TIMEOUT: Duration = Duration::from_secs(10);
+// tried in this order when no key is configured
+const DEFAULT_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SftpUrl {
+    user: String,
+    host: String,
+    port: u16,
+    // None for the home directory
+    path: Option<PathBuf>,
+}
+
+impl SftpUrl {
+    // The user is $USER when it is left out
+    pub fn parse(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix(SCHEME)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], Some(PathBuf::from(&rest[i..]))),
+            None => (rest, None),
+        };
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (std::env::var("USER").ok()?, authority),
+        };
+        // an IPv6 address is written in brackets
+        let (host, port) = match host_port.strip_prefix('[') {
+            Some(rest) => {
+                let (host, after) = rest.split_once(']')?;
+                (host, after.strip_prefix(':'))
+            }
+            None => match host_port.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None => DEFAULT_PORT,
+        };
+        if user.is_empty() || host.is_empty() {
+            return None;
+        }
+        Some(SftpUrl {
+            user,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    // "sftp://user@host", followed by the port when it isn't 22
+    pub fn root(&self) -> PathBuf {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        let root = match self.port {
+            DEFAULT_PORT => format!("{}{}@{}", SCHEME, self.user, host),
+            port => format!("{}{}@{}:{}", SCHEME, self.user, host, port),
+        };
+        PathBuf::from(root)
+    }
+}
+
+// The `sftp://user@host` a path is below, however its slashes are spelled
+pub fn host_root(path: &Path) -> Option<PathBuf> {
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(scheme)), Some(Component::Normal(host)))
+            if scheme == SCHEME.trim_end_matches('/') =>
+        {
+            Some(Path::new(SCHEME).join(host))
+        }
+        _ => None,
+    }
+}
+
+// The root of a host has nothing above it
+pub fn is_host_root(path: &Path) -> bool {
+    host_root(path).is_some_and(|root| root == path)
+}
+
+pub struct SftpFs {
+    root: PathBuf,
+    // the shown path of the directory a login starts in
+    home: PathBuf,
+    // the sftp channel needs the session to stay open
+    _session: Session,
+    sftp: Sftp,
+    // a call failed on the connection rather than on the host's files
+    broken: AtomicBool,
+}
+
+impl fmt::Debug for SftpFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SftpFs")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SftpFs {
+    // Checks the host against ~/.ssh/known_hosts, then logs in with ssh-agent,
+    // then with `key` or the usual keys in ~/.ssh
+    pub fn connect(url: &SftpUrl, key: Option<&Path>) -> Result<Self, String> {
+        let fail = |e: &dyn fmt::Display| format!("{}: {}", url.host, e);
+        let addr = (url.host.as_str(), url.port)
+            .to_socket_addrs()
+            .map_err(|e| fail(&e))?
+            .next()
+            .ok_or_else(|| fail(&"no address"))?;
+        let tcp = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| fail(&e))?;
+        let mut session = Session::new().map_err(|e| fail(&e))?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(TIMEOUT.as_millis() as u32);
+        session.handshake().map_err(|e| fail(&e))?;
+        check_host_key(&session, url).map_err(|e| fail(&e))?;
+        authenticate(&session, &url.user, key).map_err(|e| fail(&e))?;
+        let sftp = session.sftp().map_err(|e| fail(&e))?;
+        let mut fs = SftpFs {
+            root: url.root(),
+            home: PathBuf::new(),
+            _session: session,
+            sftp,
+            broken: AtomicBool::new(false),
+        };
+        let home = fs.sftp.realpath(Path::new(".")).map_err(|e| fail(&e))?;
+        fs.home = fs.shown_path(&home);
+        Ok(fs)
+    }
+
+    // The connection is gone or timed out, a new one is needed
+    pub fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::Relaxed)
+    }
+
+    // Errors of the session, unlike those of SFTP, mean the connection is lost
+    fn check<T>(&self, res: Result<T, ssh2::Error>) -> io::Result<T> {
+        res.map_err(|e| {
+            if matches!(e.code(), ErrorCode::Session(_)) {
+                self.broken.store(true, Ordering::Relaxed);
+            }
+            e.into()
+        })
+    }
+
+    // Where `url` is shown, the home directory when it has no path
+    pub fn shown_path_of(&self, url: &SftpUrl) -> PathBuf {
+        match &url.path {
+            Some(path) => self.shown_path(path),
+            None => self.home.clone(),
+        }
+    }
+
+    fn shown_path(&self, remote: &Path) -> PathBuf {
+        self.root.join(remote.strip_prefix("/").unwrap_or(remote))
+    }
+
+    fn remote_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let rel = path.strip_prefix(&self.root).map_err(|_| not_found(path))?;
+        Ok(Path::new("/").join(rel))
+    }
+
+    fn item(&self, remote: &Path, stat: &FileStat) -> FileItem {
+        let path = self.shown_path(remote);
+        let meta = meta_of(stat);
+        if !stat.file_type().is_symlink() {
+            return FileItem::plain(path, meta, stat.is_dir());
+        }
+        let target = self.check(self.sftp.readlink(remote)).unwrap_or_default();
+        // an absolute target is on the host too
+        let target = match target.is_absolute() {
+            true => self.shown_path(&target),
+            false => target,
+        };
+        let followed = self.check(self.sftp.stat(remote));
+        let target = LinkTarget::new(
+            target,
+            followed.as_ref().is_ok_and(|stat| stat.is_dir()),
+            followed.is_err(),
+        );
+        let kinds = Kinds::Symlink(Kinds::is_hidden(&path), target);
+        FileItem::new(path, meta, kinds)
+    }
+
+    fn remove_tree(&self, remote: &Path) -> io::Result<()> {
+        for (child, stat) in self.check(self.sftp.readdir(remote))? {
+            match stat.is_dir() {
+                true => self.remove_tree(&child)?,
+                false => self.check(self.sftp.unlink(&child))?,
+            }
+        }
+        self.check(self.sftp.rmdir(remote))
+    }
+}
+
+fn check_host_key(session: &Session, url: &SftpUrl) -> Result<(), String> {
+    let (key, _) = session.host_key().ok_or("no host key")?;
+    let mut known = session.known_hosts().map_err(|e| e.to_string())?;
+    if let Some(home) = get_home_directory_path() {
+        // a missing file knows no hosts
+        let _ = known.read_file(
+            &home.join(".ssh").join("known_hosts"),
+            KnownHostFileKind::OpenSSH,
+        );
+    }
+    match known.check_port(&url.host, url.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err("host key does not match ~/.ssh/known_hosts".to_string()),
+        CheckResult::NotFound => {
+            Err("not in ~/.ssh/known_hosts, connect once with ssh to add it".to_string())
+        }
+        CheckResult::Failure => Err("could not check the host key".to_string()),
+    }
+}
+
+fn authenticate(session: &Session, user: &str, key: Option<&Path>) -> Result<(), String> {
+    // fails without an agent or when it has no key the host takes
+    if session.userauth_agent(user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+    let keys: Vec<PathBuf> = match key {
+        Some(key) => vec![key.to_path_buf()],
+        None => get_home_directory_path()
+            .map(|home| {
+                DEFAULT_KEYS
+                    .iter()
+                    .map(|name| home.join(".ssh").join(name))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    for key in keys.iter().filter(|key| key.is_file()) {
+        if session.userauth_pubkey_file(user, None, key, None).is_ok() && session.authenticated() {
+            return Ok(());
+        }
+    }
+    Err(format!(
+        "{} was refused with ssh-agent and the keys {}",
+        user,
+        keys.iter()
+            .map(|key| key.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+// uid and gid are left out, they would be looked up in the local users
+fn meta_of(stat: &FileStat) -> Meta {
+    let time = |secs: Option<u64>| secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    Meta {
+        len: stat.size.unwrap_or(0),
+        mode: stat.perm,
+        modified: time(stat.mtime),
+        accessed: time(stat.atime),
+        ..Meta::default()
+    }
+}
+
+impl Vfs for SftpFs {
+    fn read_dir(&self, dir: &Path) -> (Vec<FileItem>, Vec<String>) {
+        let entries = self
+            .remote_path(dir)
+            .and_then(|remote| self.check(self.sftp.readdir(&remote)));
+        match entries {
+            Ok(entries) => {
+                let items = entries
+                    .iter()
+                    .map(|(remote, stat)| self.item(remote, stat))
+                    .collect();
+                (items, Vec::new())
+            }
+            Err(e) => (Vec::new(), vec![format!("{}: {}", dir.display(), e)]),
+        }
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileItem> {
+        let remote = self.remote_path(path)?;
+        let stat = self.check(self.sftp.lstat(&remote))?;
+        Ok(self.item(&remote, &stat))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let remote = self.remote_path(path)?;
+        Ok(Box::new(self.check(self.sftp.open(&remote))?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let remote = self.remote_path(path)?;
+        let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE;
+        let file = self.check(self.sftp.open_mode(&remote, flags, 0o644, OpenType::File))?;
+        Ok(Box::new(file))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let remote = self.remote_path(path)?;
+        self.check(self.sftp.mkdir(&remote, 0o755))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (self.remote_path(from)?, self.remote_path(to)?);
+        self.check(self.sftp.rename(&from, &to, None))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let remote = self.remote_path(path)?;
+        match self.check(self.sftp.lstat(&remote))?.is_dir() {
+            true => self.remove_tree(&remote),
+            false => self.check(self.sftp.unlink(&remote)),
+        }
+    }
+
+    // Asking the host every half second would hold up a slow link, so changes
+    // made there show up when the listing is read again
+    fn watch_stamp(&self, _dir: &Path) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use super::{host_root, is_host_root, SftpFs, SftpUrl};
+    use crate::vfs::Vfs;
+
+    #[test]
+    fn urls_are_parsed() {
+        let url = SftpUrl::parse("sftp://deploy@build-1:2222/srv/out").unwrap();
+        assert_eq!(url.root(), PathBuf::from("sftp://deploy@build-1:2222"));
+        assert_eq!(url.path.as_deref(), Some(Path::new("/srv/out")));
+
+        let url = SftpUrl::parse("sftp://me@[::1]").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 22));
+        assert_eq!(url.root(), PathBuf::from("sftp://me@[::1]"));
+        assert_eq!(url.path, None);
+
+        assert_eq!(SftpUrl::parse("sftp://me@host:x/"), None);
+        assert_eq!(SftpUrl::parse("ftp://me@host/"), None);
+
+        let root = Some(PathBuf::from("sftp://me@host"));
+        assert_eq!(host_root(Path::new("sftp://me@host/srv/out")), root);
+        // as a path put together from its components
+        assert_eq!(host_root(Path::new("sftp:/me@host/srv")), root);
+        assert_eq!(host_root(Path::new("/srv/sftp:/me@host")), None);
+        assert!(is_host_root(Path::new("sftp://me@host/")));
+        assert!(!is_host_root(Path::new("sftp://me@host/srv")));
+    }
+
+    // Needs an sshd that takes a key from ssh-agent or ~/.ssh and is in
+    // ~/.ssh/known_hosts:
+    // STFM_SFTP_TEST=sftp://$USER@localhost/tmp cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn remote_directory_works_like_a_disk() {
+        let url = std::env::var("STFM_SFTP_TEST").unwrap();
+        let url = SftpUrl::parse(&url).unwrap();
+        let fs = SftpFs::connect(&url, None).unwrap();
+        let dir = fs
+            .shown_path_of(&url)
+            .join(format!("stfm-sftp-test-{}", std::process::id()));
+        fs.create_dir(&dir).unwrap();
+        fs.create(&dir.join("a.txt"))
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        assert!(fs.create(&dir.join("a.txt")).is_err());
+        fs.create(&dir.join("b.txt"))
+            .unwrap()
+            .write_all(b"world")
+            .unwrap();
+
+        let (items, errors) = fs.read_dir(&dir);
+        let mut names: Vec<String> = items.iter().map(|item| item.name()).collect();
+        names.sort();
+        assert_eq!(
+            (names, errors),
+            (vec!["a.txt".to_string(), "b.txt".to_string()], vec![])
+        );
+        assert_eq!(fs.read(&dir.join("b.txt")).unwrap(), b"world");
+
+        fs.rename(&dir.join("a.txt"), &dir.join("c.txt")).unwrap();
+        assert_eq!(fs.stat(&dir.join("c.txt")).unwrap().meta().unwrap().len, 5);
+        fs.remove(&dir).unwrap();
+        assert!(fs.stat(&dir).is_err());
+    }
+}
@@ -1,33 +1,88 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::file_item_list::directory_item::Directory;
 use crate::file_item_list::file_item::FileItem;
-use crate::path_process::{make_info_files_from_dirpath, pathbuf_to_string_name};
+use crate::vfs::Vfs;
 use tui::widgets::TableState;
 
+// Directories always come first, then items are ordered by the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+
+impl SortKey {
+    pub const NAMES: [&'static str; 4] = ["name", "size", "modified", "extension"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            "modified" | "mtime" => Some(Self::Modified),
+            "extension" | "ext" => Some(Self::Extension),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StatefulDirectory {
     directory: Directory,
+    // where the items are read from
+    vfs: Arc<dyn Vfs>,
+    // every item, and the ones that are shown
+    all_items: Vec<FileItem>,
     file_items: Vec<FileItem>,
+    // dotfiles and ignored items, left out unless `show_hidden`
+    hideable: HashSet<PathBuf>,
+    show_hidden: bool,
     length: usize,
     state: TableState,
+    marked: HashSet<PathBuf>,
+    // what couldn't be read while listing
+    read_errors: Vec<String>,
+    // the watch stamp of the directory when it was read
+    stamp: Option<u64>,
 }
 
 impl StatefulDirectory {
-    pub fn new(dir_path: PathBuf) -> StatefulDirectory {
-        let file_items = make_info_files_from_dirpath(&dir_path);
+    pub fn new(dir_path: PathBuf, vfs: Arc<dyn Vfs>) -> StatefulDirectory {
+        // taken first so a change made while listing is noticed next time
+        let stamp = vfs.watch_stamp(&dir_path);
+        let (file_items, read_errors) = vfs.read_dir(&dir_path);
+        let mut dir = Self::from_items(dir_path, vfs, file_items, read_errors);
+        dir.stamp = stamp;
+        dir
+    }
+
+    // A listing of items that were already read
+    pub fn from_items(
+        dir_path: PathBuf,
+        vfs: Arc<dyn Vfs>,
+        file_items: Vec<FileItem>,
+        read_errors: Vec<String>,
+    ) -> StatefulDirectory {
         StatefulDirectory {
             directory: Directory::new(dir_path),
+            vfs,
             state: TableState::default(),
             length: file_items.len(),
+            all_items: file_items.clone(),
             file_items,
+            hideable: HashSet::new(),
+            show_hidden: true,
+            marked: HashSet::new(),
+            read_errors,
+            stamp: None,
         }
     }
 
-    pub fn directory(&self) -> &Directory {
-        &self.directory
-    }
-
     pub fn dir_path(&self) -> &Path {
         self.directory.pathbuf()
     }
@@ -36,9 +91,18 @@ impl StatefulDirectory {
         self.directory.parent()
     }
 
-    pub fn crr_dir_name(&self) -> String {
-        let path = self.directory.pathbuf();
-        pathbuf_to_string_name(path)
+    pub fn is_read_only(&self) -> bool {
+        self.vfs.is_read_only()
+    }
+
+    // Something was added to or removed from the directory since it was read
+    pub fn is_stale(&self) -> bool {
+        let now = self.vfs.watch_stamp(self.dir_path());
+        now.is_some() && now != self.stamp
+    }
+
+    pub fn read_errors(&self) -> &[String] {
+        &self.read_errors
     }
 
     pub fn file_items_vec(&self) -> &Vec<FileItem> {
@@ -110,12 +174,183 @@ impl StatefulDirectory {
         self.state.select(Some(i));
     }
 
-    pub fn is_selected(&self) -> bool {
-        self.state.selected().is_some()
+    pub fn toggle_mark_selected(&mut self) {
+        if let Some(item) = self.selecting_file_item() {
+            let path = item.path().to_path_buf();
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+        }
     }
 
-    pub fn sort_by_kinds(&mut self) {
+    pub fn is_marked(&self, path: &Path) -> bool {
+        self.marked.contains(path)
+    }
+
+    // In the order they are listed
+    pub fn marked_paths(&self) -> Vec<PathBuf> {
         self.file_items
-            .sort_by(|a, b| b.kinds().partial_cmp(&a.kinds()).unwrap());
+            .iter()
+            .filter(|item| self.marked.contains(item.path()))
+            .map(|item| item.path().to_path_buf())
+            .collect()
+    }
+
+    pub fn marked(&self) -> &HashSet<PathBuf> {
+        &self.marked
+    }
+
+    // Marks every item from `from` to `to`, in either order, on top of `base`
+    pub fn mark_range(&mut self, base: &HashSet<PathBuf>, from: usize, to: usize) {
+        let (start, end) = (from.min(to), from.max(to));
+        self.marked = base.clone();
+        for item in self.file_items.iter().take(end + 1).skip(start) {
+            self.marked.insert(item.path().to_path_buf());
+        }
+    }
+
+    // The marks of another listing of the same directory, for the items still in it
+    pub fn keep_marks(&mut self, marks: &HashSet<PathBuf>) {
+        self.marked = self
+            .file_items
+            .iter()
+            .map(|item| item.path().to_path_buf())
+            .filter(|path| marks.contains(path))
+            .collect();
+    }
+
+    // The same row, or the last one if the list got shorter
+    pub fn select_nearest(&mut self, i: Option<usize>) {
+        match (i, self.length) {
+            (_, 0) => self.state.select(None),
+            (Some(i), len) => self.state.select(Some(i.min(len - 1))),
+            (None, _) => self.select_top(),
+        }
+    }
+
+    pub fn all_items(&self) -> &[FileItem] {
+        &self.all_items
+    }
+
+    pub fn set_hideable(&mut self, paths: Vec<PathBuf>) {
+        self.hideable = paths.into_iter().collect();
+        self.filter_items();
+    }
+
+    pub fn set_show_hidden(&mut self, show: bool) {
+        if self.show_hidden != show {
+            self.show_hidden = show;
+            self.filter_items();
+        }
+    }
+
+    // How many items are left out
+    pub fn hidden_count(&self) -> usize {
+        self.all_items.len() - self.file_items.len()
+    }
+
+    // The selected item stays selected if it is still shown
+    fn filter_items(&mut self) {
+        let selected = self
+            .selecting_file_item()
+            .map(|item| item.path().to_path_buf());
+        let show_hidden = self.show_hidden;
+        let hideable = &self.hideable;
+        self.file_items = self
+            .all_items
+            .iter()
+            .filter(|item| show_hidden || !hideable.contains(item.path()))
+            .cloned()
+            .collect();
+        self.length = self.file_items.len();
+        let pos = self
+            .file_items
+            .iter()
+            .position(|item| Some(item.path()) == selected.as_deref());
+        match pos {
+            Some(_) => self.state.select(pos),
+            None => self.select_nearest(self.state.selected()),
+        }
+    }
+
+    pub fn sort_by_key(&mut self, key: SortKey) {
+        let is_dir = |item: &FileItem| item.kinds().is_dir();
+        let size = |item: &FileItem| item.meta().map(|meta| meta.len);
+        let by_name =
+            |a: &FileItem, b: &FileItem| a.name().to_lowercase().cmp(&b.name().to_lowercase());
+        self.all_items.sort_by(|a, b| {
+            let order = match key {
+                SortKey::Name => by_name(a, b),
+                // larger first
+                SortKey::Size => size(b).cmp(&size(a)),
+                // newer first
+                SortKey::Modified => b.get_modified_time().cmp(&a.get_modified_time()),
+                SortKey::Extension => a.path().extension().cmp(&b.path().extension()),
+            };
+            is_dir(b)
+                .cmp(&is_dir(a))
+                .then(order)
+                .then_with(|| by_name(a, b))
+        });
+        self.filter_items();
+    }
+
+    // Largest first, directories mixed in with files, like ncdu. `usage` is
+    // None for a directory that isn't added up yet. Cheap when nothing moved.
+    pub fn sort_by_usage(&mut self, usage: impl Fn(&FileItem) -> Option<u64>) {
+        let order = |a: &FileItem, b: &FileItem| {
+            usage(b)
+                .unwrap_or(0)
+                .cmp(&usage(a).unwrap_or(0))
+                .then_with(|| a.name().to_lowercase().cmp(&b.name().to_lowercase()))
+        };
+        if self.all_items.is_sorted_by(|a, b| order(a, b).is_le()) {
+            return;
+        }
+        self.all_items.sort_by(order);
+        self.filter_items();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::{SortKey, StatefulDirectory};
+    use crate::vfs::{MemFs, Vfs};
+
+    #[test]
+    fn listing_in_memory_is_sorted_and_navigated() {
+        let fs = Arc::new(MemFs::new(Path::new("/mem")));
+        fs.write(Path::new("/mem/small"), b"1").unwrap();
+        fs.write(Path::new("/mem/big"), b"1234").unwrap();
+        fs.create_dir(Path::new("/mem/dir")).unwrap();
+
+        let mut dir = StatefulDirectory::new(Path::new("/mem").to_path_buf(), fs.clone());
+        dir.sort_by_key(SortKey::Size);
+        dir.select_top();
+        let selected = |dir: &StatefulDirectory| dir.selecting_file_item().unwrap().name();
+        assert_eq!(selected(&dir), "dir");
+        dir.select_next();
+        assert_eq!(selected(&dir), "big");
+        dir.select_previous();
+        dir.select_previous();
+        assert_eq!(selected(&dir), "small");
+
+        // the selection stays on the item
+        dir.sort_by_usage(|item| match item.name().as_str() {
+            "dir" => Some(10),
+            _ => item.meta().map(|meta| meta.len),
+        });
+        assert_eq!(selected(&dir), "small");
+        dir.select_top();
+        assert_eq!(selected(&dir), "dir");
+        dir.select_bottom();
+        assert_eq!(selected(&dir), "small");
+
+        assert!(!dir.is_stale());
+        fs.remove(Path::new("/mem/big")).unwrap();
+        assert!(dir.is_stale());
     }
 }
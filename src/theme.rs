@@ -0,0 +1,633 @@
+// Colours and styles of the UI. A theme is one of the built-in ones or a
+// `themes/<name>.ron` file next to the config, and the `theme` field of the
+// config is layered over it.
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+use crate::load_config::{parse_ron, some, ConfigError};
+use crate::path_process::{get_config_directory_path, get_system_config_directory_paths};
+
+pub const THEMES_DIR_NAME: &str = "themes";
+
+// The same list of names is used by Colors and by the colour form of a slot
+macro_rules! named_colors {
+    ($($name:ident),* $(,)?) => {
+        // A colour in the config: a name, Rgb(r, g, b), Indexed(n) from the
+        // 256 colour palette, or Hex("#rrggbb")
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+        pub enum Colors {
+            $($name,)*
+            Rgb(u8, u8, u8),
+            Indexed(u8),
+            Hex(#[serde(deserialize_with = "hex")] (u8, u8, u8)),
+        }
+
+        impl Colors {
+            fn to_color(self) -> Color {
+                match self {
+                    $(Colors::$name => Color::$name,)*
+                    Colors::Rgb(r, g, b) | Colors::Hex((r, g, b)) => Color::Rgb(r, g, b),
+                    Colors::Indexed(i) => Color::Indexed(i),
+                }
+            }
+        }
+
+        // `directory: Blue` or `directory: Style(fg: Blue, bold: true)`
+        #[derive(Deserialize)]
+        #[serde(rename = "Colors")]
+        enum SlotRepr {
+            $($name,)*
+            Rgb(u8, u8, u8),
+            Indexed(u8),
+            Hex(#[serde(deserialize_with = "hex")] (u8, u8, u8)),
+            Style {
+                #[serde(default, deserialize_with = "some")]
+                fg: Option<Colors>,
+                #[serde(default, deserialize_with = "some")]
+                bg: Option<Colors>,
+                #[serde(default)]
+                bold: bool,
+                #[serde(default)]
+                italic: bool,
+                #[serde(default)]
+                underline: bool,
+                #[serde(default)]
+                reverse: bool,
+                #[serde(default)]
+                dim: bool,
+            },
+        }
+
+        impl From<SlotRepr> for SlotStyle {
+            fn from(repr: SlotRepr) -> Self {
+                let color = match repr {
+                    $(SlotRepr::$name => Colors::$name,)*
+                    SlotRepr::Rgb(r, g, b) => Colors::Rgb(r, g, b),
+                    SlotRepr::Indexed(i) => Colors::Indexed(i),
+                    SlotRepr::Hex(rgb) => Colors::Hex(rgb),
+                    SlotRepr::Style { fg, bg, bold, italic, underline, reverse, dim } => {
+                        let flags = [
+                            (bold, Modifier::BOLD),
+                            (italic, Modifier::ITALIC),
+                            (underline, Modifier::UNDERLINED),
+                            (reverse, Modifier::REVERSED),
+                            (dim, Modifier::DIM),
+                        ];
+                        let modifiers = flags
+                            .into_iter()
+                            .filter(|(on, _)| *on)
+                            .fold(Modifier::empty(), |m, (_, flag)| m | flag);
+                        return SlotStyle::Style { fg, bg, modifiers };
+                    }
+                };
+                SlotStyle::Color(color)
+            }
+        }
+    };
+}
+
+named_colors!(
+    Reset,
+    White,
+    Black,
+    Yellow,
+    Blue,
+    Green,
+    Red,
+    Gray,
+    DarkGray,
+    Cyan,
+    Magenta,
+    LightBlue,
+    LightRed,
+    LightGreen,
+    LightMagenta,
+    LightYellow,
+    LightCyan,
+);
+
+fn hex<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<(u8, u8, u8), D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_hex(&s).ok_or_else(|| {
+        serde::de::Error::custom(format!("\"{}\" is not a #rrggbb or #rgb colour", s))
+    })
+}
+
+fn parse_hex(s: &str) -> Option<(u8, u8, u8)> {
+    let digits = s.strip_prefix('#').unwrap_or(s);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, len: usize| u8::from_str_radix(&digits[i * len..(i + 1) * len], 16);
+    match digits.len() {
+        6 => Some((
+            channel(0, 2).ok()?,
+            channel(1, 2).ok()?,
+            channel(2, 2).ok()?,
+        )),
+        // #abc is #aabbcc
+        3 => {
+            let short = |i| channel(i, 1).map(|c| c * 17).ok();
+            Some((short(0)?, short(1)?, short(2)?))
+        }
+        _ => None,
+    }
+}
+
+// A plain colour is used the way the slot uses colours: as the background for
+// `background` and as the foreground everywhere else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(from = "SlotRepr")]
+pub enum SlotStyle {
+    Color(Colors),
+    Style {
+        fg: Option<Colors>,
+        bg: Option<Colors>,
+        modifiers: Modifier,
+    },
+}
+
+impl SlotStyle {
+    fn style(&self, color_is_bg: bool) -> Style {
+        match *self {
+            SlotStyle::Color(c) if color_is_bg => Style::default().bg(c.to_color()),
+            SlotStyle::Color(c) => Style::default().fg(c.to_color()),
+            SlotStyle::Style { fg, bg, modifiers } => {
+                let mut style = Style::default().add_modifier(modifiers);
+                if let Some(fg) = fg {
+                    style = style.fg(fg.to_color());
+                }
+                if let Some(bg) = bg {
+                    style = style.bg(bg.to_color());
+                }
+                style
+            }
+        }
+    }
+}
+
+impl From<Colors> for SlotStyle {
+    fn from(c: Colors) -> Self {
+        SlotStyle::Color(c)
+    }
+}
+
+// How many colours the terminal can show. Colours a terminal can't show are
+// replaced by the nearest one it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum ColorSupport {
+    // guessed from $COLORTERM and $TERM
+    #[default]
+    Auto,
+    TrueColor,
+    Palette256,
+    Basic16,
+}
+
+impl ColorSupport {
+    pub fn resolve(self) -> Self {
+        if self != ColorSupport::Auto {
+            return self;
+        }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") || cfg!(windows) {
+            ColorSupport::Palette256
+        } else {
+            ColorSupport::Basic16
+        }
+    }
+
+    fn downgrade(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::Palette256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_256(r, g, b)),
+            (ColorSupport::Basic16, Color::Rgb(r, g, b)) => nearest_basic(r, g, b),
+            (ColorSupport::Basic16, Color::Indexed(i)) => {
+                let (r, g, b) = indexed_to_rgb(i);
+                nearest_basic(r, g, b)
+            }
+            _ => color,
+        }
+    }
+
+    fn apply(self, style: Style) -> Style {
+        Style {
+            fg: style.fg.map(|c| self.downgrade(c)),
+            bg: style.bg.map(|c| self.downgrade(c)),
+            ..style
+        }
+    }
+}
+
+// xterm's defaults, in the order of the palette
+const BASIC_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn indexed_to_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => BASIC_COLORS[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + (i - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+// The nearest colour of the 6x6x6 cube and the gray ramp
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    (16..=255)
+        .min_by_key(|&i| distance(indexed_to_rgb(i), (r, g, b)))
+        .unwrap_or(16)
+}
+
+fn nearest_basic(r: u8, g: u8, b: u8) -> Color {
+    BASIC_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(c, _)| *c)
+        .unwrap_or(Color::Reset)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingTheme {
+    background: SlotStyle,
+    boader: SlotStyle,
+    directory: SlotStyle,
+    file_item: SlotStyle,
+    select: SlotStyle,
+    header: SlotStyle,
+    // (bg, fg): normal, input, stacker
+    command: Vec<(Colors, Colors)>,
+    // the slots below fall back to a default when a theme leaves them out
+    #[serde(default, deserialize_with = "some")]
+    marked: Option<SlotStyle>,
+    // dotfiles, when they are shown
+    #[serde(default, deserialize_with = "some")]
+    hidden: Option<SlotStyle>,
+    #[serde(default, deserialize_with = "some")]
+    symlink: Option<SlotStyle>,
+    #[serde(default, deserialize_with = "some")]
+    error: Option<SlotStyle>,
+    #[serde(default, deserialize_with = "some")]
+    preview: Option<SlotStyle>,
+    // pickers, prompts and the which-key popup
+    #[serde(default, deserialize_with = "some")]
+    popup: Option<SlotStyle>,
+    #[serde(skip)]
+    color_support: ColorSupport,
+}
+
+impl SettingTheme {
+    pub const NAMES: [&'static str; 3] = ["dark", "light", "dark_blue"];
+
+    fn builtin(name: &str) -> Option<SettingTheme> {
+        match name {
+            "dark" => Some(Self::dark_theme()),
+            "light" => Some(Self::light_theme()),
+            "dark_blue" => Some(Self::dark_blue_theme()),
+            _ => None,
+        }
+    }
+
+    // A built-in theme, or themes/<name>.ron in the config directories layered
+    // over the dark theme
+    pub fn from_name(name: &str) -> Result<SettingTheme, ConfigError> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let path = theme_file_paths(name)
+            .into_iter()
+            .find(|path| path.is_file())
+            .ok_or_else(|| ConfigError::UnknownTheme(name.to_string()))?;
+        let s = std::fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        let partial: PartialSettingTheme =
+            parse_ron(&s).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        let mut theme = Self::dark_theme();
+        theme.layer(partial);
+        Ok(theme)
+    }
+
+    fn new(
+        [background, header, boader, directory, file_item, select]: [SlotStyle; 6],
+        command: Vec<(Colors, Colors)>,
+    ) -> SettingTheme {
+        SettingTheme {
+            background,
+            boader,
+            directory,
+            file_item,
+            select,
+            header,
+            command,
+            marked: None,
+            hidden: None,
+            symlink: None,
+            error: None,
+            preview: None,
+            popup: None,
+            color_support: ColorSupport::TrueColor,
+        }
+    }
+
+    pub fn dark_theme() -> SettingTheme {
+        Self::new(
+            [
+                Colors::Black.into(),
+                Colors::Cyan.into(),
+                Colors::White.into(),
+                Colors::Blue.into(),
+                Colors::Gray.into(),
+                Colors::LightMagenta.into(),
+            ],
+            vec![
+                (Colors::Green, Colors::Cyan),
+                (Colors::White, Colors::Blue),
+                (Colors::Magenta, Colors::Red),
+            ],
+        )
+    }
+
+    pub fn light_theme() -> SettingTheme {
+        let mut theme = Self::new(
+            [
+                Colors::White.into(),
+                Colors::Green.into(),
+                Colors::Black.into(),
+                Colors::Blue.into(),
+                Colors::Black.into(),
+                Colors::LightRed.into(),
+            ],
+            vec![
+                (Colors::Blue, Colors::White),
+                (Colors::Green, Colors::White),
+                (Colors::Magenta, Colors::White),
+            ],
+        );
+        // the defaults are meant for a dark background
+        theme.marked = Some(Colors::Magenta.into());
+        theme.hidden = Some(Colors::Gray.into());
+        theme.symlink = Some(Colors::Cyan.into());
+        theme
+    }
+
+    pub fn dark_blue_theme() -> SettingTheme {
+        Self::new(
+            [
+                Colors::Rgb(39, 67, 100).into(),
+                Colors::Green.into(),
+                Colors::Rgb(97, 169, 252).into(),
+                Colors::Blue.into(),
+                Colors::Gray.into(),
+                Colors::Green.into(),
+            ],
+            vec![
+                (Colors::Blue, Colors::Black),
+                (Colors::Green, Colors::Blue),
+                (Colors::Magenta, Colors::Blue),
+            ],
+        )
+    }
+
+    pub fn set_color_support(&mut self, support: ColorSupport) {
+        self.color_support = support.resolve();
+    }
+
+    fn fg(&self, slot: &SlotStyle) -> Style {
+        self.color_support.apply(slot.style(false))
+    }
+
+    fn fg_or(&self, slot: &Option<SlotStyle>, default: SlotStyle) -> Style {
+        self.fg(slot.as_ref().unwrap_or(&default))
+    }
+
+    pub fn file_style(&self) -> Style {
+        self.fg(&self.file_item)
+    }
+
+    pub fn dir_style(&self) -> Style {
+        self.fg(&self.directory)
+    }
+
+    pub fn select_style(&self) -> Style {
+        self.fg(&self.select)
+    }
+
+    pub fn header_style(&self) -> Style {
+        self.fg(&self.header)
+    }
+
+    pub fn boader_style(&self) -> Style {
+        self.fg(&self.boader)
+    }
+
+    pub fn marked_style(&self) -> Style {
+        let default = SlotStyle::Style {
+            fg: Some(Colors::Yellow),
+            bg: None,
+            modifiers: Modifier::BOLD,
+        };
+        self.fg_or(&self.marked, default)
+    }
+
+    pub fn hidden_style(&self) -> Style {
+        self.fg_or(&self.hidden, Colors::DarkGray.into())
+    }
+
+    pub fn symlink_style(&self) -> Style {
+        self.fg_or(&self.symlink, Colors::LightCyan.into())
+    }
+
+    pub fn error_style(&self) -> Style {
+        let default = SlotStyle::Style {
+            fg: Some(Colors::LightRed),
+            bg: None,
+            modifiers: Modifier::BOLD,
+        };
+        self.fg_or(&self.error, default)
+    }
+
+    pub fn preview_style(&self) -> Style {
+        self.fg_or(&self.preview, self.file_item)
+    }
+
+    pub fn popup_style(&self) -> Style {
+        self.fg_or(&self.popup, self.boader)
+    }
+
+    pub fn command_style(&self) -> [Style; 3] {
+        let mut styles: [Style; 3] = [Style::default(); 3];
+        for (i, (bg, fg)) in self.command.iter().take(3).enumerate() {
+            let style = Style::default().bg(bg.to_color()).fg(fg.to_color());
+            styles[i] = self.color_support.apply(style);
+        }
+        styles
+    }
+
+    pub fn background_style(&self) -> Style {
+        self.color_support.apply(self.background.style(true))
+    }
+
+    pub fn layer(&mut self, theme: PartialSettingTheme) {
+        let fields = [
+            (&mut self.background, theme.background),
+            (&mut self.boader, theme.boader),
+            (&mut self.directory, theme.directory),
+            (&mut self.file_item, theme.file_item),
+            (&mut self.select, theme.select),
+            (&mut self.header, theme.header),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        let optional = [
+            (&mut self.marked, theme.marked),
+            (&mut self.hidden, theme.hidden),
+            (&mut self.symlink, theme.symlink),
+            (&mut self.error, theme.error),
+            (&mut self.preview, theme.preview),
+            (&mut self.popup, theme.popup),
+        ];
+        for (field, value) in optional {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        if let Some(command) = theme.command {
+            self.command = command;
+        }
+    }
+}
+
+// Every field is optional so that a config file only has to contain what it changes.
+// Fields that are present replace the lower layer.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename = "SettingTheme", default)]
+pub struct PartialSettingTheme {
+    #[serde(deserialize_with = "some")]
+    background: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    boader: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    directory: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    file_item: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    select: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    header: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    command: Option<Vec<(Colors, Colors)>>,
+    #[serde(deserialize_with = "some")]
+    marked: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    hidden: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    symlink: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    error: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    preview: Option<SlotStyle>,
+    #[serde(deserialize_with = "some")]
+    popup: Option<SlotStyle>,
+}
+
+// The user's themes first, then the system wide ones
+pub fn theme_file_paths(name: &str) -> Vec<PathBuf> {
+    let file = format!("{}.ron", name);
+    let mut dirs: Vec<PathBuf> = get_config_directory_path().into_iter().collect();
+    dirs.extend(get_system_config_directory_paths().into_iter().rev());
+    dirs.into_iter()
+        .map(|dir| dir.join(THEMES_DIR_NAME).join(&file))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use tui::style::{Color, Modifier, Style};
+
+    use super::{
+        parse_hex, rgb_to_256, ColorSupport, PartialSettingTheme, SettingTheme, SlotStyle,
+    };
+    use crate::load_config::parse_ron;
+
+    #[test]
+    fn slots_take_colours_and_styles() {
+        let partial = r##"SettingTheme(
+            directory: Blue,
+            file_item: Indexed(244),
+            select: Style(fg: Hex("#ff8800"), bg: Black, bold: true, underline: true),
+            marked: Rgb(1, 2, 3),
+        )"##;
+        let partial: PartialSettingTheme = parse_ron(partial).unwrap();
+        let mut theme = SettingTheme::dark_theme();
+        theme.layer(partial);
+
+        assert_eq!(theme.dir_style(), Style::default().fg(Color::Blue));
+        assert_eq!(theme.file_style(), Style::default().fg(Color::Indexed(244)));
+        assert_eq!(
+            theme.select_style(),
+            Style::default()
+                .fg(Color::Rgb(255, 136, 0))
+                .bg(Color::Black)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        );
+        assert_eq!(
+            theme.marked_style(),
+            Style::default().fg(Color::Rgb(1, 2, 3))
+        );
+        assert!(parse_ron::<SlotStyle>(r##"Hex("#12345")"##).is_err());
+    }
+
+    #[test]
+    fn colours_fall_back_to_what_the_terminal_has() {
+        assert_eq!(parse_hex("#abc"), Some((0xaa, 0xbb, 0xcc)));
+        assert_eq!(parse_hex("ff0000"), Some((255, 0, 0)));
+        assert_eq!(parse_hex("#ggg"), None);
+
+        assert_eq!(rgb_to_256(255, 0, 0), 196);
+        assert_eq!(rgb_to_256(128, 128, 128), 244);
+
+        let mut theme = SettingTheme::dark_blue_theme();
+        theme.set_color_support(ColorSupport::Basic16);
+        assert_eq!(theme.boader_style(), Style::default().fg(Color::LightBlue));
+        theme.set_color_support(ColorSupport::Palette256);
+        assert!(matches!(
+            theme.background_style().bg,
+            Some(Color::Indexed(_))
+        ));
+    }
+}
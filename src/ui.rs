@@ -3,15 +3,17 @@ use tui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table, Tabs},
+    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, Tabs},
     Frame,
 };
 
 use crate::{
     application::{App, Mode},
-    file_item_list::Kinds,
+    columns::{Column, ColumnKind},
+    file_item_list::file_item::calc_file_item_size,
     load_config::FileItems,
-    path_process::pathbuf_to_string_name,
+    mouse::title_parts,
+    path_process::{escape_os_str, pathbuf_to_string_name},
 };
 
 pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
@@ -37,18 +39,23 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .constraints(main_windows_constrains)
         .split(size);
 
-    let (index, file_items) = {
+    let (index_selected, file_items) = {
         let state = app.peek_selected_statefuldir();
-        (
-            state.state_table().selected().unwrap_or(0),
-            state.file_items_vec().len(),
-        )
+        (state.state_table().selected(), state.file_items_vec().len())
     };
+    let index = index_selected.unwrap_or(0);
 
     let rate = index as f32 / file_items as f32;
+    // keys of an unfinished sequence take the place of the last message
+    let pending = app.crr_keybinds().pending_notation();
+    // then the progress of a running job
+    let job = app.job_summary();
     command_display_ui(
         f,
-        app.command_history(),
+        pending
+            .as_ref()
+            .or(job.as_ref())
+            .or(app.command_history().last()),
         chunks[2],
         app.theme().command_style(),
         app.mode(),
@@ -56,21 +63,38 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     );
 
     // let index = app.tab_index();
-    let tabs = app.dirtab();
+    let tabs: Vec<String> = app
+        .dirtab()
+        .iter()
+        .map(|p| pathbuf_to_string_name(p))
+        .collect();
     let mode = app.mode();
 
-    let header_titles = ["", "", "name", "permission", "size", "date"]
+    let columns: Vec<Column> = app
+        .columns()
         .iter()
-        .map(|h| Cell::from(*h).style(header_style));
-
-    let header_constraints = [
-        Constraint::Length(1),  //  margin
-        Constraint::Length(2),  // file item's icon
-        Constraint::Length(20), // file name
-        Constraint::Length(10), // permission
-        Constraint::Length(10), // size
-        Constraint::Length(10), // date
+        .filter(|c| !c.is_hidden())
+        .cloned()
+        .collect();
+    let date_format = app.date_format().to_string();
+    let date_format = date_format.as_str();
+    // the bars of the disk usage view go in front of the columns
+    let disk_usage = app.disk_usage();
+    let usage_header = disk_usage.then(|| format!("{:<1$}", "usage", USAGE_WIDTH as usize));
+    let header_titles = ["".to_string(), "".to_string()]
+        .into_iter()
+        .chain(usage_header)
+        .chain(columns.iter().map(|c| c.header()))
+        .map(|h| Cell::from(h).style(header_style));
+
+    let mut header_constraints = vec![
+        Constraint::Length(1), //  margin
+        Constraint::Length(2), // file item's icon
     ];
+    if disk_usage {
+        header_constraints.push(Constraint::Length(USAGE_WIDTH));
+    }
+    header_constraints.extend(columns.iter().map(|c| c.constraint()));
 
     let background_window = Block::default().style(background_style);
     f.render_widget(background_window, size);
@@ -98,51 +122,99 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         }
         Mode::Stacker => todo!(),
     }
-    // TODO: Display and hide the header and each element with bool
     let header_cells = Row::new(header_titles).style(header_style).bottom_margin(1);
 
     let file_symbol = app.symbols(&FileItems::File);
     let dir_symbol = app.symbols(&FileItems::Directory);
     let select_symbol = app.symbols(&FileItems::Select);
 
-    let current_dir_path = pathbuf_to_string_name(app.crr_dir_path());
+    // the whole path, each part can be clicked to go up to it
+    let title_parts = title_parts(app.crr_dir_path());
+    let mut current_dir_path: String = title_parts.iter().map(|(text, _)| text.as_str()).collect();
+    if let Some(branch) = app.git_branch_summary() {
+        current_dir_path.push_str(&format!(" ({})", branch));
+    }
+    let hidden_count = app.hidden_count();
+    if hidden_count > 0 {
+        current_dir_path.push_str(&format!(" [{} hidden]", hidden_count));
+    }
     let file_item_iter = app.crr_file_items();
+    let stateful_dir = app.peeking_selected_statefuldir();
+    // what the shown items take up together, the bars are shares of it
+    let usage_total: u64 = file_item_iter
+        .iter()
+        .filter_map(|item| app.usage_of(item))
+        .sum();
+    if disk_usage {
+        let adding_up = file_item_iter
+            .iter()
+            .any(|item| app.is_adding_up(item.path()));
+        current_dir_path.push_str(&format!(
+            " [disk usage {}{}]",
+            calc_file_item_size(usage_total, app.size_units()),
+            if adding_up { ", adding up" } else { "" }
+        ));
+    }
+
+    let marked_style = app.theme().marked_style();
+    let hidden_style = app.theme().hidden_style();
+    let symlink_style = app.theme().symlink_style();
+    let error_style = app.theme().error_style();
 
     let file_items_list = file_item_iter.iter().map(|file_item| {
         let name = file_item.name();
-        let perm = if file_item.get_permission() {
-            format!("{:>4}", "r")
-        } else {
-            format!("{:>4}", "rx")
+        let marked = stateful_dir.is_marked(file_item.path());
+        let mark = match (file_item.error(), marked) {
+            (Some(_), _) => "!",
+            (None, true) => "*",
+            (None, false) => " ",
         };
-        let size = file_item.get_file_item_size();
-        let date = file_item.get_created_date_and_time();
-        let lines = if file_item.kinds() == Kinds::Directory(true)
-            || file_item.kinds() == Kinds::Directory(false)
-        {
-            vec![
-                Span::raw(" "),
-                Span::styled(&dir_symbol, dir_style),
-                Span::styled(name, dir_style),
-                Span::raw(perm),
-                Span::raw(size),
-                Span::raw(date),
-            ]
+        let (symbol, mut style) = if file_item.kinds().is_dir() {
+            (&dir_symbol, dir_style)
         } else {
-            vec![
-                Span::raw(" "),
-                Span::styled(&file_symbol, file_style),
-                Span::styled(name, file_style),
-                Span::raw(perm),
-                Span::raw(size),
-                Span::raw(date),
-            ]
+            (&file_symbol, file_style)
         };
-        Row::new(lines)
+        if name.starts_with('.') {
+            style = style.patch(hidden_style);
+        }
+        match file_item.kinds().link_target() {
+            _ if file_item.error().is_some() => style = style.patch(error_style),
+            Some(target) if target.is_broken() => style = style.patch(error_style),
+            Some(_) => style = style.patch(symlink_style),
+            None => {}
+        }
+        if marked {
+            style = style.patch(marked_style);
+        }
+        let git = app.git_status_of(file_item.path());
+        let mut cells = vec![Cell::from(mark), Cell::from(Span::styled(symbol, style))];
+        if disk_usage {
+            cells.push(Cell::from(usage_bar(app.usage_of(file_item), usage_total)));
+        }
+        let total = app.dir_size_of(file_item);
+        cells.extend(columns.iter().map(|column| {
+            let text = column.cell(file_item, date_format, git, total);
+            match column.kind() {
+                ColumnKind::Name => Cell::from(Span::styled(text, style)),
+                _ => Cell::from(text),
+            }
+        }));
+        let row = Row::new(cells);
+        if marked {
+            row.style(marked_style)
+        } else {
+            row
+        }
     });
 
+    // the preview takes the right half
+    let directory_constraints = match app.preview() {
+        Some(_) => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+        None => vec![Constraint::Percentage(100)],
+    };
     let directory_window = Layout::default()
-        .constraints([Constraint::Percentage(100)])
+        .direction(Direction::Horizontal)
+        .constraints(directory_constraints)
         .split(chunks[1]);
 
     let items = Table::new(file_items_list)
@@ -159,12 +231,115 @@ pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     let dir = app.peek_selected_statefuldir();
     f.render_stateful_widget(items, directory_window[0], &mut dir.state_table());
+
+    if let Some(preview) = app.preview() {
+        let height = directory_window[1].height as usize;
+        let lines: Vec<Spans> = preview
+            .lines()
+            .iter()
+            .take(height)
+            .map(|line| Spans::from(line.as_str()))
+            .collect();
+        let title = preview
+            .path()
+            .and_then(|path| path.file_name())
+            .map(escape_os_str)
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(lines)
+            .style(app.theme().preview_style())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(dir_block_style)
+                    .title(title),
+            );
+        f.render_widget(paragraph, directory_window[1]);
+    }
+
+    if app.which_key_visible() {
+        which_key_ui(f, app, chunks[1]);
+    }
+
+    // remembered so that mouse events can be mapped back to what is under them
+    let tab_titles = match app.mode() {
+        Mode::Normal => tabs,
+        _ => Vec::new(),
+    };
+    let table_area = directory_window[0];
+    let layout = app.mouse_layout_mut();
+    layout.set_tabs(chunks[0], &tab_titles);
+    layout.set_title(table_area, title_parts);
+    // inside the border, below the header and its margin
+    let rows = Rect::new(
+        table_area.x + 1,
+        table_area.y + 3,
+        table_area.width.saturating_sub(2),
+        table_area.height.saturating_sub(4),
+    );
+    layout.set_rows(rows, index_selected, file_items);
+}
+
+const USAGE_BAR_WIDTH: usize = 10;
+// "100.0% [##########]"
+const USAGE_WIDTH: u16 = USAGE_BAR_WIDTH as u16 + 9;
+
+// " 42.0% [####      ]", blank for a directory that isn't added up yet
+fn usage_bar(usage: Option<u64>, total: u64) -> String {
+    let usage = match usage {
+        Some(usage) => usage,
+        None => return String::new(),
+    };
+    let share = match total {
+        0 => 0.0,
+        _ => usage as f64 / total as f64,
+    };
+    let filled = (share * USAGE_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{:>5.1}% [{:<2$}]",
+        share * 100.0,
+        "#".repeat(filled),
+        USAGE_BAR_WIDTH
+    )
+}
+
+// Continuations of the pending key sequence, drawn over the bottom of the directory table
+fn which_key_ui<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let entries = app.which_key_entries();
+    if entries.is_empty() {
+        return;
+    }
+    let key_width = entries.iter().map(|(k, _, _)| k.len()).max().unwrap_or(0) as u16;
+    let cmd_width = entries.iter().map(|(_, c, _)| c.len()).max().unwrap_or(0) as u16;
+    let height = (entries.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(area.x, area.y + area.height - height, area.width, height);
+
+    let rows = entries.into_iter().map(|(keys, cmd, description)| {
+        Row::new(vec![
+            Cell::from(keys).style(app.theme().select_style()),
+            Cell::from(cmd),
+            Cell::from(description),
+        ])
+    });
+    let widths = [
+        Constraint::Length(key_width + 2),
+        Constraint::Length(cmd_width + 2),
+        Constraint::Min(0),
+    ];
+    let title = app.crr_keybinds().pending_notation().unwrap_or_default();
+    let table = Table::new(rows).widths(&widths).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(app.theme().popup_style())
+            .title(title),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(table, popup);
 }
 
 const BLOCK_ELEMENTS: [&str; 7] = [" ", "▁", "▂", "▃", "▄", "▅", "▆"];
 fn command_display_ui<B: Backend>(
     f: &mut Frame<B>,
-    cmd_hist: &[String],
+    cmd_line: Option<&String>,
     cmd_window: Rect,
     cmd_styles: [Style; 3],
     cmd_mode: &Mode,
@@ -200,7 +375,7 @@ fn command_display_ui<B: Backend>(
         .alignment(Alignment::Right);
     f.render_widget(uni_block, cmd_layout[2]);
 
-    if let Some(cmd) = cmd_hist.last() {
+    if let Some(cmd) = cmd_line {
         let para = Paragraph::new(cmd.clone())
             .block(block.clone())
             .style(cmd_style);
@@ -0,0 +1,637 @@
+// Where the items of a directory come from: the local disk, an archive opened
+// like a directory, or memory. A path inside an archive is the path of the archive
+// followed by the path of the entry, so going to the parent works the same way.
+#[cfg(test)]
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::file_item_list::file_item::FileItem;
+use crate::file_item_list::Kinds;
+use crate::path_process::{make_a_info_files_from_dirpath, make_info_files_from_dirpath};
+
+// What a listing shows about an item, whichever backend it came from.
+// Anything the backend doesn't know is None.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Meta {
+    pub len: u64,
+    // st_mode, only the permission bits are looked at
+    pub mode: Option<u32>,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    // when the inode last changed
+    pub changed: Option<SystemTime>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    // the device the item is on, an inode number is only unique on one
+    pub dev: Option<u64>,
+    pub inode: Option<u64>,
+    pub nlink: Option<u64>,
+}
+
+impl Meta {
+    #[cfg(unix)]
+    pub fn from_std(meta: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        use std::time::Duration;
+        let secs = meta.ctime();
+        let nanos = meta.ctime_nsec() as u32;
+        let changed = match secs >= 0 {
+            true => SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos)),
+            false => SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+        };
+        Self {
+            len: meta.len(),
+            mode: Some(meta.mode()),
+            modified: meta.modified().ok(),
+            accessed: meta.accessed().ok(),
+            created: meta.created().ok(),
+            changed,
+            uid: Some(meta.uid()),
+            gid: Some(meta.gid()),
+            dev: Some(meta.dev()),
+            inode: Some(meta.ino()),
+            nlink: Some(meta.nlink()),
+        }
+    }
+
+    // Without unix mode bits only the readonly flag is known
+    #[cfg(not(unix))]
+    pub fn from_std(meta: &Metadata) -> Self {
+        let mode = match meta.permissions().readonly() {
+            true => 0o444,
+            false => 0o644,
+        };
+        Self {
+            len: meta.len(),
+            mode: Some(mode),
+            modified: meta.modified().ok(),
+            accessed: meta.accessed().ok(),
+            created: meta.created().ok(),
+            ..Self::default()
+        }
+    }
+}
+
+pub trait Vfs: Debug + Send + Sync {
+    // The items of `dir` and what couldn't be read, as "name: error"
+    fn read_dir(&self, dir: &Path) -> (Vec<FileItem>, Vec<String>);
+
+    // The item at `path` itself, a link rather than what it points to
+    fn stat(&self, path: &Path) -> io::Result<FileItem>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    // For reading a file a bit at a time
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+
+    // A new file to write a bit at a time, fails when there is one already
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>>;
+
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    // A file, or a directory with everything in it
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    // Something that changes whenever an item is added to or removed from
+    // `dir`, polled to notice changes made elsewhere. None when it can't be told.
+    fn watch_stamp(&self, dir: &Path) -> Option<u64>;
+
+    // Nothing in it can be created, changed or removed
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+pub fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{}: not found", path.display()),
+    )
+}
+
+pub fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{}: already exists", path.display()),
+    )
+}
+
+pub fn read_only_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "read-only")
+}
+
+// The modification time of a local file or directory in nanoseconds
+pub fn local_stamp(path: &Path) -> Option<u64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    let since = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    u64::try_from(since.as_nanos()).ok()
+}
+
+// What copy_items does, collected first so the progress has a total
+struct CopyStep {
+    from: PathBuf,
+    to: PathBuf,
+    // None for a directory
+    len: Option<u64>,
+}
+
+fn plan_copy(
+    from: &dyn Vfs,
+    source: &Path,
+    target: PathBuf,
+    plan: &mut Vec<CopyStep>,
+    skipped: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let item = from.stat(source)?;
+    let len = item.meta().map_or(0, |meta| meta.len);
+    match item.kinds() {
+        Kinds::Directory(_) => {
+            plan.push(CopyStep {
+                from: source.to_path_buf(),
+                to: target.clone(),
+                len: None,
+            });
+            let (items, errors) = from.read_dir(source);
+            if let Some(e) = errors.first() {
+                return Err(io::Error::other(e.clone()));
+            }
+            for item in items {
+                let target = target.join(item.file_name());
+                plan_copy(from, item.path(), target, plan, skipped)?;
+            }
+        }
+        Kinds::File(_) => plan.push(CopyStep {
+            from: source.to_path_buf(),
+            to: target,
+            len: Some(len),
+        }),
+        // copied as the file it leads to, the size is only a guess
+        Kinds::Symlink(_, link) if !link.is_dir() && !link.is_broken() => plan.push(CopyStep {
+            from: source.to_path_buf(),
+            to: target,
+            len: Some(0),
+        }),
+        // links to directories could lead round in circles, devices and fifos
+        // can't be read to the end
+        _ => skipped.push(source.to_path_buf()),
+    }
+    Ok(())
+}
+
+// Copies `sources` into the directory `dest`, which can be on another backend,
+// and returns how many files were copied. Nothing already there is replaced.
+// Links to directories and special files are left out and returned as well.
+pub fn copy_items(
+    from: &dyn Vfs,
+    sources: &[PathBuf],
+    to: &dyn Vfs,
+    dest: &Path,
+    progress: &mut dyn FnMut(u64, u64),
+) -> io::Result<(usize, Vec<PathBuf>)> {
+    let mut plan = Vec::new();
+    let mut skipped = Vec::new();
+    for source in sources {
+        let name = source.file_name().ok_or_else(|| not_found(source))?;
+        plan_copy(from, source, dest.join(name), &mut plan, &mut skipped)?;
+    }
+    // two sources can have the same name, and anything could have been
+    // created in the meantime
+    let mut targets = HashSet::new();
+    for copy in plan.iter() {
+        if !targets.insert(&copy.to) || to.stat(&copy.to).is_ok() {
+            return Err(already_exists(&copy.to));
+        }
+    }
+    let total = plan.iter().filter_map(|copy| copy.len).sum();
+    let count = plan.iter().filter(|copy| copy.len.is_some()).count();
+    let mut done = 0;
+    let mut files = 0;
+    let mut buf = vec![0; 64 * 1024];
+    for copy in plan {
+        // what was copied stays, so the error says how far it got
+        let stopped = |e: io::Error| {
+            let message = format!(
+                "{}: {}, {} of {} files were copied",
+                copy.to.display(),
+                e,
+                files,
+                count
+            );
+            io::Error::new(e.kind(), message)
+        };
+        let len = match copy.len {
+            Some(len) => len,
+            None => {
+                to.create_dir(&copy.to).map_err(stopped)?;
+                continue;
+            }
+        };
+        let mut reader = from.open(&copy.from).map_err(stopped)?;
+        let mut writer = to.create(&copy.to).map_err(stopped)?;
+        // only the planned size counts, a link or a file that grew in the
+        // meantime would take the progress past the total
+        let mut copied = 0;
+        loop {
+            let n = reader.read(&mut buf).map_err(stopped)?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n]).map_err(stopped)?;
+            copied += n as u64;
+            progress(done + copied.min(len), total);
+        }
+        writer.flush().map_err(stopped)?;
+        done += len;
+        files += 1;
+    }
+    Ok((files, skipped))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl Vfs for LocalFs {
+    fn read_dir(&self, dir: &Path) -> (Vec<FileItem>, Vec<String>) {
+        make_info_files_from_dirpath(dir)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileItem> {
+        path.symlink_metadata()?;
+        Ok(make_a_info_files_from_dirpath(path))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        match path.symlink_metadata()?.is_dir() {
+            true => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        }
+    }
+
+    fn watch_stamp(&self, dir: &Path) -> Option<u64> {
+        local_stamp(dir)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum MemNode {
+    Dir,
+    File(Vec<u8>),
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct MemTree {
+    // every item by its full path, the root included
+    nodes: BTreeMap<PathBuf, (MemNode, SystemTime)>,
+    // counts the changes, for watch_stamp
+    version: u64,
+}
+
+// Files kept in memory under `root`, for trying out listings and
+// navigation without touching the disk
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemFs {
+    // shared with the files being written
+    tree: Arc<Mutex<MemTree>>,
+}
+
+// A panic while holding it can't leave the tree half changed
+#[cfg(test)]
+fn lock_tree(tree: &Mutex<MemTree>) -> MutexGuard<'_, MemTree> {
+    tree.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Appends to a file of a MemFs
+#[cfg(test)]
+struct MemWriter {
+    tree: Arc<Mutex<MemTree>>,
+    path: PathBuf,
+}
+
+#[cfg(test)]
+impl Write for MemWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut tree = lock_tree(&self.tree);
+        match tree.nodes.get_mut(&self.path) {
+            Some((MemNode::File(data), _)) => data.extend_from_slice(buf),
+            _ => return Err(not_found(&self.path)),
+        }
+        tree.version += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl MemFs {
+    pub fn new(root: &Path) -> Self {
+        let fs = MemFs::default();
+        fs.lock()
+            .nodes
+            .insert(root.to_path_buf(), (MemNode::Dir, SystemTime::now()));
+        fs
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemTree> {
+        lock_tree(&self.tree)
+    }
+
+    // Creates the file or replaces what is in it
+    pub fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut tree = self.lock();
+        tree.parent_is_dir(path)?;
+        if let Some((MemNode::Dir, _)) = tree.nodes.get(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: is a directory", path.display()),
+            ));
+        }
+        tree.insert(path, MemNode::File(data.to_vec()));
+        Ok(())
+    }
+
+    fn item(path: &Path, node: &MemNode, modified: SystemTime) -> FileItem {
+        let (is_dir, len, mode) = match node {
+            MemNode::Dir => (true, 0, 0o040755),
+            MemNode::File(data) => (false, data.len() as u64, 0o100644),
+        };
+        let meta = Meta {
+            len,
+            mode: Some(mode),
+            modified: Some(modified),
+            ..Meta::default()
+        };
+        FileItem::plain(path.to_path_buf(), meta, is_dir)
+    }
+}
+
+#[cfg(test)]
+impl MemTree {
+    fn parent_is_dir(&self, path: &Path) -> io::Result<()> {
+        let parent = path.parent().ok_or_else(|| not_found(path))?;
+        match self.nodes.get(parent) {
+            Some((MemNode::Dir, _)) => Ok(()),
+            _ => Err(not_found(parent)),
+        }
+    }
+
+    fn insert(&mut self, path: &Path, node: MemNode) {
+        self.nodes
+            .insert(path.to_path_buf(), (node, SystemTime::now()));
+        self.version += 1;
+    }
+
+    // `path` and everything below it
+    fn subtree(&self, path: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .range(path.to_path_buf()..)
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(path))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl Vfs for MemFs {
+    fn read_dir(&self, dir: &Path) -> (Vec<FileItem>, Vec<String>) {
+        let tree = self.lock();
+        if !matches!(tree.nodes.get(dir), Some((MemNode::Dir, _))) {
+            return (Vec::new(), vec![not_found(dir).to_string()]);
+        }
+        let items = tree
+            .nodes
+            .iter()
+            .filter(|(path, _)| path.parent() == Some(dir))
+            .map(|(path, (node, modified))| Self::item(path, node, *modified))
+            .collect();
+        (items, Vec::new())
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileItem> {
+        let tree = self.lock();
+        let (node, modified) = tree.nodes.get(path).ok_or_else(|| not_found(path))?;
+        Ok(Self::item(path, node, *modified))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.lock().nodes.get(path) {
+            Some((MemNode::File(data), _)) => Ok(data.clone()),
+            Some((MemNode::Dir, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: is a directory", path.display()),
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        let mut tree = self.lock();
+        tree.parent_is_dir(path)?;
+        if tree.nodes.contains_key(path) {
+            return Err(already_exists(path));
+        }
+        tree.insert(path, MemNode::File(Vec::new()));
+        Ok(Box::new(MemWriter {
+            tree: self.tree.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        tree.parent_is_dir(path)?;
+        if tree.nodes.contains_key(path) {
+            return Err(already_exists(path));
+        }
+        tree.insert(path, MemNode::Dir);
+        Ok(())
+    }
+
+    // Like rename(2), replacing a file at `to`
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        if !tree.nodes.contains_key(from) {
+            return Err(not_found(from));
+        }
+        tree.parent_is_dir(to)?;
+        if to.starts_with(from) || matches!(tree.nodes.get(to), Some((MemNode::Dir, _))) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't move {} to {}", from.display(), to.display()),
+            ));
+        }
+        for path in tree.subtree(from) {
+            let node = tree.nodes.remove(&path).unwrap();
+            let moved = to.join(path.strip_prefix(from).unwrap());
+            tree.nodes.insert(moved, node);
+        }
+        tree.version += 1;
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        if !tree.nodes.contains_key(path) || path.parent().is_none() {
+            return Err(not_found(path));
+        }
+        for path in tree.subtree(path) {
+            tree.nodes.remove(&path);
+        }
+        tree.version += 1;
+        Ok(())
+    }
+
+    fn watch_stamp(&self, _dir: &Path) -> Option<u64> {
+        Some(self.lock().version)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{copy_items, LocalFs, MemFs, Vfs};
+    use crate::temp_dir::TempDir;
+
+    fn names(fs: &MemFs, dir: &str) -> Vec<String> {
+        let (items, _) = fs.read_dir(Path::new(dir));
+        items.iter().map(|item| item.name()).collect()
+    }
+
+    #[test]
+    fn memory_fs_works_like_a_disk() {
+        let fs = MemFs::new(Path::new("/mem"));
+        let stamp = fs.watch_stamp(Path::new("/mem"));
+        fs.create_dir(Path::new("/mem/src")).unwrap();
+        fs.write(Path::new("/mem/src/main.rs"), b"fn main() {}")
+            .unwrap();
+        fs.write(Path::new("/mem/README"), b"hi").unwrap();
+        assert!(fs.write(Path::new("/mem/none/x"), b"").is_err());
+        assert_ne!(fs.watch_stamp(Path::new("/mem")), stamp);
+
+        assert_eq!(names(&fs, "/mem"), ["README", "src"]);
+        let main = fs.stat(Path::new("/mem/src/main.rs")).unwrap();
+        assert_eq!(main.get_permission(false), "-rw-r--r--");
+        assert!(fs.stat(Path::new("/mem/src")).unwrap().kinds().is_dir());
+
+        fs.rename(Path::new("/mem/src"), Path::new("/mem/lib"))
+            .unwrap();
+        assert_eq!(names(&fs, "/mem/lib"), ["main.rs"]);
+        assert_eq!(
+            fs.read(Path::new("/mem/lib/main.rs")).unwrap(),
+            b"fn main() {}"
+        );
+        assert!(fs
+            .rename(Path::new("/mem/lib"), Path::new("/mem/lib/x"))
+            .is_err());
+
+        fs.remove(Path::new("/mem/lib")).unwrap();
+        assert_eq!(names(&fs, "/mem"), ["README"]);
+        assert!(fs.read(Path::new("/mem/lib/main.rs")).is_err());
+    }
+
+    #[test]
+    fn items_are_copied_between_backends() {
+        let dir = TempDir::new("copy");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "pub fn f() {}").unwrap();
+        std::fs::write(dir.join("notes"), "todo").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("..", dir.join("src/up")).unwrap();
+        // copied as the file, without its bytes counting towards the total
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../notes", dir.join("src/notes")).unwrap();
+
+        let mem = MemFs::new(Path::new("/mem"));
+        let sources = [dir.join("src"), dir.join("notes")];
+        let mut last = (0, 0);
+        let (n, skipped) = copy_items(
+            &LocalFs,
+            &sources,
+            &mem,
+            Path::new("/mem"),
+            &mut |done, total| last = (done, total),
+        )
+        .unwrap();
+        assert_eq!(last, (17, 17));
+        #[cfg(unix)]
+        assert_eq!((n, skipped), (3, vec![dir.join("src/up")]));
+        assert_eq!(
+            mem.read(Path::new("/mem/src/lib.rs")).unwrap(),
+            b"pub fn f() {}"
+        );
+
+        // two items of the same name are refused before anything is copied
+        let other = MemFs::new(Path::new("/other"));
+        #[cfg(unix)]
+        {
+            let twice = [dir.join("notes"), dir.join("src/notes")];
+            assert!(copy_items(
+                &LocalFs,
+                &twice,
+                &other,
+                Path::new("/other"),
+                &mut |_, _| {}
+            )
+            .is_err());
+            assert!(names(&other, "/other").is_empty());
+        }
+        // a copy that fails says how far it got
+        let err = copy_items(
+            &LocalFs,
+            &sources,
+            &other,
+            Path::new("/other/none"),
+            &mut |_, _| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("0 of"));
+
+        // nothing is replaced, on the way back either
+        assert!(copy_items(&LocalFs, &sources, &mem, Path::new("/mem"), &mut |_, _| {}).is_err());
+        let back = [PathBuf::from("/mem/notes")];
+        assert!(copy_items(&mem, &back, &LocalFs, &dir, &mut |_, _| {}).is_err());
+        std::fs::remove_file(dir.join("notes")).unwrap();
+        copy_items(&mem, &back, &LocalFs, &dir, &mut |_, _| {}).unwrap();
+        assert_eq!(std::fs::read(dir.join("notes")).unwrap(), b"todo");
+    }
+}